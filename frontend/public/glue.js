@@ -7,6 +7,9 @@ const listen = window.__TAURI__.event.listen
 const handlersMap = new Map()
 const intervalMap = new Map()
 const litepickerMap = new Map()
+//Each import gets its own id, so its progress events can be told apart and it can be cancelled.
+//Starting somewhere random keeps ids from an earlier page load from being reused while the backend still remembers them.
+let nextImportId = crypto.getRandomValues(new Uint32Array(1))[0]
 
 
 // invoke('my_custom_command_name')
@@ -23,17 +26,123 @@ const unlisten = await listen('solar_parse_complete', (event) => {
     document.documentElement.dispatchEvent(new CustomEvent("solar_parse_complete", {detail: event.payload}));
 });
 
-const unlisten2 = await listen("data_request_complete", (event) => {
-    // console.log("recieved data_request_complete event");
-    let graph_name = event.payload.substring(event.payload.lastIndexOf('\\') + 1, event.payload.length);
-    let data = event.payload.substring(0, event.payload.lastIndexOf('\\'));
-    // console.log("data_request_complete_" + graph_name);
-    document.documentElement.dispatchEvent(new CustomEvent("data_request_complete_" + graph_name, {detail: data}));
+const unlisten6 = await listen("settings_update", (event) => {
+    document.documentElement.dispatchEvent(new CustomEvent("settings_update", {detail: event.payload}));
 });
 
+const unlisten10 = await listen("alerts_triggered", (event) => {
+    document.documentElement.dispatchEvent(new CustomEvent("alerts_triggered", {detail: event.payload}));
+});
+
+const unlisten14 = await listen("import_progress", (event) => {
+    document.documentElement.dispatchEvent(new CustomEvent("import_progress", {detail: event.payload}));
+});
+
+function downloadBlob(blob, file_name) {
+    let link = document.createElement("a");
+    link.href = URL.createObjectURL(blob);
+    link.download = file_name;
+    link.click();
+    URL.revokeObjectURL(link.href);
+}
+
+
+//Commands reject with a serialized CommandError, which the message box shows.
+function dispatchCommandError(error) {
+    document.documentElement.dispatchEvent(new CustomEvent("command_error", {detail: JSON.stringify(error)}));
+}
+
+//Resolves with the command's result as JSON. A failure is shown by the message box like any other, then rejects with the
+// CommandError as JSON so the caller knows it didn't work.
+function invokeForJson(command, args) {
+    return invoke(command, args)
+        .then((result) => JSON.stringify(result))
+        .catch((error) => {
+            dispatchCommandError(error);
+            throw JSON.stringify(error);
+        })
+}
+
+//Resolves with the series in the compact encoding, or rejects with the CommandError as JSON. The graph decides whether the error is worth showing.
+export function retrieveSolarData(json_string, request_id) {
+    return invoke('retrieve_solar_data', {graphStateRequest: json_string, requestId: request_id})
+        .catch((error) => { throw JSON.stringify(error) })
+}
+
+export function cancelDataRequest(graph_id) {
+    invoke('cancel_data_request', {graphId: graph_id}).catch(dispatchCommandError)
+}
+
+export function cancelImport(import_id) {
+    invoke('cancel_import', {importId: import_id}).catch(dispatchCommandError)
+}
+
+export function exportGraphImage(json_string) {
+    return invokeForJson('export_graph_image', {graphExportRequest: json_string})
+}
+
+export function retrieveDailySummary() {
+    return invokeForJson('retrieve_daily_summary')
+}
+
+export function retrieveCellBalanceSummary() {
+    return invokeForJson('retrieve_cell_balance_summary')
+}
+
+export function retrieveCycleSummary() {
+    return invokeForJson('retrieve_cycle_summary')
+}
+
+export function retrieveSettings() {
+    return invokeForJson('retrieve_settings')
+}
+
+export function saveDerivedChannel(json_string, replace) {
+    invoke('save_derived_channel', {derivedChannel: json_string, replace: replace}).catch(dispatchCommandError)
+}
+
+export function removeDerivedChannel(name) {
+    invoke('remove_derived_channel', {name: name}).catch(dispatchCommandError)
+}
+
+export function saveNominalCapacity(capacity) {
+    invoke('save_nominal_capacity', {nominalCapacityAh: capacity ?? null}).catch(dispatchCommandError)
+}
+
+export function saveSolarSite(json_string) {
+    invoke('save_solar_site', {solarSite: json_string}).catch(dispatchCommandError)
+}
+
+export function saveAlertRule(json_string, replace) {
+    invoke('save_alert_rule', {alertRule: json_string, replace: replace}).catch(dispatchCommandError)
+}
+
+export function removeAlertRule(name) {
+    invoke('remove_alert_rule', {name: name}).catch(dispatchCommandError)
+}
+
+export function retrieveAlerts() {
+    return invokeForJson('retrieve_alerts')
+}
+
+export function retrieveChargeStageSummary() {
+    return invokeForJson('retrieve_charge_stage_summary')
+}
+
+export function retrieveAnomalies() {
+    return invokeForJson('retrieve_anomalies')
+}
+
+export function copyTextToClipboard(text) {
+    navigator.clipboard.writeText(text);
+}
+
+export function downloadTextFile(file_name, mime_type, contents) {
+    downloadBlob(new Blob([contents], {type: mime_type}), file_name);
+}
 
-export function retrieveSolarData(json_string) {
-    invoke('retrieve_solar_data', {graphStateRequest: json_string})
+export function downloadBytes(file_name, mime_type, contents) {
+    downloadBlob(new Blob([contents], {type: mime_type}), file_name);
 }
 
 export function readFile(file) {
@@ -46,7 +155,9 @@ export function readFile(file) {
             //Accepted case
             (contents) => {
                 //invoke function which passes data to backend
-                invoke('parse_solar_data', {name: file.name, data: contents});
+                let importId = nextImportId;
+                nextImportId = (nextImportId + 1) >>> 0;
+                invoke('parse_solar_data', {name: file.name, data: contents, importId: importId}).catch(dispatchCommandError);
             },
             //Rejected case
             (err) => {
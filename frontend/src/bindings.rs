@@ -1,8 +1,12 @@
+use std::future::Future;
+
+use serde::de::DeserializeOwned;
 use thiserror::Error;
 use wasm_bindgen::prelude::*;
 use web_sys::{File, Element};
 
-use crate::component::visual::theme_data::{ThemeData, Color};
+use shared::{command_error::CommandError, graph::{graph_theme::{ThemeData, Color}, graph_export::GraphExportReturnValue}, settings::Settings, solar_data::{summary::DailySummary, cell_balance::CellBalanceRow, cycles::CycleSummary, charge_stage::ChargeStageDay, alerts::AlertEvent, anomalies::Anomaly}};
+use yew::{Component, html::Scope};
 
 #[wasm_bindgen(module = "/public/glue.js")]
 extern "C" {
@@ -58,8 +62,66 @@ extern "C" {
     #[wasm_bindgen (js_name = teardownCanvasEvents)]
     pub fn teardown_canvas_events(canvas_id: String);
 
-    #[wasm_bindgen (js_name = retrieveSolarData)]
-    pub fn retrieve_solar_data(json_string: String);
+    #[wasm_bindgen (js_name = retrieveSolarData, catch)]
+    pub async fn retrieve_solar_data(json_string: String, request_id: u32) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = cancelDataRequest)]
+    pub fn cancel_data_request(graph_id: String);
+
+    #[wasm_bindgen (js_name = cancelImport)]
+    pub fn cancel_import(import_id: u32);
+
+    //These resolve with the command's result as JSON, see parse_command_result.
+    #[wasm_bindgen (js_name = exportGraphImage, catch)]
+    async fn export_graph_image_js(json_string: String) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = retrieveDailySummary, catch)]
+    async fn retrieve_daily_summary_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = retrieveCellBalanceSummary, catch)]
+    async fn retrieve_cell_balance_summary_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = retrieveCycleSummary, catch)]
+    async fn retrieve_cycle_summary_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = retrieveSettings, catch)]
+    async fn retrieve_settings_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = saveDerivedChannel)]
+    pub fn save_derived_channel(json_string: String, replace: bool);
+
+    #[wasm_bindgen (js_name = removeDerivedChannel)]
+    pub fn remove_derived_channel(name: String);
+
+    #[wasm_bindgen (js_name = saveNominalCapacity)]
+    pub fn save_nominal_capacity(capacity: Option<f64>);
+
+    #[wasm_bindgen (js_name = saveSolarSite)]
+    pub fn save_solar_site(json_string: String);
+
+    #[wasm_bindgen (js_name = saveAlertRule)]
+    pub fn save_alert_rule(json_string: String, replace: bool);
+
+    #[wasm_bindgen (js_name = removeAlertRule)]
+    pub fn remove_alert_rule(name: String);
+
+    #[wasm_bindgen (js_name = retrieveAlerts, catch)]
+    async fn retrieve_alerts_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = retrieveChargeStageSummary, catch)]
+    async fn retrieve_charge_stage_summary_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = retrieveAnomalies, catch)]
+    async fn retrieve_anomalies_js() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen (js_name = copyTextToClipboard)]
+    pub fn copy_text_to_clipboard(text: String);
+
+    #[wasm_bindgen (js_name = downloadTextFile)]
+    pub fn download_text_file(file_name: String, mime_type: String, contents: String);
+
+    #[wasm_bindgen (js_name = downloadBytes)]
+    pub fn download_bytes(file_name: String, mime_type: String, contents: &[u8]);
 
     #[wasm_bindgen (js_name = setupGraphDatePicker)]
     pub fn setup_graph_date_picker(picker_id: String, start_time: i64, end_time: i64);
@@ -109,6 +171,59 @@ pub fn read_file(file: File) -> Result<(), ReadFileError> {
 
 }
 
+//A rejected command has already been shown by the message box, so callers only need to know that it failed.
+fn parse_command_result<T: DeserializeOwned>(result: Result<JsValue, JsValue>) -> Result<T, CommandError> {
+    match result {
+        Ok(payload) => serde_json::from_str::<T>(&payload.as_string().unwrap_or_default()).map_err(|e| CommandError::Serialization(e.to_string())),
+        Err(e) => {
+            let error = e.as_string().unwrap_or_default();
+            Err(serde_json::from_str::<CommandError>(&error).unwrap_or(CommandError::InvalidRequest(error)))
+        },
+    }
+}
+
+//Runs a command in the background and hands its result to the component as a message.
+pub fn send_command_result<C: Component, T: 'static>(link: &Scope<C>, command: impl Future<Output = Result<T, CommandError>> + 'static, message: impl FnOnce(T) -> C::Message + 'static) {
+    let link = link.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Ok(result) = command.await {
+            link.send_message(message(result));
+        }
+    });
+}
+
+pub async fn export_graph_image(json_string: String) -> Result<GraphExportReturnValue, CommandError> {
+    parse_command_result(export_graph_image_js(json_string).await)
+}
+
+pub async fn retrieve_daily_summary() -> Result<Vec<DailySummary>, CommandError> {
+    parse_command_result(retrieve_daily_summary_js().await)
+}
+
+pub async fn retrieve_cell_balance_summary() -> Result<Vec<CellBalanceRow>, CommandError> {
+    parse_command_result(retrieve_cell_balance_summary_js().await)
+}
+
+pub async fn retrieve_cycle_summary() -> Result<CycleSummary, CommandError> {
+    parse_command_result(retrieve_cycle_summary_js().await)
+}
+
+pub async fn retrieve_charge_stage_summary() -> Result<Vec<ChargeStageDay>, CommandError> {
+    parse_command_result(retrieve_charge_stage_summary_js().await)
+}
+
+pub async fn retrieve_alerts() -> Result<Vec<AlertEvent>, CommandError> {
+    parse_command_result(retrieve_alerts_js().await)
+}
+
+pub async fn retrieve_anomalies() -> Result<Vec<Anomaly>, CommandError> {
+    parse_command_result(retrieve_anomalies_js().await)
+}
+
+pub async fn retrieve_settings() -> Result<Settings, CommandError> {
+    parse_command_result(retrieve_settings_js().await)
+}
+
 pub fn get_theme() -> Theme {
     let theme = get_theme_js();
     if theme == "theme-light" {
@@ -130,6 +245,7 @@ pub fn get_theme_data() -> Result<ThemeData, Box<dyn std::error::Error>> {
         theme_graph_mesh_light: Color::from_hex_code(get_style(":root".to_string(), "--theme-graph-mesh-light".to_string()).ok_or("Could not get style")?.as_str())?,
         theme_graph_mesh_dark: Color::from_hex_code(get_style(":root".to_string(), "--theme-graph-mesh-dark".to_string()).ok_or("Could not get style")?.as_str())?,
         theme_graph_border: Color::from_hex_code(get_style(":root".to_string(), "--theme-graph-border".to_string()).ok_or("Could not get style")?.as_str())?,
+        theme_graph_anomaly: Color::from_hex_code(get_style(":root".to_string(), "--theme-graph-anomaly".to_string()).ok_or("Could not get style")?.as_str())?,
     })
 }
 
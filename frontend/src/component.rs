@@ -1,5 +1,7 @@
 pub mod file_handling;
 pub mod message_handling;
 pub mod graph_handling;
+pub mod report_handling;
+pub mod settings_handling;
 pub mod visual;
 pub mod control;
\ No newline at end of file
@@ -1,3 +1,4 @@
 //All components related to receiving files
 pub mod file_drag;
-pub mod file_select;
\ No newline at end of file
+pub mod file_select;
+pub mod import_progress;
\ No newline at end of file
@@ -0,0 +1,110 @@
+use gloo_events::EventListener;
+use shared::parse::utils::{ImportProgress, ImportState};
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::CustomEvent;
+use yew::prelude::*;
+
+use crate::{bindings, component::message_handling::simple_message::SimpleMessageProperties, component_channel::ComponentChannelTx};
+
+
+//A progress bar for each file being imported, with a button to stop it.
+pub struct ImportProgressList {
+    imports: Vec<ImportProgress>,
+    progress_listener: Option<EventListener>,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct ImportProgressListProps {
+    pub notification_tx: ComponentChannelTx<SimpleMessageProperties>,
+}
+
+pub enum ImportProgressListMessage {
+    Progress(ImportProgress),
+    Cancel(u32),
+}
+
+impl Component for ImportProgressList {
+    type Message = ImportProgressListMessage;
+    type Properties = ImportProgressListProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            imports: Vec::new(),
+            progress_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::Progress(progress) => {
+                if progress.is_finished() {
+                    self.imports.retain(|import| import.import_id != progress.import_id);
+                    //Failures are already shown as errors by the message box.
+                    let message = match progress.state {
+                        ImportState::Complete => format!("Imported {}: {} lines, {} rejected.", progress.name, progress.lines_processed, progress.lines_rejected),
+                        ImportState::Cancelled => format!("Cancelled importing {}, none of its data was kept.", progress.name),
+                        _ => return true,
+                    };
+                    let message = SimpleMessageProperties { class: AttrValue::from("notification"), message: AttrValue::from(message) };
+                    if let Err(e) = ctx.props().notification_tx.try_send(message) {
+                        web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(e.to_string().as_str()));
+                    }
+                    return true;
+                }
+                match self.imports.iter_mut().find(|import| import.import_id == progress.import_id) {
+                    Some(import) => *import = progress,
+                    None => self.imports.push(progress),
+                }
+            },
+            Self::Message::Cancel(import_id) => {
+                bindings::cancel_import(import_id);
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html!(
+            <div class="import-progress-list">
+                {self.imports.iter().map(|import| {
+                    let import_id = import.import_id;
+                    let oncancel = ctx.link().callback(move |_e: MouseEvent| Self::Message::Cancel(import_id));
+                    html!(
+                        <div class="import-progress">
+                            <span class="import-progress-name">{import.name.clone()}</span>
+                            <button class="graph-button" onclick={oncancel}>{"Cancel"}</button>
+                            <progress max="1" value={import.get_fraction().to_string()}/>
+                            <span>{format!("{:.0}%, {} lines, {} rejected", import.get_fraction() * 100.0, import.lines_processed, import.lines_rejected)}</span>
+                        </div>
+                    )
+                }).collect::<Html>()}
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_progress = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail();
+
+            let progress = serde_json::from_str::<ImportProgress>(payload.as_string().unwrap().as_ref());
+
+            Self::Message::Progress(progress.unwrap_throw())
+        });
+
+        let progress_listener = EventListener::new(
+            &root,
+            "import_progress",
+            move |e| on_progress.emit(e.clone())
+        );
+
+        self.progress_listener = Some(progress_listener);
+    }
+}
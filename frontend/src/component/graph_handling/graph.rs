@@ -5,21 +5,28 @@ pub mod y_axis_controls;
 pub mod secondary_y_axis_controls;
 pub mod time_range_controls;
 pub mod graph_coordination;
+pub mod export_controls;
+pub mod graph_type_controls;
+pub mod trend_line_controls;
+pub mod night_shading_controls;
+pub mod transform_controls;
+pub mod statistics_panel;
 
 use std::ops::Range;
 
 use gloo_events::EventListener;
-use shared::{graph::{graph_axis::{LineSeriesHolder, AxisControlsRequest, AxisTimeRequest}, graph_type::GraphType, graph_state_request::GraphStateRequest}, parse::utils::ParseCompleteReturnValue, solar_data::{cell::AvailableCells, controllers::AvailableControllers}};
+use shared::{graph::{graph_axis::{LineSeriesHolder, AxisControlsRequest, AxisTimeRequest, AxisDataType}, graph_type::GraphType, graph_regression::{TrendLine, SeriesTrendFit}, graph_draw::GraphRanges, graph_state_request::{GraphStateRequest, NightShading}, graph_export::{GraphExportRequest, GraphExportReturnValue}, graph_statistics::SeriesStatistics, graph_transport::decode_line_series}, parse::utils::ParseCompleteReturnValue, settings::Settings, command_error::CommandError, solar_data::{cell::AvailableCells, controllers::AvailableControllers, anomalies::Anomaly}};
 use wasm_bindgen::{UnwrapThrowExt, JsCast};
 use web_sys::{HtmlElement, CustomEvent};
 use yew::prelude::*;
 
-use crate::{bindings, component::{message_handling::simple_message::SimpleMessageProperties, graph_handling::graph::{time_range_controls::TimeRangeSelector, x_axis_controls::XAxisControls, y_axis_controls::YAxisControls, secondary_y_axis_controls::SecYAxisControls}}, component_channel::ComponentChannelTx};
+use crate::{bindings, component::{message_handling::simple_message::SimpleMessageProperties, graph_handling::graph::{time_range_controls::TimeRangeSelector, x_axis_controls::XAxisControls, y_axis_controls::YAxisControls, secondary_y_axis_controls::SecYAxisControls, export_controls::{ExportControls, GraphExportSettings}, graph_type_controls::GraphTypeControls, trend_line_controls::TrendLineControls, night_shading_controls::NightShadingControls, statistics_panel::StatisticsPanel}}, component_channel::ComponentChannelTx};
 
 
 pub struct Graph {
     available_cells: AvailableCells,
     available_controllers: AvailableControllers,
+    settings: Settings,
     graph_state: GraphStateRequest,
     line_series: LineSeriesHolder,
     // _context_handle: ContextHandle<Rc<GraphState>>,
@@ -28,8 +35,17 @@ pub struct Graph {
     previous_y_range: Option<Range<f64>>,
     previous_sec_y_range: Option<Range<f64>>,
     markpoints: Vec<(f64, f64)>,
+    anomalies: Vec<Anomaly>,
+    statistics_visible: bool,
+    statistics: Vec<SeriesStatistics>,
+    //Fitted to the same visible points as the trend lines on the graph.
+    trend_fits: Vec<SeriesTrendFit>,
+    //The ranges the statistics were last taken over. None when they need taking again.
+    statistics_ranges: Option<GraphRanges>,
     parse_complete_listener: Option<EventListener>,
-    data_complete_listener: Option<EventListener>,
+    //The id of the newest data request. Responses to any earlier request are dropped.
+    data_request_id: u32,
+    settings_listener: Option<EventListener>,
     pub canvas_node_ref: NodeRef,
     pub draw_listener: Option<EventListener>,
 }
@@ -50,12 +66,21 @@ pub enum GraphMessage {
     MouseMovement(MouseInput),
     MouseExit,
     ParseComplete(ParseCompleteReturnValue),
-    NewData(LineSeriesHolder),
+    NewData(u32, LineSeriesHolder),
+    DataRequestFailed(u32, CommandError),
+    NewSettings(Settings),
+    NewAnomalies(Vec<Anomaly>),
     TimeControlsUpdate(AxisTimeRequest),
     XAxisControlsUpdate(AxisControlsRequest),
     YAxisControlsUpdate(AxisControlsRequest),
     SecYAxisControlsUpdate(AxisControlsRequest),
+    GraphTypeUpdate(Option<GraphType>),
+    TrendLinesUpdate(Vec<TrendLine>),
+    NightShadingUpdate(NightShading),
+    ToggleStatistics,
     UpdateGraphData,
+    ExportGraph(GraphExportSettings),
+    ExportComplete(GraphExportReturnValue),
 }
 
 
@@ -86,6 +111,7 @@ impl Component for Graph {
         Self {
             available_cells: AvailableCells::default(),
             available_controllers: AvailableControllers::default(),
+            settings: Settings::default(),
             graph_state: GraphStateRequest::default_with_name(ctx.props().canvas_id.to_string()),
             line_series: LineSeriesHolder::default(),
             // _context_handle: _context_handle,
@@ -96,8 +122,14 @@ impl Component for Graph {
             previous_y_range: None,
             previous_sec_y_range: None,
             markpoints: Vec::new(),
+            anomalies: Vec::new(),
+            statistics_visible: false,
+            statistics: Vec::new(),
+            trend_fits: Vec::new(),
+            statistics_ranges: None,
             parse_complete_listener: None,
-            data_complete_listener: None,
+            data_request_id: 0,
+            settings_listener: None,
         }
     }
 
@@ -114,7 +146,8 @@ impl Component for Graph {
                     let error = wasm_bindgen::JsValue::from_str(e.to_string().as_str());
                     web_sys::console::error_1(&error);
                 }
-                return false;
+                //Panning and zooming only redraw the canvas, so this is where the statistics find out the visible range changed.
+                return self.update_statistics();
             },
             GraphMessage::MouseClick(mouse_input) => {
                 // Disabling this for now. There's no good way to label the markpoints using plotters without manually doing the dynamic styling and placement,
@@ -239,23 +272,64 @@ impl Component for Graph {
                     web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(format!("{:?}", e).as_str()));
                 }
                 // self.app_state.notification_callback.clone().expect("Notification callback must be set").emit(message);
-                bindings::retrieve_solar_data(serde_json::to_string(&self.graph_state).unwrap());
+                self.request_data(ctx);
+                bindings::send_command_result(ctx.link(), bindings::retrieve_anomalies(), GraphMessage::NewAnomalies);
+                return false;
+            },
+            GraphMessage::NewData(request_id, _data) if request_id != self.data_request_id => {
                 return false;
             },
-            GraphMessage::NewData(data) => {
+            GraphMessage::NewData(_request_id, data) => {
                 // web_sys::console::info_1(&wasm_bindgen::JsValue::from_str(format!("{:?}", &data).as_str()));
                 self.line_series = data;
+                self.statistics_ranges = None;
                 self.previous_x_range = None;
                 self.previous_y_range = None;
                 self.previous_sec_y_range = None;
                 return false;
             },
+            GraphMessage::DataRequestFailed(request_id, e) => {
+                //Replaced requests fail on purpose when the backend stops them.
+                if request_id == self.data_request_id && !matches!(e, CommandError::Superseded(_)) {
+                    let error = SimpleMessageProperties { 
+                        class: AttrValue::from("error"), 
+                        message: AttrValue::from(format!("Could not retrieve graph data: {}", e)),
+                    };
+                    if let Err(e) = ctx.props().notification_tx.try_send(error) {
+                        web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(format!("{:?}", e).as_str()));
+                    }
+                }
+                return false;
+            },
+            GraphMessage::ToggleStatistics => {
+                self.statistics_visible = !self.statistics_visible;
+                self.statistics_ranges = None;
+                self.update_statistics();
+            },
+            GraphMessage::NewAnomalies(anomalies) => {
+                //Picked up by the next redraw.
+                self.anomalies = anomalies;
+                return false;
+            },
+            GraphMessage::NewSettings(settings) => {
+                //Derived channels, state of health and the clear sky channels depend on the settings, so any already on the graph need new data.
+                let uses_settings = self.graph_state.x_axis.requests.iter()
+                    .chain(self.graph_state.y_axis.0.requests.iter())
+                    .chain(self.graph_state.y_axis.1.requests.iter())
+                    .any(|(data_type, _)| matches!(data_type, AxisDataType::Custom(_) | AxisDataType::StateOfHealthPercent
+                        | AxisDataType::ExpectedSolarWatts | AxisDataType::ExpectedControllerAmps(_) | AxisDataType::PerformanceRatioPercent));
+                let update = uses_settings && self.settings != settings;
+                self.settings = settings;
+                if update {
+                    ctx.link().callback(|_| {GraphMessage::UpdateGraphData}).emit(());
+                }
+            },
             GraphMessage::TimeControlsUpdate(new_time_frame) => {
                 // web_sys::console::info_1(&wasm_bindgen::JsValue::from_str("NewDateRange callback called"));
                 let update = new_time_frame.start < self.graph_state.time_frame.start || new_time_frame.end > self.graph_state.time_frame.end;
                 self.graph_state.time_frame = new_time_frame;
                 // if update {
-                    bindings::retrieve_solar_data(serde_json::to_string(&self.graph_state).unwrap())
+                    self.request_data(ctx)
                 // }
             },
             GraphMessage::XAxisControlsUpdate(new_x_axis) => {
@@ -279,8 +353,61 @@ impl Component for Graph {
                     ctx.link().callback(|_| {GraphMessage::UpdateGraphData}).emit(());
                 }
             },
+            GraphMessage::GraphTypeUpdate(new_graph_type) => {
+                //Histograms are binned in the backend, so switching to or from one needs new data.
+                self.graph_state.graph_type = new_graph_type;
+                ctx.link().callback(|_| {GraphMessage::UpdateGraphData}).emit(());
+            },
+            GraphMessage::TrendLinesUpdate(new_trend_lines) => {
+                //Trend lines are fitted while drawing, so the data doesn't change.
+                self.graph_state.trend_lines = new_trend_lines;
+                self.statistics_ranges = None;
+                self.update_statistics();
+            },
+            GraphMessage::NightShadingUpdate(new_night_shading) => {
+                //Nights are worked out while drawing, from the site in the settings.
+                self.graph_state.night_shading = new_night_shading;
+            },
             GraphMessage::UpdateGraphData => {
-                bindings::retrieve_solar_data(serde_json::to_string(&self.graph_state).unwrap())
+                self.request_data(ctx)
+            },
+            GraphMessage::ExportGraph(settings) => {
+                let theme = match bindings::get_theme_data() {
+                    Ok(theme) => theme,
+                    Err(e) => {
+                        let error = SimpleMessageProperties { 
+                            class: AttrValue::from("error"), 
+                            message: AttrValue::from(format!("Could not export graph: {}", e)),
+                        };
+                        if let Err(e) = ctx.props().notification_tx.try_send(error) {
+                            web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(format!("{:?}", e).as_str()));
+                        }
+                        return false;
+                    },
+                };
+                let (width, height) = settings.size.unwrap_or((
+                    bindings::get_canvas_width(ctx.props().canvas_id.to_string()) as u32,
+                    bindings::get_canvas_height(ctx.props().canvas_id.to_string()) as u32,
+                ));
+                let request = GraphExportRequest {
+                    file_name: ctx.props().canvas_id.to_string(),
+                    format: settings.format,
+                    width,
+                    height,
+                    graph_state: self.graph_state.clone(),
+                    line_series: self.line_series.clone(),
+                    ranges: self.get_graph_ranges(),
+                    markpoints: self.markpoints.clone(),
+                    theme,
+                    solar_site: self.settings.solar_site.clone(),
+                    anomalies: self.anomalies.clone(),
+                };
+                bindings::send_command_result(ctx.link(), bindings::export_graph_image(serde_json::to_string(&request).unwrap()), GraphMessage::ExportComplete);
+                return false;
+            },
+            GraphMessage::ExportComplete(exported_graph) => {
+                bindings::download_bytes(exported_graph.file_name, exported_graph.mime_type, &exported_graph.data);
+                return false;
             },
         }
         true
@@ -358,6 +485,26 @@ impl Component for Graph {
             Self::Message::SecYAxisControlsUpdate(sec_y_axis_controls_request)
         });
 
+        let ongraphtypechange = ctx.link().callback(|graph_type: Option<GraphType>| {
+            Self::Message::GraphTypeUpdate(graph_type)
+        });
+
+        let ontrendlineschange = ctx.link().callback(|trend_lines: Vec<TrendLine>| {
+            Self::Message::TrendLinesUpdate(trend_lines)
+        });
+
+        let onnightshadingchange = ctx.link().callback(|night_shading: NightShading| {
+            Self::Message::NightShadingUpdate(night_shading)
+        });
+
+        let onexport = ctx.link().callback(|settings: GraphExportSettings| {
+            Self::Message::ExportGraph(settings)
+        });
+
+        let ontogglestatistics = ctx.link().callback(|_| {
+            Self::Message::ToggleStatistics
+        });
+
         html!(
             <div class="graph">
                 <div id={ctx.props().canvas_container_id.to_string()}>
@@ -365,11 +512,21 @@ impl Component for Graph {
                     </canvas>
                 </div>
                 <div class="graph-controls">
-                    <TimeRangeSelector current_date_range={AxisTimeRequest {start: self.graph_state.time_frame.start, end: self.graph_state.time_frame.end, manual_resolution: self.graph_state.time_frame.manual_resolution.clone()}} id={format!("{}_litepicker", ctx.props().canvas_id)} callback={onnewtimeframe}/>
-                    <XAxisControls current_state={self.graph_state.x_axis.clone()} callback={onnewxaxisrequest} available_cells={self.available_cells.clone()} available_controllers={self.available_controllers.clone()} />
-                    <YAxisControls current_state={self.graph_state.y_axis.0.clone()} callback={onnewyaxisrequest} available_cells={self.available_cells.clone()} available_controllers={self.available_controllers.clone()} />
-                    <SecYAxisControls current_state={self.graph_state.y_axis.1.clone()} callback={onnewsecyaxisrequest} available_cells={self.available_cells.clone()} available_controllers={self.available_controllers.clone()} />
+                    <TimeRangeSelector current_date_range={self.graph_state.time_frame.clone()} id={format!("{}_litepicker", ctx.props().canvas_id)} callback={onnewtimeframe}/>
+                    <XAxisControls current_state={self.graph_state.x_axis.clone()} callback={onnewxaxisrequest} available_cells={self.available_cells.clone()} available_controllers={self.available_controllers.clone()} derived_channels={self.settings.derived_channels.clone()} />
+                    <YAxisControls current_state={self.graph_state.y_axis.0.clone()} callback={onnewyaxisrequest} available_cells={self.available_cells.clone()} available_controllers={self.available_controllers.clone()} derived_channels={self.settings.derived_channels.clone()} />
+                    <SecYAxisControls current_state={self.graph_state.y_axis.1.clone()} callback={onnewsecyaxisrequest} available_cells={self.available_cells.clone()} available_controllers={self.available_controllers.clone()} derived_channels={self.settings.derived_channels.clone()} />
+                    <GraphTypeControls current_graph_type={self.graph_state.graph_type.clone()} callback={ongraphtypechange}/>
+                    if self.graph_state.get_graph_type() == GraphType::XAxisLine {
+                        <NightShadingControls current_night_shading={self.graph_state.night_shading} has_solar_site={self.settings.solar_site.is_some()} callback={onnightshadingchange}/>
+                    }
+                    if self.graph_state.get_graph_type() == GraphType::XYScatter {
+                        <TrendLineControls current_trend_lines={self.graph_state.trend_lines.clone()} callback={ontrendlineschange}/>
+                    }
+                    <ExportControls callback={onexport}/>
                 </div>
+                <StatisticsPanel statistics={self.statistics.clone()} trend_fits={self.trend_fits.clone()} visible={self.statistics_visible} ontoggle={ontogglestatistics} 
+                    available={matches!(self.graph_state.get_graph_type(), GraphType::XAxisLine | GraphType::XYScatter)}/>
             </div>
         )
     }
@@ -419,23 +576,26 @@ impl Component for Graph {
         
         self.parse_complete_listener = Some(parse_listener);
 
-        let on_new_data = ctx.link().callback(|e: Event| {
-            // web_sys::console::info_1(&wasm_bindgen::JsValue::from_str("on_new_data callback called"));
+        let on_settings = ctx.link().callback(|e: Event| {
             let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
             let payload = casted_event.detail();
 
-            let data = serde_json::from_str::<LineSeriesHolder>(payload.as_string().unwrap().as_ref());
+            let settings = serde_json::from_str::<Settings>(payload.as_string().unwrap().as_ref());
 
-            Self::Message::NewData(data.unwrap_throw())
+            Self::Message::NewSettings(settings.unwrap_throw())
         });
 
-        let data_listener = EventListener::new(
+        let settings_listener = EventListener::new(
             &root, 
-            format!("data_request_complete_{}", ctx.props().canvas_id), 
-            move |e| on_new_data.emit(e.clone())
+            "settings_update", 
+            move |e| on_settings.emit(e.clone())
         );
 
-        self.data_complete_listener = Some(data_listener);
+        self.settings_listener = Some(settings_listener);
+
+        //Settings updates are only pushed when something changes, so the current ones are asked for once.
+        bindings::send_command_result(ctx.link(), bindings::retrieve_settings(), Self::Message::NewSettings);
+        bindings::send_command_result(ctx.link(), bindings::retrieve_anomalies(), Self::Message::NewAnomalies);
 
         ctx.link().callback(|_| {
             Self::Message::UpdateGraphData
@@ -444,5 +604,29 @@ impl Component for Graph {
 
     fn destroy(&mut self, ctx: &Context<Self>) {
         bindings::teardown_canvas_events(ctx.props().canvas_id.to_string());
+        bindings::cancel_data_request(ctx.props().canvas_id.to_string());
+    }
+}
+
+impl Graph {
+    //Each request replaces the one before it. The backend stops working on replaced requests, and any response that still arrives for one is dropped.
+    fn request_data(&mut self, ctx: &Context<Self>) {
+        self.data_request_id = self.data_request_id.wrapping_add(1);
+        let request_id = self.data_request_id;
+        let request = serde_json::to_string(&self.graph_state).unwrap();
+        let link = ctx.link().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let message = match bindings::retrieve_solar_data(request, request_id).await {
+                Ok(payload) => match decode_line_series(&payload.as_string().unwrap_or_default()) {
+                    Ok(data) => GraphMessage::NewData(request_id, data),
+                    Err(e) => GraphMessage::DataRequestFailed(request_id, CommandError::Serialization(e.to_string())),
+                },
+                Err(e) => {
+                    let error = e.as_string().unwrap_or_default();
+                    GraphMessage::DataRequestFailed(request_id, serde_json::from_str::<CommandError>(&error).unwrap_or(CommandError::InvalidRequest(error)))
+                },
+            };
+            link.send_message(message);
+        });
     }
 }
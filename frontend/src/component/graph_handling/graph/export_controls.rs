@@ -0,0 +1,80 @@
+use shared::graph::graph_export::ExportFormat;
+use wasm_bindgen::UnwrapThrowExt;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+pub struct ExportControls {
+    format_node_ref: NodeRef,
+    size_node_ref: NodeRef,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct ExportControlsProps {
+    pub callback: Callback<GraphExportSettings>,
+}
+
+pub enum ExportControlsMessage {
+    Export,
+}
+
+//The user's choices for an export. A size of None means the export should match the size of the graph on screen.
+pub struct GraphExportSettings {
+    pub format: ExportFormat,
+    pub size: Option<(u32, u32)>,
+}
+
+impl Component for ExportControls {
+    type Message = ExportControlsMessage;
+    type Properties = ExportControlsProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        ExportControls {
+            format_node_ref: NodeRef::default(),
+            size_node_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::Export => {
+                let format_element = self.format_node_ref.cast::<HtmlSelectElement>().unwrap_throw();
+                let size_element = self.size_node_ref.cast::<HtmlSelectElement>().unwrap_throw();
+                let format = match format_element.value().as_ref() {
+                    "png" => ExportFormat::Png,
+                    _ => ExportFormat::Svg,
+                };
+                let size = match size_element.value().as_ref() {
+                    "1280x720" => Some((1280, 720)),
+                    "1920x1080" => Some((1920, 1080)),
+                    "3840x2160" => Some((3840, 2160)),
+                    _ => None,
+                };
+                ctx.props().callback.emit(GraphExportSettings { format, size });
+            },
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onexport = ctx.link().callback(|_e: MouseEvent| {
+            Self::Message::Export
+        });
+
+        html!(
+            <div>
+                <p>{"Export"}</p>
+                <select class="graph-dropdown" ref={self.format_node_ref.clone()}>
+                    <option value={"svg"} selected=true>{"SVG"}</option>
+                    <option value={"png"}>{"PNG"}</option>
+                </select>
+                <select class="graph-dropdown" ref={self.size_node_ref.clone()}>
+                    <option value={"current"} selected=true>{"Current Size"}</option>
+                    <option value={"1280x720"}>{"1280 x 720"}</option>
+                    <option value={"1920x1080"}>{"1920 x 1080"}</option>
+                    <option value={"3840x2160"}>{"3840 x 2160"}</option>
+                </select>
+                <button class="graph-button" onclick={onexport}>{"Export Image"}</button>
+            </div>
+        )
+    }
+}
@@ -1,21 +1,11 @@
+use shared::graph::graph_draw::{CHART_MARGIN_SIZE, CHART_LABEL_SIZE};
 use yew::Context;
 
 use crate::bindings::{get_canvas_width, get_canvas_height};
 
-use super::{Graph, graph_draw::{CHART_MARGIN_SIZE, CHART_LABEL_SIZE}};
+use super::Graph;
 
 
-pub fn time_axis_label_formatter(unix_time: &f64) -> String {
-    time::OffsetDateTime::from_unix_timestamp(unix_time.clone() as i64)
-        .expect("All stored timestamps are valid")
-        .format(time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]"))
-        .expect("Given format is verified during compilation")
-}
-
-pub fn other_axis_label_formatter(data: &f64) -> String {
-    format!("{:.0}", data)
-}
-
 impl Graph {
     pub fn convert_local_x_y_to_graph_x_y(&self, ctx: &Context<Self>, x: f64, y: f64) -> (Option<f64>, Option<f64>) {
         let x = if x.is_finite() {
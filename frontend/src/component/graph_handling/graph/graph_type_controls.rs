@@ -0,0 +1,117 @@
+use shared::graph::graph_type::{GraphType, HistogramOptions, HistogramBins, HistogramMeasure};
+use wasm_bindgen::UnwrapThrowExt;
+use web_sys::{HtmlSelectElement, HtmlInputElement};
+use yew::prelude::*;
+
+pub struct GraphTypeControls {
+    type_node_ref: NodeRef,
+    bins_node_ref: NodeRef,
+    bin_value_node_ref: NodeRef,
+    measure_node_ref: NodeRef,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct GraphTypeControlsProps {
+    pub callback: Callback<Option<GraphType>>,
+    //None means the graph type is picked from the x axis.
+    pub current_graph_type: Option<GraphType>,
+}
+
+pub enum GraphTypeControlsMessage {
+    Changed,
+}
+
+impl Component for GraphTypeControls {
+    type Message = GraphTypeControlsMessage;
+    type Properties = GraphTypeControlsProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        GraphTypeControls {
+            type_node_ref: NodeRef::default(),
+            bins_node_ref: NodeRef::default(),
+            bin_value_node_ref: NodeRef::default(),
+            measure_node_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::Changed => {
+                let type_element = self.type_node_ref.cast::<HtmlSelectElement>().unwrap_throw();
+                let graph_type = match type_element.value().as_ref() {
+                    "scatter" => Some(GraphType::XYScatter),
+                    "histogram" => Some(GraphType::Histogram(self.get_histogram_options())),
+                    "heatmap" => Some(GraphType::Heatmap),
+                    _ => None,
+                };
+                if graph_type != ctx.props().current_graph_type {
+                    ctx.props().callback.emit(graph_type);
+                }
+            },
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onchange = ctx.link().callback(|_event: Event| {
+            Self::Message::Changed
+        });
+        let current_graph_type = &ctx.props().current_graph_type;
+
+        html!(
+            <div>
+                <p>{"Graph Type"}</p>
+                <select class="graph-dropdown" onchange={onchange.clone()} ref={self.type_node_ref.clone()}>
+                    <option value={"auto"} selected={current_graph_type.is_none()}>{"From X Axis"}</option>
+                    <option value={"scatter"} selected={*current_graph_type == Some(GraphType::XYScatter)}>{"Scatter"}</option>
+                    <option value={"histogram"} selected={matches!(current_graph_type, Some(GraphType::Histogram(_)))}>{"Histogram"}</option>
+                    <option value={"heatmap"} selected={*current_graph_type == Some(GraphType::Heatmap)}>{"Day x Hour Heatmap"}</option>
+                </select>
+                if let Some(GraphType::Histogram(options)) = current_graph_type {
+                    //Histograms are built from the primary y axis channels.
+                    <select class="graph-dropdown" onchange={onchange.clone()} ref={self.bins_node_ref.clone()}>
+                        <option value={"count"} selected={matches!(options.bins, HistogramBins::Count(_))}>{"Bin Count"}</option>
+                        <option value={"width"} selected={matches!(options.bins, HistogramBins::Width(_))}>{"Bin Width"}</option>
+                    </select>
+                    <input class="graph-text-input" type="number" min="0" step="any" onchange={onchange.clone()} ref={self.bin_value_node_ref.clone()}
+                        value={match options.bins {
+                            HistogramBins::Count(count) => count.to_string(),
+                            HistogramBins::Width(width) => width.to_string(),
+                        }}/>
+                    <select class="graph-dropdown" onchange={onchange} ref={self.measure_node_ref.clone()}>
+                        <option value={"count"} selected={options.measure == HistogramMeasure::Count}>{"Count"}</option>
+                        <option value={"hours"} selected={options.measure == HistogramMeasure::Hours}>{"Hours"}</option>
+                    </select>
+                }
+            </div>
+        )
+    }
+}
+
+impl GraphTypeControls {
+    //The histogram controls only exist once histogram has been picked, so the first pick gets the defaults.
+    fn get_histogram_options(&self) -> HistogramOptions {
+        let (bins_element, bin_value_element, measure_element) = match (
+            self.bins_node_ref.cast::<HtmlSelectElement>(),
+            self.bin_value_node_ref.cast::<HtmlInputElement>(),
+            self.measure_node_ref.cast::<HtmlSelectElement>()
+        ) {
+            (Some(bins_element), Some(bin_value_element), Some(measure_element)) => (bins_element, bin_value_element, measure_element),
+            _ => return HistogramOptions::default(),
+        };
+        //Values that don't parse fall back to the default bins, rather than leaving the graph empty.
+        let bins = match bins_element.value().as_ref() {
+            "width" => bin_value_element.value().parse::<f64>().ok()
+                .filter(|width| *width > 0f64 && width.is_finite())
+                .map(HistogramBins::Width),
+            _ => bin_value_element.value().parse::<u16>().ok()
+                .filter(|count| *count > 0)
+                .map(HistogramBins::Count),
+        }.unwrap_or_default();
+        let measure = match measure_element.value().as_ref() {
+            "hours" => HistogramMeasure::Hours,
+            _ => HistogramMeasure::Count,
+        };
+        HistogramOptions { bins, measure }
+    }
+}
@@ -0,0 +1,69 @@
+use shared::graph::graph_state_request::NightShading;
+use wasm_bindgen::UnwrapThrowExt;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+pub struct NightShadingControls {
+    select_node_ref: NodeRef,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct NightShadingControlsProps {
+    pub callback: Callback<NightShading>,
+    pub current_night_shading: NightShading,
+    //Night can't be worked out without a site, so the controls say so instead.
+    pub has_solar_site: bool,
+}
+
+pub enum NightShadingControlsMessage {
+    Changed,
+}
+
+impl Component for NightShadingControls {
+    type Message = NightShadingControlsMessage;
+    type Properties = NightShadingControlsProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        NightShadingControls {
+            select_node_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::Changed => {
+                let element = self.select_node_ref.cast::<HtmlSelectElement>().unwrap_throw();
+                let night_shading = match element.value().as_ref() {
+                    "shade" => NightShading::Shade,
+                    "mark" => NightShading::ShadeAndMark,
+                    _ => NightShading::Off,
+                };
+                if night_shading != ctx.props().current_night_shading {
+                    ctx.props().callback.emit(night_shading);
+                }
+            },
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onchange = ctx.link().callback(|_event: Event| {
+            Self::Message::Changed
+        });
+        let current_night_shading = ctx.props().current_night_shading;
+
+        html!(
+            <div>
+                <p>{"Night"}</p>
+                <select class="graph-dropdown" onchange={onchange} ref={self.select_node_ref.clone()}>
+                    <option value={"off"} selected={current_night_shading == NightShading::Off}>{"Not Shown"}</option>
+                    <option value={"shade"} selected={current_night_shading == NightShading::Shade}>{"Shade Night"}</option>
+                    <option value={"mark"} selected={current_night_shading == NightShading::ShadeAndMark}>{"Shade Night, Mark Sunrise/Sunset"}</option>
+                </select>
+                if !ctx.props().has_solar_site && current_night_shading != NightShading::Off {
+                    <p class="settings-error">{"Enter a solar site in the settings to show night."}</p>
+                }
+            </div>
+        )
+    }
+}
@@ -0,0 +1,123 @@
+use shared::graph::{graph_statistics::{SeriesStatistics, statistics_to_tsv}, graph_regression::SeriesTrendFit};
+use yew::prelude::*;
+
+use crate::bindings;
+
+pub struct StatisticsPanel {}
+
+#[derive(PartialEq, Properties)]
+pub struct StatisticsPanelProps {
+    pub statistics: Vec<SeriesStatistics>,
+    pub trend_fits: Vec<SeriesTrendFit>,
+    pub visible: bool,
+    pub ontoggle: Callback<()>,
+    //Histograms and heatmaps don't have series over time to take statistics of.
+    pub available: bool,
+}
+
+pub enum StatisticsPanelMessage {
+    Toggle,
+    Copy,
+}
+
+impl Component for StatisticsPanel {
+    type Message = StatisticsPanelMessage;
+    type Properties = StatisticsPanelProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        StatisticsPanel {}
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::Toggle => ctx.props().ontoggle.emit(()),
+            Self::Message::Copy => bindings::copy_text_to_clipboard(statistics_to_tsv(&ctx.props().statistics)),
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let ontoggle = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::Toggle
+        });
+        let oncopy = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::Copy
+        });
+        let optional = |value: Option<f64>| value.map(|value| format!("{:.3}", value)).unwrap_or_default();
+
+        html!(
+            <div class="graph-statistics">
+                <div class="report-header">
+                    <button class="graph-button" onclick={ontoggle}>{if props.visible { "Hide Visible Range Statistics" } else { "Show Visible Range Statistics" }}</button>
+                    if props.visible && props.available {
+                        <button class="graph-button" onclick={oncopy}>{"Copy Table"}</button>
+                    }
+                </div>
+                if props.visible && !props.available {
+                    <p>{"Statistics are only available for line and scatter graphs."}</p>
+                }
+                if props.visible && props.available {
+                    <table class="report-table">
+                        <thead>
+                            <tr>
+                                <th>{"Series"}</th>
+                                <th>{"Count"}</th>
+                                <th>{"Min"}</th>
+                                <th>{"Max"}</th>
+                                <th>{"Mean"}</th>
+                                <th>{"Time Weighted Mean"}</th>
+                                <th>{"Std Dev"}</th>
+                                <th>{"Integral"}</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {props.statistics.iter().map(|row| {
+                                html!(
+                                    <tr>
+                                        <td>{row.name.clone()}</td>
+                                        <td>{row.count}</td>
+                                        <td>{format!("{:.3}", row.minimum)}</td>
+                                        <td>{format!("{:.3}", row.maximum)}</td>
+                                        <td>{format!("{:.3}", row.mean)}</td>
+                                        <td>{optional(row.time_weighted_mean)}</td>
+                                        <td>{format!("{:.3}", row.standard_deviation)}</td>
+                                        <td>{row.integral.as_ref().map(|(value, unit)| format!("{:.3} {}", value, unit)).unwrap_or_default()}</td>
+                                    </tr>
+                                )
+                            }).collect::<Html>()}
+                        </tbody>
+                    </table>
+                    if !props.trend_fits.is_empty() {
+                        <table class="report-table">
+                            <thead>
+                                <tr>
+                                    <th>{"Series"}</th>
+                                    <th>{"Trend Line"}</th>
+                                    <th>{"Equation"}</th>
+                                    <th>{"Coefficients"}</th>
+                                    <th>{"R²"}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {props.trend_fits.iter().map(|row| {
+                                    //Unrounded, since the equation already shows them to three places.
+                                    let coefficients = row.fit.coefficients.iter().map(|coefficient| coefficient.to_string()).collect::<Vec<_>>().join(", ");
+                                    html!(
+                                        <tr>
+                                            <td>{row.name.clone()}</td>
+                                            <td>{row.fit.trend_line.get_name()}</td>
+                                            <td>{row.fit.get_equation()}</td>
+                                            <td>{coefficients}</td>
+                                            <td>{format!("{:.4}", row.fit.r_squared)}</td>
+                                        </tr>
+                                    )
+                                }).collect::<Html>()}
+                            </tbody>
+                        </table>
+                    }
+                }
+            </div>
+        )
+    }
+}
@@ -1,7 +1,7 @@
 use std::rc::Rc;
 
 use gloo_events::EventListener;
-use shared::graph::{graph_axis::AxisTimeRequest, graph_state_request::Resolution};
+use shared::graph::{graph_aggregation::BucketFillPolicy, graph_axis::AxisTimeRequest, graph_comparison::ComparisonShift, graph_fold::PeriodicDisplay, graph_state_request::Resolution};
 use wasm_bindgen::{UnwrapThrowExt, JsCast};
 use web_sys::{CustomEvent, HtmlSelectElement};
 use yew::prelude::*;
@@ -14,6 +14,9 @@ pub struct TimeRangeSelector {
     input_node_ref: NodeRef,
     input_listener: Option<EventListener>,
     select_node_ref: NodeRef,
+    fill_select_node_ref: NodeRef,
+    periodic_select_node_ref: NodeRef,
+    comparison_select_node_ref: NodeRef,
     copy_state: Option<(Rc<Option<SharableGraphData>>, Callback<Rc<Option<SharableGraphData>>>)>,
     _context_handle: Option<ContextHandle<(Rc<Option<SharableGraphData>>, Callback<Rc<Option<SharableGraphData>>>)>>,
 }
@@ -28,6 +31,11 @@ pub struct TimeRangeSelectorProps {
 pub enum TimeRangeSelectorMessage {
     NewTimeFrame(i64,i64),
     NewResolution(Option<Resolution>),
+    NewFillPolicy(BucketFillPolicy),
+    NewPeriodicDisplay(PeriodicDisplay),
+    //Index into the comparison presets.
+    AddComparison(usize),
+    RemoveComparison(usize),
     ContextChanged((Rc<Option<SharableGraphData>>, Callback<Rc<Option<SharableGraphData>>>)),
     Copy,
     Paste,
@@ -48,6 +56,9 @@ impl Component for TimeRangeSelector {
             input_node_ref: NodeRef::default(),
             input_listener: None,
             select_node_ref: NodeRef::default(),
+            fill_select_node_ref: NodeRef::default(),
+            periodic_select_node_ref: NodeRef::default(),
+            comparison_select_node_ref: NodeRef::default(),
             copy_state,
             _context_handle,
         }
@@ -61,6 +72,9 @@ impl Component for TimeRangeSelector {
                     start, 
                     end, 
                     manual_resolution: props.current_date_range.manual_resolution.clone(), 
+                    fill_policy: props.current_date_range.fill_policy.clone(),
+                    periodic_display: props.current_date_range.periodic_display,
+                    comparisons: props.current_date_range.comparisons.clone(),
                 });
             },
             Self::Message::NewResolution(manual_resolution) => {
@@ -68,8 +82,46 @@ impl Component for TimeRangeSelector {
                     start: props.current_date_range.start, 
                     end: props.current_date_range.end, 
                     manual_resolution,
+                    fill_policy: props.current_date_range.fill_policy.clone(),
+                    periodic_display: props.current_date_range.periodic_display,
+                    comparisons: props.current_date_range.comparisons.clone(),
                 });
             },
+            Self::Message::NewFillPolicy(fill_policy) => {
+                props.callback.emit(AxisTimeRequest { 
+                    start: props.current_date_range.start, 
+                    end: props.current_date_range.end, 
+                    manual_resolution: props.current_date_range.manual_resolution.clone(),
+                    fill_policy,
+                    periodic_display: props.current_date_range.periodic_display,
+                    comparisons: props.current_date_range.comparisons.clone(),
+                });
+            },
+            Self::Message::NewPeriodicDisplay(periodic_display) => {
+                props.callback.emit(AxisTimeRequest { 
+                    start: props.current_date_range.start, 
+                    end: props.current_date_range.end, 
+                    manual_resolution: props.current_date_range.manual_resolution.clone(),
+                    fill_policy: props.current_date_range.fill_policy.clone(),
+                    periodic_display,
+                    comparisons: props.current_date_range.comparisons.clone(),
+                });
+            },
+            Self::Message::AddComparison(preset) => {
+                let mut time_frame = props.current_date_range.clone();
+                match ComparisonShift::get_presets().get(preset) {
+                    Some(shift) if !time_frame.comparisons.contains(shift) => time_frame.comparisons.push(*shift),
+                    _ => return false,
+                }
+                props.callback.emit(time_frame);
+            },
+            Self::Message::RemoveComparison(index) => {
+                let mut time_frame = props.current_date_range.clone();
+                if index < time_frame.comparisons.len() {
+                    time_frame.comparisons.remove(index);
+                    props.callback.emit(time_frame);
+                }
+            },
             Self::Message::ContextChanged(new_copy_state) => {
                 self.copy_state = Some(new_copy_state);
                 return true;
@@ -108,6 +160,35 @@ impl Component for TimeRangeSelector {
             };
             Self::Message::NewResolution(resolution)
         });
+        let reference = self.fill_select_node_ref.clone();
+        let onfillpolicychange = ctx.link().callback(move |_event: Event| {
+            let element = reference.cast::<HtmlSelectElement>().unwrap_throw();
+            let fill_policy = match element.value().as_ref() {
+                "previous" => BucketFillPolicy::Previous,
+                "linear" => BucketFillPolicy::Linear,
+                _ => BucketFillPolicy::Drop,
+            };
+            Self::Message::NewFillPolicy(fill_policy)
+        });
+        let fill_policy = &props.current_date_range.fill_policy;
+        let reference = self.periodic_select_node_ref.clone();
+        let onperiodicdisplaychange = ctx.link().callback(move |_event: Event| {
+            let element = reference.cast::<HtmlSelectElement>().unwrap_throw();
+            let periodic_display = match element.value().as_ref() {
+                "envelope" => PeriodicDisplay::Envelope,
+                _ => PeriodicDisplay::Overlay,
+            };
+            Self::Message::NewPeriodicDisplay(periodic_display)
+        });
+        let periodic_display = props.current_date_range.periodic_display;
+        let reference = self.comparison_select_node_ref.clone();
+        let oncomparisonadd = ctx.link().batch_callback(move |_event: Event| {
+            let element = reference.cast::<HtmlSelectElement>().unwrap_throw();
+            let preset = element.value().parse::<usize>().ok();
+            //The dropdown goes back to its placeholder, so the same comparison can be picked again after it's removed.
+            element.set_value("none");
+            preset.map(Self::Message::AddComparison)
+        });
         let oncopypaste = ctx.link().callback(|msg| {
             match msg {
                 Request::Copy => Self::Message::Copy,
@@ -138,6 +219,28 @@ impl Component for TimeRangeSelector {
                     <option value={"1_hour"}>{"1 Hour"}</option>
                     <option value={"1_day"}>{"1 Day"}</option>
                 </select>
+                //What to do with buckets where only one of the axes has data.
+                <select class="graph-dropdown" onchange={onfillpolicychange} ref={self.fill_select_node_ref.clone()}>
+                    <option value={"drop"} selected={*fill_policy == BucketFillPolicy::Drop}>{"Drop Gaps"}</option>
+                    <option value={"previous"} selected={*fill_policy == BucketFillPolicy::Previous}>{"Fill Previous"}</option>
+                    <option value={"linear"} selected={*fill_policy == BucketFillPolicy::Linear}>{"Fill Linear"}</option>
+                </select>
+                //How periods are drawn when the x axis is periodic time.
+                <select class="graph-dropdown" onchange={onperiodicdisplaychange} ref={self.periodic_select_node_ref.clone()}>
+                    <option value={"overlay"} selected={periodic_display == PeriodicDisplay::Overlay}>{"Overlay Periods"}</option>
+                    <option value={"envelope"} selected={periodic_display == PeriodicDisplay::Envelope}>{"Min / Mean / Max"}</option>
+                </select>
+                //Earlier periods overlaid on this one.
+                <select class="graph-dropdown" onchange={oncomparisonadd} ref={self.comparison_select_node_ref.clone()}>
+                    <option value={"none"} selected=true>{"Compare With..."}</option>
+                    {ComparisonShift::get_presets().iter().enumerate().map(|(index, shift)| {
+                        html!(<option value={index.to_string()}>{shift.get_name()}</option>)
+                    }).collect::<Html>()}
+                </select>
+                {props.current_date_range.comparisons.iter().enumerate().map(|(index, shift)| {
+                    let onremove = ctx.link().callback(move |_event: MouseEvent| Self::Message::RemoveComparison(index));
+                    html!(<button class="graph-button" onclick={onremove}>{format!("Remove {}", shift.get_name())}</button>)
+                }).collect::<Html>()}
                 <CopyPaste copy_visible={true} paste_visible={paste_visible} callback={oncopypaste}/>
             </div>
         )
@@ -0,0 +1,118 @@
+use shared::graph::{graph_axis::{AxisControlsRequest, AxisDataOption}, graph_transform::SeriesTransform};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+pub struct TransformControls {}
+
+#[derive(PartialEq, Properties)]
+pub struct TransformControlsProps {
+    pub current_state: AxisControlsRequest,
+    pub callback: Callback<AxisControlsRequest>,
+}
+
+pub enum TransformControlsMessage {
+    //Index of the series, and the index of the transform in the selectable transforms. None for no transform.
+    TransformChanged(usize, Option<usize>),
+    ParameterChanged(usize, String),
+    PercentileChanged(usize, String),
+}
+
+impl Component for TransformControls {
+    type Message = TransformControlsMessage;
+    type Properties = TransformControlsProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        TransformControls {}
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let mut request = ctx.props().current_state.clone();
+        match msg {
+            Self::Message::TransformChanged(series, transform) => {
+                let (data_type, data_option) = match request.requests.get(series) {
+                    Some(requested) => requested.clone(),
+                    None => return false,
+                };
+                let transform = transform.and_then(|index| SeriesTransform::get_selectable_transforms().get(index).copied());
+                request.set_transform(&data_type, &data_option, transform);
+            },
+            Self::Message::ParameterChanged(series, parameter) => {
+                let (data_type, data_option) = match request.requests.get(series) {
+                    Some(requested) => requested.clone(),
+                    None => return false,
+                };
+                //Unfinished input is ignored until it's a number.
+                let (transform, parameter) = match (request.get_transform(&data_type, &data_option), parameter.parse::<u16>()) {
+                    (Some(transform), Ok(parameter)) => (transform, parameter),
+                    _ => return false,
+                };
+                request.set_transform(&data_type, &data_option, Some(transform.with_parameter(parameter)));
+            },
+            Self::Message::PercentileChanged(series, percentile) => {
+                //Unfinished input is ignored until it's a number, as is a percentile the series already has.
+                match percentile.parse::<u8>() {
+                    Ok(percentile) => { request.set_percentile(series, percentile); },
+                    Err(_) => return false,
+                }
+            },
+        }
+        if request != ctx.props().current_state {
+            ctx.props().callback.emit(request);
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let current_state = &ctx.props().current_state;
+        let selectable_transforms = SeriesTransform::get_selectable_transforms();
+
+        html!(
+            <div>
+                if !current_state.requests.is_empty() {
+                    <p>{"Transforms"}</p>
+                }
+                {current_state.requests.iter().enumerate().map(|(series, (data_type, data_option))| {
+                    let transform = current_state.get_transform(data_type, data_option);
+                    let ontransformchange = ctx.link().callback(move |event: Event| {
+                        let select: HtmlSelectElement = event.target_unchecked_into();
+                        Self::Message::TransformChanged(series, select.value().parse::<usize>().ok())
+                    });
+                    let onparameterchange = ctx.link().callback(move |event: Event| {
+                        let input: HtmlInputElement = event.target_unchecked_into();
+                        Self::Message::ParameterChanged(series, input.value())
+                    });
+                    let onpercentilechange = ctx.link().callback(move |event: Event| {
+                        let input: HtmlInputElement = event.target_unchecked_into();
+                        Self::Message::PercentileChanged(series, input.value())
+                    });
+                    let selected_index = transform.and_then(|transform| {
+                        selectable_transforms.iter().position(|selectable| std::mem::discriminant(selectable) == std::mem::discriminant(&transform))
+                    });
+                    html!(
+                        <div class="transform-control">
+                            <span>{format!("{} {}", data_type.get_name(), data_option.get_name())}</span>
+                            if let AxisDataOption::Percentile(percentile) = data_option {
+                                <input class="graph-text-input" type="number" min="0" max="100" value={percentile.to_string()} onchange={onpercentilechange}/>
+                            }
+                            <select class="graph-dropdown" onchange={ontransformchange}>
+                                <option value={"none"} selected={transform.is_none()}>{"None"}</option>
+                                {selectable_transforms.iter().enumerate().map(|(index, selectable)| {
+                                    let name = match selectable {
+                                        SeriesTransform::MovingAverage(_) => "Moving Average",
+                                        SeriesTransform::ExponentialSmoothing(_) => "Exponential Smoothing (%)",
+                                        SeriesTransform::MedianFilter(_) => "Median Filter",
+                                        SeriesTransform::Derivative => "Rate of Change per Hour",
+                                    };
+                                    html!(<option value={index.to_string()} selected={selected_index == Some(index)}>{name}</option>)
+                                }).collect::<Html>()}
+                            </select>
+                            if let Some(parameter) = transform.and_then(|transform| transform.get_parameter()) {
+                                <input class="graph-text-input" type="number" min="1" value={parameter.to_string()} onchange={onparameterchange}/>
+                            }
+                        </div>
+                    )
+                }).collect::<Html>()}
+            </div>
+        )
+    }
+}
@@ -0,0 +1,94 @@
+use shared::graph::graph_regression::{TrendLine, MAX_POLYNOMIAL_DEGREE};
+use wasm_bindgen::UnwrapThrowExt;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+const DEFAULT_POLYNOMIAL_DEGREE: u8 = 2;
+
+pub struct TrendLineControls {
+    linear_node_ref: NodeRef,
+    polynomial_node_ref: NodeRef,
+    degree_node_ref: NodeRef,
+    exponential_node_ref: NodeRef,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct TrendLineControlsProps {
+    pub callback: Callback<Vec<TrendLine>>,
+    pub current_trend_lines: Vec<TrendLine>,
+}
+
+pub enum TrendLineControlsMessage {
+    Changed,
+}
+
+impl Component for TrendLineControls {
+    type Message = TrendLineControlsMessage;
+    type Properties = TrendLineControlsProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        TrendLineControls {
+            linear_node_ref: NodeRef::default(),
+            polynomial_node_ref: NodeRef::default(),
+            degree_node_ref: NodeRef::default(),
+            exponential_node_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::Changed => {
+                let is_checked = |node_ref: &NodeRef| node_ref.cast::<HtmlInputElement>().unwrap_throw().checked();
+                //Degrees out of range are clamped rather than dropping the polynomial fit.
+                let degree = self.degree_node_ref.cast::<HtmlInputElement>().unwrap_throw().value().parse::<u8>()
+                    .unwrap_or(DEFAULT_POLYNOMIAL_DEGREE)
+                    .clamp(2, MAX_POLYNOMIAL_DEGREE);
+                let mut trend_lines = Vec::new();
+                if is_checked(&self.linear_node_ref) {
+                    trend_lines.push(TrendLine::Linear);
+                }
+                if is_checked(&self.polynomial_node_ref) {
+                    trend_lines.push(TrendLine::Polynomial(degree));
+                }
+                if is_checked(&self.exponential_node_ref) {
+                    trend_lines.push(TrendLine::Exponential);
+                }
+                if trend_lines != ctx.props().current_trend_lines {
+                    ctx.props().callback.emit(trend_lines);
+                }
+            },
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onchange = ctx.link().callback(|_event: Event| {
+            Self::Message::Changed
+        });
+        let current_trend_lines = &ctx.props().current_trend_lines;
+        let current_degree = current_trend_lines.iter().find_map(|trend_line| match trend_line {
+            TrendLine::Polynomial(degree) => Some(*degree),
+            _ => None,
+        });
+
+        html!(
+            <div>
+                <p>{"Trend Lines"}</p>
+                <label class="graph-checkbox-label">
+                    <input type="checkbox" onchange={onchange.clone()} ref={self.linear_node_ref.clone()} checked={current_trend_lines.contains(&TrendLine::Linear)}/>
+                    {TrendLine::Linear.get_name()}
+                </label>
+                <label class="graph-checkbox-label">
+                    <input type="checkbox" onchange={onchange.clone()} ref={self.polynomial_node_ref.clone()} checked={current_degree.is_some()}/>
+                    {"Polynomial"}
+                </label>
+                <input class="graph-text-input" type="number" min="2" max={MAX_POLYNOMIAL_DEGREE.to_string()} step="1" onchange={onchange.clone()} ref={self.degree_node_ref.clone()}
+                    value={current_degree.unwrap_or(DEFAULT_POLYNOMIAL_DEGREE).to_string()}/>
+                <label class="graph-checkbox-label">
+                    <input type="checkbox" onchange={onchange} ref={self.exponential_node_ref.clone()} checked={current_trend_lines.contains(&TrendLine::Exponential)}/>
+                    {TrendLine::Exponential.get_name()}
+                </label>
+            </div>
+        )
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, rc::Rc};
 
-use shared::{solar_data::{cell::AvailableCells, controllers::AvailableControllers}, graph::graph_axis::{AxisDataOption, AxisDataType, AxisControlsRequest, DataUnit}};
+use shared::{solar_data::{cell::AvailableCells, controllers::AvailableControllers, expression::DerivedChannel}, graph::{graph_axis::{AxisDataOption, AxisDataType, AxisControlsRequest, DataUnit}, graph_fold::Period}};
 use yew::prelude::*;
 
 use crate::component::control::{modal_window::ModalWindow, channel::Channel, channel_checkbox::ChannelCheckbox, copy_paste::{CopyPaste, Request}};
@@ -19,6 +19,7 @@ pub struct XAxisControlsProps {
     pub callback: Callback<AxisControlsRequest>,
     pub available_cells: AvailableCells,
     pub available_controllers: AvailableControllers,
+    pub derived_channels: Vec<DerivedChannel>,
 }
 
 pub enum XAxisControlsMessage {
@@ -91,6 +92,7 @@ impl Component for XAxisControls {
                             {generate_x_axis_time_controls(&props.current_state)}
                             {generate_y_axis_system_controls(&props.available_cells, &props.current_state)}
                             {generate_y_axis_controller_controls(&props.available_controllers, &props.current_state)}
+                            {generate_derived_channel_controls(&props.derived_channels, &props.current_state)}
                         </Channel<(),DataUnit,(AxisDataType,AxisDataOption)>>
                     </div>
                 </ModalWindow>
@@ -143,7 +145,7 @@ pub fn generate_x_axis_time_controls(current_state: &AxisControlsRequest) -> Htm
     let active_checkboxes = current_state.requests.iter().filter_map(|(data_type, data_option)| {
         match data_type {
             AxisDataType::Time
-            | AxisDataType::PeriodicTime => Some((data_type.clone(), data_option.clone())),
+            | AxisDataType::PeriodicTime(_) => Some((data_type.clone(), data_option.clone())),
             _ => None,
         }
     }).collect::<Vec<_>>();
@@ -151,7 +153,16 @@ pub fn generate_x_axis_time_controls(current_state: &AxisControlsRequest) -> Htm
         <>
         <p>{"Time"}</p>
             {get_channel_checkbox(&active_checkboxes, AxisDataType::Time, AxisDataOption::Average)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::PeriodicTime, AxisDataOption::Average)}
+        <p>{"Periodic Time"}</p>
+            {[Period::Day, Period::Week, Period::Year].into_iter().map(|period| {
+                let axis_type = AxisDataType::PeriodicTime(period);
+                html!(
+                    <label class="graph-checkbox-label">
+                        {get_channel_checkbox(&active_checkboxes, axis_type.clone(), AxisDataOption::Average)}
+                        {axis_type.get_name()}
+                    </label>
+                )
+            }).collect::<Html>()}
         </>
     )
 }
@@ -164,40 +175,64 @@ pub fn generate_y_axis_system_controls(cells: &AvailableCells, current_state: &A
             | AxisDataType::SolarWatts 
             | AxisDataType::LoadWatts 
             | AxisDataType::StateOfChargePercent 
-            | AxisDataType::CellVoltage(_) => Some((data_type.clone(), data_option.clone())),
+            | AxisDataType::SolarEnergy
+            | AxisDataType::LoadEnergy
+            | AxisDataType::SolarEnergyTotal
+            | AxisDataType::LoadEnergyTotal
+            | AxisDataType::BatteryCapacity
+            | AxisDataType::StateOfHealthPercent
+            | AxisDataType::ExpectedSolarWatts
+            | AxisDataType::PerformanceRatioPercent
+            | AxisDataType::CellSpread
+            | AxisDataType::HighestCell
+            | AxisDataType::LowestCell
+            | AxisDataType::CellVoltage(_)
+            | AxisDataType::CellDeviation(_) => Some((data_type.clone(), data_option.clone())),
             _ => None,
         }
     }).collect::<Vec<_>>();
     html!(
         <>
         <p>{"Battery Voltage"}</p>
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::BatteryVoltage, AxisDataOption::Average)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::BatteryVoltage, AxisDataOption::Minimum)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::BatteryVoltage, AxisDataOption::Maximum)}
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::BatteryVoltage)}
         <p>{"Battery Amps"}</p>
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::BatteryAmps, AxisDataOption::Average)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::BatteryAmps, AxisDataOption::Minimum)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::BatteryAmps, AxisDataOption::Maximum)}
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::BatteryAmps)}
         <p>{"Solar Watts"}</p>
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::SolarWatts, AxisDataOption::Average)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::SolarWatts, AxisDataOption::Minimum)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::SolarWatts, AxisDataOption::Maximum)}
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::SolarWatts)}
         <p>{"Load Watts"}</p>
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::LoadWatts, AxisDataOption::Average)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::LoadWatts, AxisDataOption::Minimum)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::LoadWatts, AxisDataOption::Maximum)}
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::LoadWatts)}
         <p>{"State of Charge Percent"}</p>
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::StateOfChargePercent, AxisDataOption::Average)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::StateOfChargePercent, AxisDataOption::Minimum)}
-            {get_channel_checkbox(&active_checkboxes, AxisDataType::StateOfChargePercent, AxisDataOption::Maximum)}
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::StateOfChargePercent)}
+        <p>{"Solar Energy (kWh per bucket)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::SolarEnergy)}
+        <p>{"Load Energy (kWh per bucket)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::LoadEnergy)}
+        <p>{"Solar Energy Total (kWh)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::SolarEnergyTotal)}
+        <p>{"Load Energy Total (kWh)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::LoadEnergyTotal)}
+        <p>{"Battery Capacity (monthly estimate)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::BatteryCapacity)}
+        <p>{"State of Health % (monthly estimate)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::StateOfHealthPercent)}
+        <p>{"Expected Solar Watts (clear sky)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::ExpectedSolarWatts)}
+        <p>{"Performance Ratio % (daily)"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::PerformanceRatioPercent)}
+        <p>{"Cell Spread"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::CellSpread)}
+        <p>{"Highest Cell"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::HighestCell)}
+        <p>{"Lowest Cell"}</p>
+            {get_channel_checkboxes(&active_checkboxes, AxisDataType::LowestCell)}
 
             {cells.get_cells().iter().map(|cell| {
                 html!(
                     <>
                     <p>{format!("Cell #{} Voltage", cell)}</p>
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::CellVoltage(*cell), AxisDataOption::Average)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::CellVoltage(*cell), AxisDataOption::Minimum)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::CellVoltage(*cell), AxisDataOption::Maximum)}
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::CellVoltage(*cell))}
+                    <p>{format!("Cell #{} Deviation From Pack Mean", cell)}</p>
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::CellDeviation(*cell))}
                     </>
                 )
             }).collect::<Html>()}
@@ -210,7 +245,9 @@ pub fn generate_y_axis_controller_controls(controllers: &AvailableControllers, c
         match data_type {
             AxisDataType::ControllerPanelVoltage(_) 
             | AxisDataType::ControllerAmps(_)
-            | AxisDataType::ControllerTemperatureF(_) => Some((data_type.clone(), data_option.clone())),
+            | AxisDataType::ExpectedControllerAmps(_)
+            | AxisDataType::ControllerTemperatureF(_)
+            | AxisDataType::ChargeStage(_) => Some((data_type.clone(), data_option.clone())),
             _ => None,
         }
     }).collect::<Vec<_>>();
@@ -220,17 +257,36 @@ pub fn generate_y_axis_controller_controls(controllers: &AvailableControllers, c
                 html!(
                     <>
                     <p>{format!("Controller #{} Pannel Voltage", controller)}</p>
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerPanelVoltage(*controller), AxisDataOption::Average)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerPanelVoltage(*controller), AxisDataOption::Minimum)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerPanelVoltage(*controller), AxisDataOption::Maximum)}
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::ControllerPanelVoltage(*controller))}
                     <p>{format!("Controller #{} Amps", controller)}</p>
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerAmps(*controller), AxisDataOption::Average)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerAmps(*controller), AxisDataOption::Minimum)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerAmps(*controller), AxisDataOption::Maximum)}
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::ControllerAmps(*controller))}
+                    <p>{format!("Controller #{} Expected Amps (clear sky)", controller)}</p>
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::ExpectedControllerAmps(*controller))}
                     <p>{format!("Controller #{} TempF", controller)}</p>
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerTemperatureF(*controller), AxisDataOption::Average)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerTemperatureF(*controller), AxisDataOption::Minimum)}
-                        {get_channel_checkbox(&active_checkboxes, AxisDataType::ControllerTemperatureF(*controller), AxisDataOption::Maximum)}
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::ControllerTemperatureF(*controller))}
+                    <p>{format!("Controller #{} Charge Stage (0 Idle, 1 Bulk, 2 Absorption, 3 Float)", controller)}</p>
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::ChargeStage(*controller))}
+                    </>
+                )
+            }).collect::<Html>()}
+        </>
+    )
+}
+
+pub fn generate_derived_channel_controls(derived_channels: &[DerivedChannel], current_state: &AxisControlsRequest) -> Html {
+    let active_checkboxes = current_state.requests.iter().filter_map(|(data_type, data_option)| {
+        match data_type {
+            AxisDataType::Custom(_) => Some((data_type.clone(), data_option.clone())),
+            _ => None,
+        }
+    }).collect::<Vec<_>>();
+    html!(
+        <>
+            {derived_channels.iter().map(|derived_channel| {
+                html!(
+                    <>
+                    <p>{format!("{} ({})", derived_channel.name, derived_channel.expression)}</p>
+                        {get_channel_checkboxes(&active_checkboxes, AxisDataType::Custom(derived_channel.name.clone()))}
                     </>
                 )
             }).collect::<Html>()}
@@ -238,6 +294,25 @@ pub fn generate_y_axis_controller_controls(controllers: &AvailableControllers, c
     )
 }
 
+//A labelled checkbox for every selectable option of a channel, plus any other percentile already picked for it, so that closing
+// the window doesn't drop it.
+pub fn get_channel_checkboxes(active_checkboxes: &Vec<(AxisDataType, AxisDataOption)>, axis_type: AxisDataType) -> Html {
+    let mut options = AxisDataOption::get_selectable_options();
+    active_checkboxes.iter().for_each(|(active_type, active_option)| {
+        if *active_type == axis_type && matches!(active_option, AxisDataOption::Percentile(_)) && !options.contains(active_option) {
+            options.push(active_option.clone());
+        }
+    });
+    options.into_iter().map(|axis_option| {
+        html!(
+            <label class="graph-checkbox-label">
+                {get_channel_checkbox(active_checkboxes, axis_type.clone(), axis_option.clone())}
+                {axis_option.get_name()}
+            </label>
+        )
+    }).collect::<Html>()
+}
+
 pub fn get_channel_checkbox(active_checkboxes: &Vec<(AxisDataType, AxisDataOption)>, axis_type: AxisDataType, axis_option: AxisDataOption) -> Html {
     html!(
         if active_checkboxes.contains(&(axis_type.clone(), axis_option.clone())) {
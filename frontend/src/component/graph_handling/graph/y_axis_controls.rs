@@ -1,9 +1,9 @@
 use std::{collections::HashMap, rc::Rc};
 
-use shared::{solar_data::{cell::AvailableCells, controllers::AvailableControllers}, graph::graph_axis::{AxisDataOption, AxisDataType, AxisControlsRequest, DataUnit}};
+use shared::{solar_data::{cell::AvailableCells, controllers::AvailableControllers, expression::DerivedChannel}, graph::graph_axis::{AxisDataOption, AxisDataType, AxisControlsRequest, DataUnit}};
 use yew::prelude::*;
 
-use crate::component::{control::{modal_window::ModalWindow, channel::Channel, copy_paste::{CopyPaste, Request}}, graph_handling::graph::x_axis_controls::{generate_y_axis_system_controls, generate_y_axis_controller_controls}};
+use crate::component::{control::{modal_window::ModalWindow, channel::Channel, copy_paste::{CopyPaste, Request}}, graph_handling::graph::{x_axis_controls::{generate_y_axis_system_controls, generate_y_axis_controller_controls, generate_derived_channel_controls}, transform_controls::TransformControls}};
 
 use super::graph_coordination::SharableGraphData;
 
@@ -19,6 +19,7 @@ pub struct YAxisControlsProps {
     pub callback: Callback<AxisControlsRequest>,
     pub available_cells: AvailableCells,
     pub available_controllers: AvailableControllers,
+    pub derived_channels: Vec<DerivedChannel>,
 }
 
 pub enum YAxisControlsMessage {
@@ -89,11 +90,13 @@ impl Component for YAxisControls {
                         <Channel<(),DataUnit,(AxisDataType,AxisDataOption)> on_destroy_callback={on_channel_close}>
                             {generate_y_axis_system_controls(&props.available_cells, &props.current_state)}
                             {generate_y_axis_controller_controls(&props.available_controllers, &props.current_state)}
+                            {generate_derived_channel_controls(&props.derived_channels, &props.current_state)}
                         </Channel<(),DataUnit,(AxisDataType,AxisDataOption)>>
                     </div>
                 </ModalWindow>
                 <button class="graph-button" onclick={open_modal}>{"Y-Axis Options"}</button>
                 <CopyPaste copy_visible={true} paste_visible={paste_visible} callback={oncopypaste}/>
+                <TransformControls current_state={props.current_state.clone()} callback={props.callback.clone()}/>
             </div>
         )
     }
@@ -104,7 +107,11 @@ impl Component for YAxisControls {
             Self::Message::CloseModalWindow => self.modal_open = false,
             Self::Message::OpenModalWindow => self.modal_open = true,
             Self::Message::NewYAxisState(map) => {
-                let mut control_request = AxisControlsRequest::default();
+                //Transforms are set outside the modal window, so keep them for the series that are still picked.
+                let mut control_request = AxisControlsRequest {
+                    requests: Vec::new(),
+                    transforms: props.current_state.transforms.clone(),
+                };
                 map.into_iter().for_each(|(_, channel_map)| {
                     channel_map.into_iter().for_each(|(_data_unit, axis_data)| {
                         axis_data.into_iter().for_each(|data| {
@@ -112,6 +119,7 @@ impl Component for YAxisControls {
                         });
                     });
                 });
+                control_request.retain_requested_transforms();
                 props.callback.emit(control_request);
             },
             Self::Message::ContextChanged(new_copy_state) => {
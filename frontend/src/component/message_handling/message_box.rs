@@ -1,8 +1,11 @@
 //Component responsible for containing messages
 
-use shared::types::fixed_size_queue::FixedSizeQueue;
+use gloo_events::EventListener;
+use shared::{types::fixed_size_queue::FixedSizeQueue, command_error::CommandError};
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::CustomEvent;
 use yew::prelude::*;
-use crate::{component::message_handling::simple_message::SimpleMessage, component_channel::ComponentChannelRx};
+use crate::{bindings, component::message_handling::simple_message::SimpleMessage, component_channel::ComponentChannelRx};
 
 use super::simple_message::SimpleMessageProperties;
 
@@ -11,6 +14,8 @@ pub struct MessageBox {
     // _context_handle: ContextHandle<Rc<AppState>>,
     messages: FixedSizeQueue<(u32, SimpleMessageProperties)>,
     listener: Option<gloo_events::EventListener>,
+    //Errors from backend commands come straight here, rather than through whichever component made the request.
+    command_error_listener: Option<EventListener>,
 }
 
 pub enum MessageBoxMessage {
@@ -51,6 +56,7 @@ impl Component for MessageBox {
             // _context_handle,
             messages: FixedSizeQueue::new(props.queue_capacity),
             listener: None,
+            command_error_listener: None,
         };
         
         component.messages.push((0, SimpleMessageProperties {class: AttrValue::from("message TestClass"), message: AttrValue::from("TestMessage")}));
@@ -150,6 +156,27 @@ impl Component for MessageBox {
             },
         };
         self.listener = listener;
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_command_error = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail().as_string().unwrap_or_default();
+            //Anything that isn't a CommandError still gets shown, as whatever it was.
+            let message = match serde_json::from_str::<CommandError>(&payload) {
+                Ok(error) => error.to_string(),
+                Err(_) => payload,
+            };
+            MessageBoxMessage::NewMessage(SimpleMessageProperties { class: AttrValue::from("error"), message: AttrValue::from(message) })
+        });
+
+        let command_error_listener = EventListener::new(
+            &root, 
+            "command_error", 
+            move |e| on_command_error.emit(e.clone())
+        );
+
+        self.command_error_listener = Some(command_error_listener);
     }
 
     fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
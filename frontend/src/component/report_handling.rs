@@ -0,0 +1,7 @@
+// All components related to reports built from the whole data set.
+pub mod daily_summary;
+pub mod cell_balance;
+pub mod cycles;
+pub mod alerts;
+pub mod anomalies;
+pub mod charge_stages;
\ No newline at end of file
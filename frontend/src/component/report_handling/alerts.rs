@@ -0,0 +1,186 @@
+use gloo_events::EventListener;
+use shared::solar_data::alerts::{AlertEvent, AlertSeverity};
+use time::OffsetDateTime;
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::CustomEvent;
+use yew::prelude::*;
+
+use crate::{bindings, component::message_handling::simple_message::SimpleMessageProperties, component_channel::ComponentChannelTx};
+
+
+//An import with a long stretch of bad data could otherwise bury every other notification.
+const MAX_ALERT_NOTIFICATIONS: usize = 5;
+
+pub struct AlertsPanel {
+    events: Vec<AlertEvent>,
+    parse_complete_listener: Option<EventListener>,
+    settings_listener: Option<EventListener>,
+    alerts_triggered_listener: Option<EventListener>,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct AlertsPanelProps {
+    pub notification_tx: ComponentChannelTx<SimpleMessageProperties>,
+}
+
+pub enum AlertsPanelMessage {
+    UpdateAlerts,
+    NewAlerts(Vec<AlertEvent>),
+    Triggered(Vec<AlertEvent>),
+}
+
+impl Component for AlertsPanel {
+    type Message = AlertsPanelMessage;
+    type Properties = AlertsPanelProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            events: Vec::new(),
+            parse_complete_listener: None,
+            settings_listener: None,
+            alerts_triggered_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::UpdateAlerts => {
+                bindings::send_command_result(ctx.link(), bindings::retrieve_alerts(), Self::Message::NewAlerts);
+                return false;
+            },
+            Self::Message::NewAlerts(events) => {
+                self.events = events;
+            },
+            Self::Message::Triggered(events) => {
+                //The most severe first, so they're the ones that make it into the notifications.
+                let mut events = events;
+                events.sort_by(|a, b| b.severity.cmp(&a.severity));
+                let mut messages = events.iter().take(MAX_ALERT_NOTIFICATIONS).map(|event| {
+                    SimpleMessageProperties {
+                        class: AttrValue::from(get_notification_class(event.severity)),
+                        message: AttrValue::from(event.get_message()),
+                    }
+                }).collect::<Vec<_>>();
+                if events.len() > MAX_ALERT_NOTIFICATIONS {
+                    messages.push(SimpleMessageProperties {
+                        class: AttrValue::from("notification"),
+                        message: AttrValue::from(format!("{} more alerts, see the alerts panel.", events.len() - MAX_ALERT_NOTIFICATIONS)),
+                    });
+                }
+                messages.into_iter().for_each(|message| {
+                    if let Err(e) = ctx.props().notification_tx.try_send(message) {
+                        web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(format!("{:?}", e).as_str()));
+                    }
+                });
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html!(
+            <div class="report">
+                <div class="report-header">
+                    <p>{"Alerts"}</p>
+                    <p>{format!("Events: {}", self.events.len())}</p>
+                </div>
+                <table class="report-table">
+                    <thead>
+                        <tr>
+                            <th>{"Severity"}</th>
+                            <th>{"Rule"}</th>
+                            <th>{"Start"}</th>
+                            <th>{"Duration (s)"}</th>
+                            <th>{"Extreme Value"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {self.events.iter().map(|event| {
+                            html!(
+                                <tr class={format!("alert-{}", event.severity.get_name().to_lowercase())}>
+                                    <td>{event.severity.get_name()}</td>
+                                    <td>{event.rule_name.clone()}</td>
+                                    <td>{format_time(event.start)}</td>
+                                    <td>{event.end - event.start}</td>
+                                    <td>{format!("{:.3}", event.extreme_value)}</td>
+                                </tr>
+                            )
+                        }).collect::<Html>()}
+                    </tbody>
+                </table>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_parse_complete = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateAlerts
+        });
+
+        let parse_listener = EventListener::new(
+            &root,
+            "solar_parse_complete",
+            move |e| on_parse_complete.emit(e.clone())
+        );
+
+        self.parse_complete_listener = Some(parse_listener);
+
+        //The rules live in the settings, so any change to them changes the events.
+        let on_settings = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateAlerts
+        });
+
+        let settings_listener = EventListener::new(
+            &root,
+            "settings_update",
+            move |e| on_settings.emit(e.clone())
+        );
+
+        self.settings_listener = Some(settings_listener);
+
+        let on_triggered = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail();
+
+            let events = serde_json::from_str::<Vec<AlertEvent>>(payload.as_string().unwrap().as_ref());
+
+            Self::Message::Triggered(events.unwrap_throw())
+        });
+
+        let alerts_triggered_listener = EventListener::new(
+            &root,
+            "alerts_triggered",
+            move |e| on_triggered.emit(e.clone())
+        );
+
+        self.alerts_triggered_listener = Some(alerts_triggered_listener);
+
+        ctx.link().callback(|_| {
+            Self::Message::UpdateAlerts
+        }).emit(());
+    }
+}
+
+//See the MessageBox styles.
+fn get_notification_class(severity: AlertSeverity) -> &'static str {
+    match severity {
+        AlertSeverity::Info => "notification",
+        AlertSeverity::Warning => "warning",
+        AlertSeverity::Critical => "error",
+    }
+}
+
+pub fn format_time(timestamp: i64) -> String {
+    match OffsetDateTime::from_unix_timestamp(timestamp) {
+        Ok(date_time) => format!("{}-{:02}-{:02} {:02}:{:02}:{:02}", date_time.year(), date_time.month() as u8, date_time.day(),
+            date_time.hour(), date_time.minute(), date_time.second()),
+        Err(_) => timestamp.to_string(),
+    }
+}
@@ -0,0 +1,104 @@
+use gloo_events::EventListener;
+use shared::solar_data::anomalies::Anomaly;
+use yew::prelude::*;
+
+use crate::{bindings, component::report_handling::alerts::format_time};
+
+
+pub struct AnomaliesPanel {
+    anomalies: Vec<Anomaly>,
+    parse_complete_listener: Option<EventListener>,
+}
+
+pub enum AnomaliesPanelMessage {
+    UpdateAnomalies,
+    NewAnomalies(Vec<Anomaly>),
+}
+
+impl Component for AnomaliesPanel {
+    type Message = AnomaliesPanelMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            anomalies: Vec::new(),
+            parse_complete_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::UpdateAnomalies => {
+                bindings::send_command_result(ctx.link(), bindings::retrieve_anomalies(), Self::Message::NewAnomalies);
+                return false;
+            },
+            Self::Message::NewAnomalies(anomalies) => {
+                self.anomalies = anomalies;
+            },
+        }
+        true
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html!(
+            <div class="report">
+                <div class="report-header">
+                    <p>{"Anomalies"}</p>
+                    <p>{format!("Found: {}", self.anomalies.len())}</p>
+                </div>
+                <table class="report-table">
+                    <thead>
+                        <tr>
+                            <th>{"Channel"}</th>
+                            <th>{"Start"}</th>
+                            <th>{"Duration (s)"}</th>
+                            <th>{"Value"}</th>
+                            <th>{"Usual"}</th>
+                            <th>{"Score"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {self.anomalies.iter().map(|anomaly| {
+                            let scale = anomaly.kind.get_display_scale();
+                            let unit = anomaly.kind.get_unit();
+                            html!(
+                                <tr>
+                                    <td>{anomaly.kind.get_name()}</td>
+                                    <td>{format_time(anomaly.start)}</td>
+                                    <td>{anomaly.end - anomaly.start}</td>
+                                    <td>{format!("{:.1} {}", anomaly.value * scale, unit)}</td>
+                                    <td>{format!("{:.1} {}", anomaly.expected * scale, unit)}</td>
+                                    <td>{format!("{:.1}", anomaly.score)}</td>
+                                </tr>
+                            )
+                        }).collect::<Html>()}
+                    </tbody>
+                </table>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_parse_complete = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateAnomalies
+        });
+
+        let parse_listener = EventListener::new(
+            &root,
+            "solar_parse_complete",
+            move |e| on_parse_complete.emit(e.clone())
+        );
+
+        self.parse_complete_listener = Some(parse_listener);
+
+        ctx.link().callback(|_| {
+            Self::Message::UpdateAnomalies
+        }).emit(());
+    }
+}
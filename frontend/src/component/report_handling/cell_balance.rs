@@ -0,0 +1,101 @@
+use gloo_events::EventListener;
+use shared::solar_data::cell_balance::CellBalanceRow;
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct CellBalanceTable {
+    rows: Vec<CellBalanceRow>,
+    parse_complete_listener: Option<EventListener>,
+}
+
+pub enum CellBalanceTableMessage {
+    UpdateRows,
+    NewRows(Vec<CellBalanceRow>),
+}
+
+impl Component for CellBalanceTable {
+    type Message = CellBalanceTableMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            rows: Vec::new(),
+            parse_complete_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::UpdateRows => {
+                bindings::send_command_result(ctx.link(), bindings::retrieve_cell_balance_summary(), Self::Message::NewRows);
+                return false;
+            },
+            Self::Message::NewRows(rows) => {
+                self.rows = rows;
+            },
+        }
+        true
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html!(
+            <div class="report">
+                <div class="report-header">
+                    <p>{"Cell Balance (most frequent outlier first)"}</p>
+                </div>
+                <table class="report-table">
+                    <thead>
+                        <tr>
+                            <th>{"Cell"}</th>
+                            <th>{"Times Highest"}</th>
+                            <th>{"Times Lowest"}</th>
+                            <th>{"Outlier %"}</th>
+                            <th>{"Avg Deviation (mV)"}</th>
+                            <th>{"Drift (mV / 30 days)"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {self.rows.iter().map(|row| {
+                            html!(
+                                <tr>
+                                    <td>{format!("#{}", row.cell)}</td>
+                                    <td>{row.times_highest}</td>
+                                    <td>{row.times_lowest}</td>
+                                    <td>{format!("{:.1}", row.outlier_percent)}</td>
+                                    <td>{format!("{:.1}", row.average_deviation_mv)}</td>
+                                    <td>{row.drift_mv_per_30_days.map(|drift| format!("{:.2}", drift)).unwrap_or_default()}</td>
+                                </tr>
+                            )
+                        }).collect::<Html>()}
+                    </tbody>
+                </table>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_parse_complete = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateRows
+        });
+
+        let parse_listener = EventListener::new(
+            &root, 
+            "solar_parse_complete", 
+            move |e| on_parse_complete.emit(e.clone())
+        );
+
+        self.parse_complete_listener = Some(parse_listener);
+
+        ctx.link().callback(|_| {
+            Self::Message::UpdateRows
+        }).emit(());
+    }
+}
@@ -0,0 +1,112 @@
+use gloo_events::EventListener;
+use shared::solar_data::{charge_stage::ChargeStageDay, summary::format_day};
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct ChargeStageTable {
+    days: Vec<ChargeStageDay>,
+    parse_complete_listener: Option<EventListener>,
+}
+
+pub enum ChargeStageTableMessage {
+    UpdateSummary,
+    NewSummary(Vec<ChargeStageDay>),
+}
+
+impl Component for ChargeStageTable {
+    type Message = ChargeStageTableMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            days: Vec::new(),
+            parse_complete_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::UpdateSummary => {
+                bindings::send_command_result(ctx.link(), bindings::retrieve_charge_stage_summary(), Self::Message::NewSummary);
+                return false;
+            },
+            Self::Message::NewSummary(days) => {
+                self.days = days;
+            },
+        }
+        true
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        //Days without any charging don't say anything about whether the batteries get full.
+        let charging_days = self.days.iter().filter(|day| day.bulk_seconds + day.absorption_seconds + day.float_seconds > 0).count();
+        let absorption_days = self.days.iter().filter(|day| day.reached_absorption).count();
+
+        html!(
+            <div class="report">
+                <div class="report-header">
+                    <p>{"Charge Stages"}</p>
+                    <p>{format!("Reached Absorption: {} of {} charging days", absorption_days, charging_days)}</p>
+                </div>
+                <table class="report-table">
+                    <thead>
+                        <tr>
+                            <th>{"Day"}</th>
+                            <th>{"Controller"}</th>
+                            <th>{"Idle (h)"}</th>
+                            <th>{"Bulk (h)"}</th>
+                            <th>{"Absorption (h)"}</th>
+                            <th>{"Float (h)"}</th>
+                            <th>{"Reached Absorption"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {self.days.iter().map(|day| {
+                            html!(
+                                <tr>
+                                    <td>{format_day(day.day)}</td>
+                                    <td>{day.controller}</td>
+                                    <td>{format_hours(day.idle_seconds)}</td>
+                                    <td>{format_hours(day.bulk_seconds)}</td>
+                                    <td>{format_hours(day.absorption_seconds)}</td>
+                                    <td>{format_hours(day.float_seconds)}</td>
+                                    <td>{if day.reached_absorption { "Yes" } else { "No" }}</td>
+                                </tr>
+                            )
+                        }).collect::<Html>()}
+                    </tbody>
+                </table>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_parse_complete = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateSummary
+        });
+
+        let parse_listener = EventListener::new(
+            &root, 
+            "solar_parse_complete", 
+            move |e| on_parse_complete.emit(e.clone())
+        );
+
+        self.parse_complete_listener = Some(parse_listener);
+
+        ctx.link().callback(|_| {
+            Self::Message::UpdateSummary
+        }).emit(());
+    }
+}
+
+fn format_hours(seconds: i64) -> String {
+    format!("{:.1}", seconds as f64 / 3600f64)
+}
@@ -0,0 +1,125 @@
+use gloo_events::EventListener;
+use shared::solar_data::cycles::CycleSummary;
+use time::OffsetDateTime;
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct CycleTable {
+    summary: CycleSummary,
+    parse_complete_listener: Option<EventListener>,
+}
+
+pub enum CycleTableMessage {
+    UpdateSummary,
+    NewSummary(CycleSummary),
+}
+
+impl Component for CycleTable {
+    type Message = CycleTableMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            summary: CycleSummary::default(),
+            parse_complete_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::UpdateSummary => {
+                bindings::send_command_result(ctx.link(), bindings::retrieve_cycle_summary(), Self::Message::NewSummary);
+                return false;
+            },
+            Self::Message::NewSummary(summary) => {
+                self.summary = summary;
+            },
+        }
+        true
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html!(
+            <div class="report">
+                <div class="report-header">
+                    <p>{"Battery Cycles"}</p>
+                    <p>{format!("Equivalent Full Cycles: {:.1}", self.summary.equivalent_full_cycles)}</p>
+                    <p>{format!("Total Cycles: {:.1}", self.summary.total_cycles)}</p>
+                </div>
+                <div class="report-tables">
+                    <table class="report-table">
+                        <thead>
+                            <tr>
+                                <th>{"Depth of Discharge"}</th>
+                                <th>{"Cycles"}</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {self.summary.depth_histogram.iter().map(|bin| {
+                                html!(
+                                    <tr>
+                                        <td>{format!("{:.0}% - {:.0}%", bin.min_depth, bin.max_depth)}</td>
+                                        <td>{format!("{:.1}", bin.cycles)}</td>
+                                    </tr>
+                                )
+                            }).collect::<Html>()}
+                        </tbody>
+                    </table>
+                    <table class="report-table">
+                        <thead>
+                            <tr>
+                                <th>{"Month"}</th>
+                                <th>{"Cycles"}</th>
+                                <th>{"Equivalent Full Cycles"}</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {self.summary.monthly.iter().map(|monthly_cycles| {
+                                html!(
+                                    <tr>
+                                        <td>{format_month(monthly_cycles.month)}</td>
+                                        <td>{format!("{:.1}", monthly_cycles.cycles)}</td>
+                                        <td>{format!("{:.2}", monthly_cycles.equivalent_full_cycles)}</td>
+                                    </tr>
+                                )
+                            }).collect::<Html>()}
+                        </tbody>
+                    </table>
+                </div>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_parse_complete = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateSummary
+        });
+
+        let parse_listener = EventListener::new(
+            &root, 
+            "solar_parse_complete", 
+            move |e| on_parse_complete.emit(e.clone())
+        );
+
+        self.parse_complete_listener = Some(parse_listener);
+
+        ctx.link().callback(|_| {
+            Self::Message::UpdateSummary
+        }).emit(());
+    }
+}
+
+fn format_month(month: i64) -> String {
+    match OffsetDateTime::from_unix_timestamp(month) {
+        Ok(date_time) => format!("{}-{:02}", date_time.year(), date_time.month() as u8),
+        Err(_) => month.to_string(),
+    }
+}
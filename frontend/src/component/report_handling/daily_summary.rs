@@ -0,0 +1,134 @@
+use gloo_events::EventListener;
+use shared::solar_data::summary::{DailySummary, SummaryColumn, sort_daily_summaries, daily_summaries_to_csv};
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct DailySummaryTable {
+    summaries: Vec<DailySummary>,
+    sort_column: SummaryColumn,
+    sort_ascending: bool,
+    parse_complete_listener: Option<EventListener>,
+}
+
+pub enum DailySummaryTableMessage {
+    UpdateSummaries,
+    NewSummaries(Vec<DailySummary>),
+    SortBy(SummaryColumn),
+    ExportCsv,
+}
+
+impl Component for DailySummaryTable {
+    type Message = DailySummaryTableMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            summaries: Vec::new(),
+            sort_column: SummaryColumn::Day,
+            sort_ascending: true,
+            parse_complete_listener: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::UpdateSummaries => {
+                bindings::send_command_result(ctx.link(), bindings::retrieve_daily_summary(), Self::Message::NewSummaries);
+                return false;
+            },
+            Self::Message::NewSummaries(summaries) => {
+                self.summaries = summaries;
+                sort_daily_summaries(&mut self.summaries, self.sort_column, self.sort_ascending);
+            },
+            Self::Message::SortBy(column) => {
+                //Clicking the current column again flips the direction.
+                if self.sort_column == column {
+                    self.sort_ascending = !self.sort_ascending;
+                } else {
+                    self.sort_column = column;
+                    self.sort_ascending = true;
+                }
+                sort_daily_summaries(&mut self.summaries, self.sort_column, self.sort_ascending);
+            },
+            Self::Message::ExportCsv => {
+                bindings::download_text_file("daily_summary.csv".to_owned(), "text/csv".to_owned(), daily_summaries_to_csv(&self.summaries));
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onexport = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::ExportCsv
+        });
+        let columns = SummaryColumn::get_all_columns();
+
+        html!(
+            <div class="report">
+                <div class="report-header">
+                    <p>{"Daily Summary"}</p>
+                    <button class="graph-button" onclick={onexport}>{"Export CSV"}</button>
+                </div>
+                <table class="report-table">
+                    <thead>
+                        <tr>
+                            {columns.iter().map(|column| {
+                                let column = *column;
+                                let onsort = ctx.link().callback(move |_event: MouseEvent| {
+                                    Self::Message::SortBy(column)
+                                });
+                                let sort_indicator = match (column == self.sort_column, self.sort_ascending) {
+                                    (true, true) => " ▲",
+                                    (true, false) => " ▼",
+                                    (false, _) => "",
+                                };
+                                html!(
+                                    <th onclick={onsort}>{format!("{}{}", column.get_name(), sort_indicator)}</th>
+                                )
+                            }).collect::<Html>()}
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {self.summaries.iter().map(|summary| {
+                            html!(
+                                <tr>
+                                    {columns.iter().map(|column| {
+                                        html!(<td>{summary.get_formatted_value(*column)}</td>)
+                                    }).collect::<Html>()}
+                                </tr>
+                            )
+                        }).collect::<Html>()}
+                    </tbody>
+                </table>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        //Any newly parsed file can change the summary.
+        let on_parse_complete = ctx.link().callback(|_e: Event| {
+            Self::Message::UpdateSummaries
+        });
+
+        let parse_listener = EventListener::new(
+            &root, 
+            "solar_parse_complete", 
+            move |e| on_parse_complete.emit(e.clone())
+        );
+
+        self.parse_complete_listener = Some(parse_listener);
+
+        ctx.link().callback(|_| {
+            Self::Message::UpdateSummaries
+        }).emit(());
+    }
+}
@@ -0,0 +1,5 @@
+// All components related to editing the saved settings.
+pub mod derived_channel_editor;
+pub mod battery_settings;
+pub mod solar_site_settings;
+pub mod alert_rule_editor;
\ No newline at end of file
@@ -0,0 +1,223 @@
+use gloo_events::EventListener;
+use shared::{settings::Settings, solar_data::alerts::{AlertRule, AlertComparison, AlertSeverity}};
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::{CustomEvent, HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct AlertRuleEditor {
+    alert_rules: Vec<AlertRule>,
+    name: String,
+    expression: String,
+    comparison: AlertComparison,
+    threshold: String,
+    duration_seconds: String,
+    severity: AlertSeverity,
+    //Rules are checked as they are typed, so mistakes show up before saving.
+    error: Option<String>,
+    settings_listener: Option<EventListener>,
+}
+
+pub enum AlertRuleEditorMessage {
+    NewSettings(Settings),
+    NameInput(String),
+    ExpressionInput(String),
+    ComparisonInput(AlertComparison),
+    ThresholdInput(String),
+    DurationInput(String),
+    SeverityInput(AlertSeverity),
+    Edit(AlertRule),
+    Save,
+    Remove(String),
+}
+
+impl AlertRuleEditor {
+    fn get_alert_rule(&self) -> Result<AlertRule, String> {
+        let threshold = self.threshold.trim().parse::<f64>()
+            .map_err(|_| format!("Threshold must be a number, found \"{}\".", self.threshold))?;
+        //An empty duration fires on a single line.
+        let duration_seconds = match self.duration_seconds.trim() {
+            "" => 0,
+            text => text.parse::<u32>().map_err(|_| format!("Duration must be a whole number of seconds, found \"{}\".", text))?,
+        };
+        let alert_rule = AlertRule {
+            name: self.name.trim().to_owned(),
+            expression: self.expression.clone(),
+            comparison: self.comparison,
+            threshold,
+            duration_seconds,
+            severity: self.severity,
+        };
+        alert_rule.validate().map_err(|e| e.to_string())?;
+        Ok(alert_rule)
+    }
+
+    fn validate(&mut self) {
+        self.error = self.get_alert_rule().err();
+    }
+}
+
+impl Component for AlertRuleEditor {
+    type Message = AlertRuleEditorMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            alert_rules: Vec::new(),
+            name: String::new(),
+            expression: String::new(),
+            comparison: AlertComparison::Above,
+            threshold: String::new(),
+            duration_seconds: String::new(),
+            severity: AlertSeverity::Warning,
+            error: None,
+            settings_listener: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::NewSettings(settings) => {
+                self.alert_rules = settings.alert_rules;
+                return true;
+            },
+            Self::Message::NameInput(name) => self.name = name,
+            Self::Message::ExpressionInput(expression) => self.expression = expression,
+            Self::Message::ComparisonInput(comparison) => self.comparison = comparison,
+            Self::Message::ThresholdInput(threshold) => self.threshold = threshold,
+            Self::Message::DurationInput(duration_seconds) => self.duration_seconds = duration_seconds,
+            Self::Message::SeverityInput(severity) => self.severity = severity,
+            Self::Message::Edit(alert_rule) => {
+                self.name = alert_rule.name;
+                self.expression = alert_rule.expression;
+                self.comparison = alert_rule.comparison;
+                self.threshold = alert_rule.threshold.to_string();
+                self.duration_seconds = alert_rule.duration_seconds.to_string();
+                self.severity = alert_rule.severity;
+            },
+            Self::Message::Save => {
+                let alert_rule = match self.get_alert_rule() {
+                    Ok(alert_rule) => alert_rule,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return true;
+                    },
+                };
+                //Saving under an existing name updates that rule.
+                bindings::save_alert_rule(serde_json::to_string(&alert_rule).unwrap(), true);
+                self.name.clear();
+                self.expression.clear();
+                self.threshold.clear();
+                self.duration_seconds.clear();
+                self.error = None;
+                return true;
+            },
+            Self::Message::Remove(name) => {
+                bindings::remove_alert_rule(name);
+                return false;
+            },
+        }
+        self.validate();
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let text_input = |message: fn(String) -> AlertRuleEditorMessage| ctx.link().callback(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            message(input.value())
+        });
+        let oncomparisonchange = ctx.link().callback(|event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            match select.value().as_ref() {
+                "below" => Self::Message::ComparisonInput(AlertComparison::Below),
+                _ => Self::Message::ComparisonInput(AlertComparison::Above),
+            }
+        });
+        let onseveritychange = ctx.link().callback(|event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            match select.value().as_ref() {
+                "info" => Self::Message::SeverityInput(AlertSeverity::Info),
+                "critical" => Self::Message::SeverityInput(AlertSeverity::Critical),
+                _ => Self::Message::SeverityInput(AlertSeverity::Warning),
+            }
+        });
+        let onsave = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::Save
+        });
+        let show_error = !self.name.is_empty() || !self.expression.is_empty() || !self.threshold.is_empty();
+
+        html!(
+            <div class="alert-rule-editor">
+                <p>{"Alert Rules"}</p>
+                {self.alert_rules.iter().map(|alert_rule| {
+                    let edit_rule = alert_rule.clone();
+                    let onedit = ctx.link().callback(move |_event: MouseEvent| {
+                        Self::Message::Edit(edit_rule.clone())
+                    });
+                    let remove_name = alert_rule.name.clone();
+                    let onremove = ctx.link().callback(move |_event: MouseEvent| {
+                        Self::Message::Remove(remove_name.clone())
+                    });
+                    html!(
+                        <div class="alert-rule">
+                            <span>{format!("{} ({})", alert_rule.name, alert_rule.severity.get_name())}</span>
+                            <span class="derived-channel-expression">
+                                {format!("{} {} {} for {}s", alert_rule.expression, alert_rule.comparison.get_name().to_lowercase(), alert_rule.threshold, alert_rule.duration_seconds)}
+                            </span>
+                            <button class="graph-button" onclick={onedit}>{"Edit"}</button>
+                            <button class="graph-button" onclick={onremove}>{"Remove"}</button>
+                        </div>
+                    )
+                }).collect::<Html>()}
+                <input class="graph-text-input" type="text" placeholder="Name" value={self.name.clone()} oninput={text_input(Self::Message::NameInput)}/>
+                <input class="graph-text-input" type="text" placeholder="max(CellVoltage[*])" value={self.expression.clone()} oninput={text_input(Self::Message::ExpressionInput)}/>
+                <select class="graph-dropdown" onchange={oncomparisonchange}>
+                    <option value={"above"} selected={self.comparison == AlertComparison::Above}>{"Above"}</option>
+                    <option value={"below"} selected={self.comparison == AlertComparison::Below}>{"Below"}</option>
+                </select>
+                <input class="graph-text-input" type="number" step="any" placeholder="Threshold" value={self.threshold.clone()} oninput={text_input(Self::Message::ThresholdInput)}/>
+                <input class="graph-text-input" type="number" min="0" placeholder="For at least (seconds)" value={self.duration_seconds.clone()} oninput={text_input(Self::Message::DurationInput)}/>
+                <select class="graph-dropdown" onchange={onseveritychange}>
+                    <option value={"info"} selected={self.severity == AlertSeverity::Info}>{AlertSeverity::Info.get_name()}</option>
+                    <option value={"warning"} selected={self.severity == AlertSeverity::Warning}>{AlertSeverity::Warning.get_name()}</option>
+                    <option value={"critical"} selected={self.severity == AlertSeverity::Critical}>{AlertSeverity::Critical.get_name()}</option>
+                </select>
+                if show_error {
+                    if let Some(error) = &self.error {
+                        <p class="settings-error">{error.clone()}</p>
+                    }
+                }
+                <button class="graph-button" onclick={onsave} disabled={self.error.is_some() || self.name.is_empty()}>{"Save"}</button>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_settings = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail();
+
+            let settings = serde_json::from_str::<Settings>(payload.as_string().unwrap().as_ref());
+
+            Self::Message::NewSettings(settings.unwrap_throw())
+        });
+
+        let settings_listener = EventListener::new(
+            &root,
+            "settings_update",
+            move |e| on_settings.emit(e.clone())
+        );
+
+        self.settings_listener = Some(settings_listener);
+
+        bindings::send_command_result(ctx.link(), bindings::retrieve_settings(), Self::Message::NewSettings);
+    }
+}
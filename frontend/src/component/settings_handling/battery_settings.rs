@@ -0,0 +1,113 @@
+use gloo_events::EventListener;
+use shared::settings::Settings;
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::{CustomEvent, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct BatterySettings {
+    nominal_capacity: String,
+    error: Option<String>,
+    settings_listener: Option<EventListener>,
+}
+
+pub enum BatterySettingsMessage {
+    NewSettings(Settings),
+    NominalCapacityInput(String),
+    Save,
+}
+
+impl Component for BatterySettings {
+    type Message = BatterySettingsMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            nominal_capacity: String::new(),
+            error: None,
+            settings_listener: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::NewSettings(settings) => {
+                self.nominal_capacity = settings.nominal_capacity_ah.map(|capacity| capacity.to_string()).unwrap_or_default();
+            },
+            Self::Message::NominalCapacityInput(nominal_capacity) => {
+                self.nominal_capacity = nominal_capacity;
+                self.error = None;
+            },
+            Self::Message::Save => {
+                //An empty input clears the nominal capacity.
+                let nominal_capacity = match self.nominal_capacity.trim() {
+                    "" => None,
+                    text => match text.parse::<f64>() {
+                        Ok(capacity) => Some(capacity),
+                        Err(_) => {
+                            self.error = Some(format!("{} is not a number.", text));
+                            return true;
+                        },
+                    },
+                };
+                if let Err(e) = Settings::default().set_nominal_capacity(nominal_capacity) {
+                    self.error = Some(e.to_string());
+                    return true;
+                }
+                bindings::save_nominal_capacity(nominal_capacity);
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let oninput = ctx.link().callback(|event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            Self::Message::NominalCapacityInput(input.value())
+        });
+        let onsave = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::Save
+        });
+
+        html!(
+            <div class="battery-settings">
+                <p>{"Nominal Battery Capacity (Ah)"}</p>
+                <input class="graph-text-input" type="number" min="0" value={self.nominal_capacity.clone()} oninput={oninput}/>
+                if let Some(error) = &self.error {
+                    <p class="settings-error">{error.clone()}</p>
+                }
+                <button class="graph-button" onclick={onsave}>{"Save"}</button>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_settings = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail();
+
+            let settings = serde_json::from_str::<Settings>(payload.as_string().unwrap().as_ref());
+
+            Self::Message::NewSettings(settings.unwrap_throw())
+        });
+
+        let settings_listener = EventListener::new(
+            &root, 
+            "settings_update", 
+            move |e| on_settings.emit(e.clone())
+        );
+
+        self.settings_listener = Some(settings_listener);
+
+        bindings::send_command_result(ctx.link(), bindings::retrieve_settings(), Self::Message::NewSettings);
+    }
+}
@@ -0,0 +1,159 @@
+use gloo_events::EventListener;
+use shared::{settings::Settings, solar_data::expression::DerivedChannel};
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::{CustomEvent, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+pub struct DerivedChannelEditor {
+    derived_channels: Vec<DerivedChannel>,
+    name: String,
+    expression: String,
+    //Expressions are checked as they are typed, so mistakes show up before saving.
+    error: Option<String>,
+    settings_listener: Option<EventListener>,
+}
+
+pub enum DerivedChannelEditorMessage {
+    NewSettings(Settings),
+    NameInput(String),
+    ExpressionInput(String),
+    Edit(DerivedChannel),
+    Save,
+    Remove(String),
+}
+
+impl DerivedChannelEditor {
+    fn validate(&mut self) {
+        let derived_channel = DerivedChannel { name: self.name.clone(), expression: self.expression.clone() };
+        self.error = derived_channel.validate().err().map(|e| e.to_string());
+    }
+}
+
+impl Component for DerivedChannelEditor {
+    type Message = DerivedChannelEditorMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            derived_channels: Vec::new(),
+            name: String::new(),
+            expression: String::new(),
+            error: None,
+            settings_listener: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::NewSettings(settings) => {
+                self.derived_channels = settings.derived_channels;
+            },
+            Self::Message::NameInput(name) => {
+                self.name = name;
+                self.validate();
+            },
+            Self::Message::ExpressionInput(expression) => {
+                self.expression = expression;
+                self.validate();
+            },
+            Self::Message::Edit(derived_channel) => {
+                self.name = derived_channel.name;
+                self.expression = derived_channel.expression;
+                self.validate();
+            },
+            Self::Message::Save => {
+                self.validate();
+                if self.error.is_some() {
+                    return true;
+                }
+                let derived_channel = DerivedChannel { name: self.name.trim().to_owned(), expression: self.expression.clone() };
+                //Saving under an existing name updates that channel.
+                bindings::save_derived_channel(serde_json::to_string(&derived_channel).unwrap(), true);
+                self.name.clear();
+                self.expression.clear();
+            },
+            Self::Message::Remove(name) => {
+                bindings::remove_derived_channel(name);
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onnameinput = ctx.link().callback(|event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            Self::Message::NameInput(input.value())
+        });
+        let onexpressioninput = ctx.link().callback(|event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            Self::Message::ExpressionInput(input.value())
+        });
+        let onsave = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::Save
+        });
+        let show_error = !self.name.is_empty() || !self.expression.is_empty();
+
+        html!(
+            <div class="derived-channel-editor">
+                <p>{"Derived Channels"}</p>
+                {self.derived_channels.iter().map(|derived_channel| {
+                    let edit_channel = derived_channel.clone();
+                    let onedit = ctx.link().callback(move |_event: MouseEvent| {
+                        Self::Message::Edit(edit_channel.clone())
+                    });
+                    let remove_name = derived_channel.name.clone();
+                    let onremove = ctx.link().callback(move |_event: MouseEvent| {
+                        Self::Message::Remove(remove_name.clone())
+                    });
+                    html!(
+                        <div class="derived-channel">
+                            <span class="derived-channel-name">{derived_channel.name.clone()}</span>
+                            <span class="derived-channel-expression">{derived_channel.expression.clone()}</span>
+                            <button class="graph-button" onclick={onedit}>{"Edit"}</button>
+                            <button class="graph-button" onclick={onremove}>{"Remove"}</button>
+                        </div>
+                    )
+                }).collect::<Html>()}
+                <input class="graph-text-input" type="text" placeholder="Name" value={self.name.clone()} oninput={onnameinput}/>
+                <input class="graph-text-input" type="text" placeholder="SolarWatts - LoadWatts" value={self.expression.clone()} oninput={onexpressioninput}/>
+                if show_error {
+                    if let Some(error) = &self.error {
+                        <p class="settings-error">{error.clone()}</p>
+                    }
+                }
+                <button class="graph-button" onclick={onsave} disabled={self.error.is_some() || self.name.is_empty()}>{"Save"}</button>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_settings = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail();
+
+            let settings = serde_json::from_str::<Settings>(payload.as_string().unwrap().as_ref());
+
+            Self::Message::NewSettings(settings.unwrap_throw())
+        });
+
+        let settings_listener = EventListener::new(
+            &root, 
+            "settings_update", 
+            move |e| on_settings.emit(e.clone())
+        );
+
+        self.settings_listener = Some(settings_listener);
+
+        bindings::send_command_result(ctx.link(), bindings::retrieve_settings(), Self::Message::NewSettings);
+    }
+}
@@ -0,0 +1,229 @@
+use gloo_events::EventListener;
+use shared::{settings::Settings, solar_data::clear_sky::{SolarSite, SolarArray}};
+use wasm_bindgen::{UnwrapThrowExt, JsCast};
+use web_sys::{CustomEvent, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::bindings;
+
+
+//Inputs are kept as typed, and only parsed on save, so half typed numbers aren't thrown away.
+#[derive(Clone, PartialEq, Default)]
+struct ArrayInput {
+    controller: String,
+    tilt: String,
+    azimuth: String,
+    kwp: String,
+}
+
+pub struct SolarSiteSettings {
+    latitude: String,
+    longitude: String,
+    utc_offset_hours: String,
+    arrays: Vec<ArrayInput>,
+    error: Option<String>,
+    settings_listener: Option<EventListener>,
+}
+
+#[derive(Clone, Copy)]
+pub enum SiteField {
+    Latitude,
+    Longitude,
+    UtcOffset,
+}
+
+#[derive(Clone, Copy)]
+pub enum ArrayField {
+    Controller,
+    Tilt,
+    Azimuth,
+    PeakPower,
+}
+
+pub enum SolarSiteSettingsMessage {
+    NewSettings(Settings),
+    SiteInput(SiteField, String),
+    ArrayInput(usize, ArrayField, String),
+    AddArray,
+    RemoveArray(usize),
+    Save,
+}
+
+impl SolarSiteSettings {
+    //None when every site field is empty, which clears the site.
+    fn parse(&self) -> Result<Option<SolarSite>, String> {
+        if self.latitude.trim().is_empty() && self.longitude.trim().is_empty() && self.utc_offset_hours.trim().is_empty() {
+            return Ok(None)
+        }
+        let arrays = self.arrays.iter().map(|array| {
+            Ok(SolarArray {
+                controller: parse_number(&array.controller, "Controller")?,
+                tilt: parse_number(&array.tilt, "Tilt")?,
+                azimuth: parse_number(&array.azimuth, "Azimuth")?,
+                kwp: parse_number(&array.kwp, "Peak power")?,
+            })
+        }).collect::<Result<Vec<_>, String>>()?;
+        Ok(Some(SolarSite {
+            latitude: parse_number(&self.latitude, "Latitude")?,
+            longitude: parse_number(&self.longitude, "Longitude")?,
+            utc_offset_hours: parse_number(&self.utc_offset_hours, "UTC offset")?,
+            arrays,
+        }))
+    }
+}
+
+impl Component for SolarSiteSettings {
+    type Message = SolarSiteSettingsMessage;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            latitude: String::new(),
+            longitude: String::new(),
+            utc_offset_hours: String::new(),
+            arrays: Vec::new(),
+            error: None,
+            settings_listener: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Self::Message::NewSettings(settings) => {
+                let site = settings.solar_site;
+                self.latitude = site.as_ref().map(|site| site.latitude.to_string()).unwrap_or_default();
+                self.longitude = site.as_ref().map(|site| site.longitude.to_string()).unwrap_or_default();
+                self.utc_offset_hours = site.as_ref().map(|site| site.utc_offset_hours.to_string()).unwrap_or_default();
+                self.arrays = site.map(|site| site.arrays).unwrap_or_default().into_iter().map(|array| {
+                    ArrayInput {
+                        controller: array.controller.to_string(),
+                        tilt: array.tilt.to_string(),
+                        azimuth: array.azimuth.to_string(),
+                        kwp: array.kwp.to_string(),
+                    }
+                }).collect();
+            },
+            Self::Message::SiteInput(field, value) => {
+                match field {
+                    SiteField::Latitude => self.latitude = value,
+                    SiteField::Longitude => self.longitude = value,
+                    SiteField::UtcOffset => self.utc_offset_hours = value,
+                }
+                self.error = None;
+            },
+            Self::Message::ArrayInput(index, field, value) => {
+                if let Some(array) = self.arrays.get_mut(index) {
+                    match field {
+                        ArrayField::Controller => array.controller = value,
+                        ArrayField::Tilt => array.tilt = value,
+                        ArrayField::Azimuth => array.azimuth = value,
+                        ArrayField::PeakPower => array.kwp = value,
+                    }
+                }
+                self.error = None;
+            },
+            Self::Message::AddArray => {
+                //Most arrays in the northern hemisphere face south.
+                self.arrays.push(ArrayInput { azimuth: "180".to_owned(), ..Default::default() });
+            },
+            Self::Message::RemoveArray(index) => {
+                if index < self.arrays.len() {
+                    self.arrays.remove(index);
+                }
+            },
+            Self::Message::Save => {
+                let solar_site = match self.parse() {
+                    Ok(solar_site) => solar_site,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return true;
+                    },
+                };
+                if let Err(e) = Settings::default().set_solar_site(solar_site.clone()) {
+                    self.error = Some(e.to_string());
+                    return true;
+                }
+                bindings::save_solar_site(serde_json::to_string(&solar_site).unwrap());
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let site_input = |field: SiteField| ctx.link().callback(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            Self::Message::SiteInput(field, input.value())
+        });
+        let array_input = |index: usize, field: ArrayField| ctx.link().callback(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            Self::Message::ArrayInput(index, field, input.value())
+        });
+        let onadd = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::AddArray
+        });
+        let onsave = ctx.link().callback(|_event: MouseEvent| {
+            Self::Message::Save
+        });
+
+        html!(
+            <div class="solar-site-settings">
+                <p>{"Solar Site"}</p>
+                <input class="graph-text-input" type="number" step="any" placeholder="Latitude (°N)" value={self.latitude.clone()} oninput={site_input(SiteField::Latitude)}/>
+                <input class="graph-text-input" type="number" step="any" placeholder="Longitude (°E)" value={self.longitude.clone()} oninput={site_input(SiteField::Longitude)}/>
+                <input class="graph-text-input" type="number" step="any" placeholder="Logger UTC Offset (hours)" value={self.utc_offset_hours.clone()} oninput={site_input(SiteField::UtcOffset)}/>
+                <p>{"Arrays"}</p>
+                {self.arrays.iter().enumerate().map(|(index, array)| {
+                    let onremove = ctx.link().callback(move |_event: MouseEvent| {
+                        Self::Message::RemoveArray(index)
+                    });
+                    html!(
+                        <div class="solar-array">
+                            <input class="graph-text-input" type="number" min="0" placeholder="Controller #" value={array.controller.clone()} oninput={array_input(index, ArrayField::Controller)}/>
+                            <input class="graph-text-input" type="number" step="any" placeholder="Tilt (°)" value={array.tilt.clone()} oninput={array_input(index, ArrayField::Tilt)}/>
+                            <input class="graph-text-input" type="number" step="any" placeholder="Azimuth (°)" value={array.azimuth.clone()} oninput={array_input(index, ArrayField::Azimuth)}/>
+                            <input class="graph-text-input" type="number" step="any" placeholder="kWp" value={array.kwp.clone()} oninput={array_input(index, ArrayField::PeakPower)}/>
+                            <button class="graph-button" onclick={onremove}>{"Remove"}</button>
+                        </div>
+                    )
+                }).collect::<Html>()}
+                <button class="graph-button" onclick={onadd}>{"Add Array"}</button>
+                if let Some(error) = &self.error {
+                    <p class="settings-error">{error.clone()}</p>
+                }
+                <button class="graph-button" onclick={onsave}>{"Save"}</button>
+            </div>
+        )
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render {
+            return
+        }
+
+        let root = bindings::get_root().expect("We should always be able to get the root element");
+
+        let on_settings = ctx.link().callback(|e: Event| {
+            let casted_event = e.dyn_ref::<CustomEvent>().unwrap_throw();
+            let payload = casted_event.detail();
+
+            let settings = serde_json::from_str::<Settings>(payload.as_string().unwrap().as_ref());
+
+            Self::Message::NewSettings(settings.unwrap_throw())
+        });
+
+        let settings_listener = EventListener::new(
+            &root,
+            "settings_update",
+            move |e| on_settings.emit(e.clone())
+        );
+
+        self.settings_listener = Some(settings_listener);
+
+        bindings::send_command_result(ctx.link(), bindings::retrieve_settings(), Self::Message::NewSettings);
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, field: &str) -> Result<T, String> {
+    text.trim().parse::<T>().map_err(|_| format!("{} must be a number, found \"{}\".", field, text))
+}
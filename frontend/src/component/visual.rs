@@ -1,5 +1,4 @@
 pub mod sidebar;
 pub mod sidemenu;
 pub mod svg;
-pub mod theme_data;
 pub mod general_props;
\ No newline at end of file
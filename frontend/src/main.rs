@@ -12,7 +12,7 @@ use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 
-use crate::{component::{file_handling::file_select::{FileSelect, FileSelectProperties}, message_handling::message_box::MessageBox, visual::{sidebar::Sidebar, sidemenu::Sidemenu, svg::{cog::Cog, file_upload::FileUpload}}, control::{switch::{Switch, SwitchProperties}, button::{Button, ButtonProperties}, shared_data_context::SharedDataContext}, graph_handling::graph::{Graph, graph_coordination::SharableGraphData}}, bindings::{Theme, remove_classes, add_classes, toggle_classes}, component_channel::ComponentChannel};
+use crate::{component::{file_handling::{file_select::{FileSelect, FileSelectProperties}, import_progress::ImportProgressList}, message_handling::message_box::MessageBox, visual::{sidebar::Sidebar, sidemenu::Sidemenu, svg::{cog::Cog, file_upload::FileUpload}}, control::{switch::{Switch, SwitchProperties}, button::{Button, ButtonProperties}, shared_data_context::SharedDataContext}, graph_handling::graph::{Graph, graph_coordination::SharableGraphData}, report_handling::{daily_summary::DailySummaryTable, cell_balance::CellBalanceTable, cycles::CycleTable, alerts::AlertsPanel, anomalies::AnomaliesPanel, charge_stages::ChargeStageTable}, settings_handling::{derived_channel_editor::DerivedChannelEditor, battery_settings::BatterySettings, solar_site_settings::SolarSiteSettings, alert_rule_editor::AlertRuleEditor}}, bindings::{Theme, remove_classes, add_classes, toggle_classes}, component_channel::ComponentChannel};
 
 fn main() {
     bindings::set_detected_theme();
@@ -85,6 +85,12 @@ fn app() -> Html {
                     <SharedDataContext<Option<SharableGraphData>> init={Rc::from(None)}>
                         <Graph canvas_id={AttrValue::from("test")} canvas_container_id={AttrValue::from("test-container")} notification_tx={notification_tx.clone()}/>
                         <Graph canvas_id={AttrValue::from("test2")} canvas_container_id={AttrValue::from("test-container2")} notification_tx={notification_tx.clone()}/>
+                        <DailySummaryTable/>
+                        <CellBalanceTable/>
+                        <CycleTable/>
+                        <ChargeStageTable/>
+                        <AlertsPanel notification_tx={notification_tx.clone()}/>
+                        <AnomaliesPanel/>
                         <MessageBox ..message_box_props/>
                     </SharedDataContext<Option<SharableGraphData>>>
                 </div>
@@ -94,6 +100,10 @@ fn app() -> Html {
                     </Button>
                     <Sidemenu class="settings-menu">
                         <Switch ..theme_switch_props/>
+                        <BatterySettings/>
+                        <SolarSiteSettings/>
+                        <DerivedChannelEditor/>
+                        <AlertRuleEditor/>
                     </Sidemenu>
                     <Button ..file_upload_button_props>
                         <FileUpload class="sidebar-icon svg" />
@@ -101,6 +111,7 @@ fn app() -> Html {
                     <Sidemenu class="file-upload-menu">
                         <p>{"Side menu 2!"}</p>
                         <FileSelect ..file_select_props/>
+                        <ImportProgressList notification_tx={notification_tx.clone()}/>
                     </Sidemenu>
                 </Sidebar>
             </div>
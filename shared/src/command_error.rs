@@ -0,0 +1,41 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::{settings::SettingsError, graph::graph_export::GraphExportError};
+
+
+//Every backend command fails with one of these. They cross to the frontend as JSON, where they are shown as error notifications.
+//Errors from other modules are kept as their messages, since that's all the frontend shows of them.
+#[derive(Debug, Error, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CommandError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("The backend is still starting up, try again shortly.")]
+    NotInitialized,
+    #[error("No data could be read from {0}.")]
+    NoData(String),
+    #[error("Data request {0} was superseded by a newer request from the same graph.")]
+    Superseded(u32),
+    #[error("{0}")]
+    Settings(String),
+    #[error("Failed to save settings: {0}")]
+    SettingsStorage(String),
+    #[error("Failed to export graph: {0}")]
+    Export(String),
+    #[error("Failed to serialize response: {0}")]
+    Serialization(String),
+    #[error("Failed to send {0} to the frontend: {1}")]
+    Emit(String, String),
+}
+
+impl From<SettingsError> for CommandError {
+    fn from(value: SettingsError) -> Self {
+        CommandError::Settings(value.to_string())
+    }
+}
+
+impl From<GraphExportError> for CommandError {
+    fn from(value: GraphExportError) -> Self {
+        CommandError::Export(value.to_string())
+    }
+}
\ No newline at end of file
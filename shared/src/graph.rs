@@ -1,3 +1,15 @@
 pub mod graph_axis;
 pub mod graph_state_request;
-pub mod graph_type;
\ No newline at end of file
+pub mod graph_type;
+pub mod graph_theme;
+pub mod graph_draw;
+pub mod graph_draw_utils;
+pub mod graph_export;
+pub mod graph_aggregation;
+pub mod graph_fold;
+pub mod graph_histogram;
+pub mod graph_regression;
+pub mod graph_transform;
+pub mod graph_statistics;
+pub mod graph_comparison;
+pub mod graph_transport;
\ No newline at end of file
@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+
+use serde::{Serialize, Deserialize};
+use time::{Date, OffsetDateTime};
+
+use super::{graph_axis::AxisDataOption, graph_state_request::Resolution};
+
+
+//Reduces the points that fall in one time bucket to a single value.
+pub trait Aggregator {
+    //Points are in ascending time order, and a bucket always contains at least one point.
+    fn aggregate(&self, bucket: &[(i64, f64)]) -> f64;
+
+    //Aggregators that don't use buckets see every point as its own bucket.
+    fn uses_buckets(&self) -> bool {
+        true
+    }
+}
+
+impl Aggregator for AxisDataOption {
+    fn aggregate(&self, bucket: &[(i64, f64)]) -> f64 {
+        match self {
+            AxisDataOption::Average => {
+                bucket.iter().map(|point| point.1).sum::<f64>() / bucket.len() as f64
+            },
+            AxisDataOption::Minimum => {
+                bucket.iter().map(|point| point.1).fold(f64::INFINITY, f64::min)
+            },
+            AxisDataOption::Maximum => {
+                bucket.iter().map(|point| point.1).fold(f64::NEG_INFINITY, f64::max)
+            },
+            AxisDataOption::Median => {
+                percentile(bucket, 50)
+            },
+            AxisDataOption::Percentile(p) => {
+                percentile(bucket, *p)
+            },
+            AxisDataOption::Sum => {
+                bucket.iter().map(|point| point.1).sum()
+            },
+            AxisDataOption::Count => {
+                bucket.len() as f64
+            },
+            AxisDataOption::StdDev => {
+                let mean = bucket.iter().map(|point| point.1).sum::<f64>() / bucket.len() as f64;
+                let variance = bucket.iter().map(|point| (point.1 - mean).powi(2)).sum::<f64>() / bucket.len() as f64;
+                variance.sqrt()
+            },
+            AxisDataOption::First
+            | AxisDataOption::Sample => {
+                bucket.first().expect("Buckets are never empty").1
+            },
+            AxisDataOption::Last => {
+                bucket.last().expect("Buckets are never empty").1
+            },
+        }
+    }
+
+    fn uses_buckets(&self) -> bool {
+        *self != AxisDataOption::Sample
+    }
+}
+
+//Linearly interpolated percentile, p is clamped to 0..=100.
+fn percentile(bucket: &[(i64, f64)], p: u8) -> f64 {
+    let mut values = bucket.iter().map(|point| point.1).collect::<Vec<_>>();
+    values.sort_by(|a, b| a.total_cmp(b));
+    let rank = (p.min(100) as f64 / 100f64) * (values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    values[lower] + (values[upper] - values[lower]) * (rank - lower as f64)
+}
+
+//The start of the time bucket that the given timestamp falls into.
+pub fn get_bucket_start(timestamp: i64, resolution: &Resolution) -> i64 {
+    let time_interval = resolution.get_timestamp_offset();
+    timestamp - timestamp.rem_euclid(time_interval)
+}
+
+//The start of the calendar month that the given timestamp falls into. Months aren't a fixed length, so they aren't a Resolution.
+pub fn get_month_start(timestamp: i64) -> Option<i64> {
+    let date = OffsetDateTime::from_unix_timestamp(timestamp).ok()?.date();
+    let month_start = Date::from_calendar_date(date.year(), date.month(), 1).ok()?;
+    Some(month_start.midnight().assume_utc().unix_timestamp())
+}
+
+//Groups time ordered points into buckets of the given resolution, and reduces each bucket with the aggregator.
+//Returns the start time of each bucket along with its aggregated value. Buckets with no points are skipped.
+pub fn aggregate_axis_data<T, A>(data: T, resolution: &Resolution, aggregator: &A) -> Vec<(i64, f64)>
+where
+    T: IntoIterator<Item = (i64, f64)>,
+    A: Aggregator + ?Sized,
+{
+    let mut aggregated_data = Vec::new();
+    let mut storage: Vec<(i64, f64)> = Vec::new();
+    let mut current_bucket = None;
+    for point in data {
+        let bucket = if aggregator.uses_buckets() {
+            get_bucket_start(point.0, resolution)
+        } else {
+            point.0
+        };
+        if current_bucket != Some(bucket) {
+            if let Some(previous_bucket) = current_bucket {
+                aggregated_data.push((previous_bucket, aggregator.aggregate(&storage)));
+                storage.clear();
+            }
+            current_bucket = Some(bucket);
+        }
+        storage.push(point);
+    }
+    if let Some(previous_bucket) = current_bucket {
+        aggregated_data.push((previous_bucket, aggregator.aggregate(&storage)));
+    }
+    aggregated_data
+}
+
+
+//How to pair up x and y values when one side has no value for a bucket.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug, Default)]
+pub enum BucketFillPolicy {
+    //Only keep buckets where both sides have a value.
+    #[default]
+    Drop,
+    //Use the most recent earlier value.
+    Previous,
+    //Linearly interpolate between the surrounding values.
+    Linear,
+}
+
+//Pairs two aggregated series by their bucket timestamps, so that every (x, y) pair comes from the same time.
+//A side that was bucketed can also answer for any timestamp inside one of its buckets, which lets raw samples pair with bucketed data.
+//Returns (bucket timestamp, x, y) in ascending time order.
+pub fn join_on_buckets(x_data: &[(i64, f64)], x_bucketed: bool, y_data: &[(i64, f64)], y_bucketed: bool, resolution: &Resolution, fill_policy: &BucketFillPolicy) -> Vec<(i64, f64, f64)> {
+    let mut timestamps = x_data.iter().map(|point| point.0)
+        .chain(y_data.iter().map(|point| point.0))
+        .collect::<Vec<_>>();
+    timestamps.sort_unstable();
+    timestamps.dedup();
+    //When one side is bucketed and the other isn't, the bucket start itself is only a real point for the bucketed side.
+    if x_bucketed != y_bucketed {
+        let bucketed_data = if x_bucketed { x_data } else { y_data };
+        let raw_data = if x_bucketed { y_data } else { x_data };
+        let raw_buckets = raw_data.iter().map(|point| get_bucket_start(point.0, resolution)).collect::<HashSet<_>>();
+        timestamps.retain(|timestamp| {
+            bucketed_data.binary_search_by_key(timestamp, |point| point.0).is_err() || !raw_buckets.contains(timestamp)
+        });
+    }
+
+    let x_values = timestamps.iter().map(|timestamp| find_value(x_data, x_bucketed, *timestamp, resolution)).collect::<Vec<_>>();
+    let y_values = timestamps.iter().map(|timestamp| find_value(y_data, y_bucketed, *timestamp, resolution)).collect::<Vec<_>>();
+    let x_values = fill_values(&timestamps, x_values, fill_policy);
+    let y_values = fill_values(&timestamps, y_values, fill_policy);
+
+    timestamps.into_iter().zip(x_values).zip(y_values).filter_map(|((timestamp, x), y)| {
+        Some((timestamp, x?, y?))
+    }).collect()
+}
+
+fn find_value(data: &[(i64, f64)], bucketed: bool, timestamp: i64, resolution: &Resolution) -> Option<f64> {
+    let key = if bucketed {
+        get_bucket_start(timestamp, resolution)
+    } else {
+        timestamp
+    };
+    data.binary_search_by_key(&key, |point| point.0).ok().map(|index| data[index].1)
+}
+
+fn fill_values(timestamps: &[i64], values: Vec<Option<f64>>, fill_policy: &BucketFillPolicy) -> Vec<Option<f64>> {
+    match fill_policy {
+        BucketFillPolicy::Drop => values,
+        BucketFillPolicy::Previous => {
+            let mut previous = None;
+            values.into_iter().map(|value| {
+                if value.is_some() {
+                    previous = value;
+                }
+                previous
+            }).collect()
+        },
+        BucketFillPolicy::Linear => {
+            let known = timestamps.iter().zip(values.iter()).filter_map(|(timestamp, value)| {
+                value.map(|value| (*timestamp, value))
+            }).collect::<Vec<_>>();
+            timestamps.iter().zip(values.iter()).map(|(timestamp, value)| {
+                if value.is_some() {
+                    return *value;
+                }
+                match known.binary_search_by_key(timestamp, |point| point.0) {
+                    Ok(index) => Some(known[index].1),
+                    Err(index) if index > 0 && index < known.len() => {
+                        let before = known[index - 1];
+                        let after = known[index];
+                        Some(before.1 + (after.1 - before.1) * (timestamp - before.0) as f64 / (after.0 - before.0) as f64)
+                    },
+                    Err(_) => None,
+                }
+            }).collect()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hourly_data() -> Vec<(i64, f64)> {
+        //Two one hour buckets: [1, 5, 3] and [10, 2]
+        vec![(0, 1.0), (600, 5.0), (1200, 3.0), (3600, 10.0), (4200, 2.0)]
+    }
+
+    #[test]
+    fn average_minimum_maximum() {
+        let data = hourly_data();
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Average), vec![(0, 3.0), (3600, 6.0)]);
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Minimum), vec![(0, 1.0), (3600, 2.0)]);
+        assert_eq!(aggregate_axis_data(data, &Resolution::OneHour, &AxisDataOption::Maximum), vec![(0, 5.0), (3600, 10.0)]);
+    }
+
+    #[test]
+    fn maximum_of_final_bucket() {
+        let data = vec![(0, 1.0), (3600, 2.0), (3660, 9.0), (3720, 4.0)];
+        assert_eq!(aggregate_axis_data(data, &Resolution::OneHour, &AxisDataOption::Maximum), vec![(0, 1.0), (3600, 9.0)]);
+    }
+
+    #[test]
+    fn median_and_percentile() {
+        let data = hourly_data();
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Median), vec![(0, 3.0), (3600, 6.0)]);
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Percentile(0)), vec![(0, 1.0), (3600, 2.0)]);
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Percentile(100)), vec![(0, 5.0), (3600, 10.0)]);
+        assert_eq!(aggregate_axis_data(data, &Resolution::OneHour, &AxisDataOption::Percentile(25)), vec![(0, 2.0), (3600, 4.0)]);
+    }
+
+    #[test]
+    fn sum_count_and_standard_deviation() {
+        let data = hourly_data();
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Sum), vec![(0, 9.0), (3600, 12.0)]);
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Count), vec![(0, 3.0), (3600, 2.0)]);
+        assert_eq!(aggregate_axis_data(data, &Resolution::OneHour, &AxisDataOption::StdDev), vec![(0, (8f64 / 3f64).sqrt()), (3600, 4.0)]);
+    }
+
+    #[test]
+    fn first_last_and_sample() {
+        let data = hourly_data();
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::First), vec![(0, 1.0), (3600, 10.0)]);
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Last), vec![(0, 3.0), (3600, 2.0)]);
+        assert_eq!(aggregate_axis_data(data.clone(), &Resolution::OneHour, &AxisDataOption::Sample), data);
+    }
+
+    #[test]
+    fn empty_and_gapped_data() {
+        assert_eq!(aggregate_axis_data(Vec::new(), &Resolution::OneHour, &AxisDataOption::Average), Vec::new());
+        let data = vec![(0, 1.0), (86400 * 2 + 5, 3.0)];
+        assert_eq!(aggregate_axis_data(data, &Resolution::OneDay, &AxisDataOption::Average), vec![(0, 1.0), (86400 * 2, 3.0)]);
+    }
+
+    #[test]
+    fn join_drops_unpaired_buckets() {
+        let x = vec![(0, 1.0), (3600, 2.0), (7200, 3.0)];
+        let y = vec![(0, 10.0), (7200, 30.0)];
+        assert_eq!(join_on_buckets(&x, true, &y, true, &Resolution::OneHour, &BucketFillPolicy::Drop), vec![(0, 1.0, 10.0), (7200, 3.0, 30.0)]);
+    }
+
+    #[test]
+    fn join_fills_missing_buckets() {
+        let x = vec![(0, 1.0), (3600, 2.0), (7200, 3.0)];
+        let y = vec![(0, 10.0), (7200, 30.0)];
+        assert_eq!(join_on_buckets(&x, true, &y, true, &Resolution::OneHour, &BucketFillPolicy::Previous), vec![(0, 1.0, 10.0), (3600, 2.0, 10.0), (7200, 3.0, 30.0)]);
+        assert_eq!(join_on_buckets(&x, true, &y, true, &Resolution::OneHour, &BucketFillPolicy::Linear), vec![(0, 1.0, 10.0), (3600, 2.0, 20.0), (7200, 3.0, 30.0)]);
+    }
+
+    #[test]
+    fn join_with_either_side_missing_buckets() {
+        //x is missing the first and fourth buckets, y the second and last.
+        let x = vec![(3600, 2.0), (7200, 3.0), (14400, 5.0)];
+        let y = vec![(0, 10.0), (7200, 30.0), (10800, 40.0)];
+        let join = |fill_policy| join_on_buckets(&x, true, &y, true, &Resolution::OneHour, &fill_policy);
+        assert_eq!(join(BucketFillPolicy::Drop), vec![(7200, 3.0, 30.0)]);
+        //Nothing comes before the first value of a side, so those buckets are still dropped.
+        assert_eq!(join(BucketFillPolicy::Previous), vec![(3600, 2.0, 10.0), (7200, 3.0, 30.0), (10800, 3.0, 40.0), (14400, 5.0, 40.0)]);
+        //Interpolation needs a value on both sides of a bucket.
+        assert_eq!(join(BucketFillPolicy::Linear), vec![(3600, 2.0, 20.0), (7200, 3.0, 30.0), (10800, 4.0, 40.0)]);
+    }
+
+    #[test]
+    fn join_raw_samples_with_buckets() {
+        let x = vec![(60, 1.0), (120, 2.0), (3660, 3.0)];
+        let y = vec![(0, 10.0), (3600, 20.0)];
+        assert_eq!(join_on_buckets(&x, false, &y, true, &Resolution::OneHour, &BucketFillPolicy::Drop), vec![(60, 1.0, 10.0), (120, 2.0, 10.0), (3660, 3.0, 20.0)]);
+        //A bucket without any raw samples keeps its start, so filling can pair it with the previous sample.
+        let y = vec![(0, 10.0), (3600, 20.0), (7200, 30.0)];
+        assert_eq!(join_on_buckets(&x, false, &y, true, &Resolution::OneHour, &BucketFillPolicy::Previous), vec![(60, 1.0, 10.0), (120, 2.0, 10.0), (3660, 3.0, 20.0), (7200, 3.0, 30.0)]);
+    }
+}
\ No newline at end of file
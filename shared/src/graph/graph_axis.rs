@@ -1,18 +1,62 @@
 use serde::{Deserialize, Serialize};
 
-use super::graph_state_request::Resolution;
+use super::{graph_aggregation::BucketFillPolicy, graph_comparison::{ComparisonShift, ComparisonFrame}, graph_fold::{Period, PeriodicDisplay}, graph_state_request::Resolution, graph_transform::SeriesTransform};
 
 
 #[derive(PartialEq, Eq, Clone, Serialize, Deserialize, Default, Debug)]
 pub struct AxisControlsRequest {
-    pub requests: Vec<(AxisDataType,AxisDataOption)>
+    pub requests: Vec<(AxisDataType,AxisDataOption)>,
+    //At most one transform per requested series. Series without one are drawn as bucketed.
+    #[serde(default)]
+    pub transforms: Vec<((AxisDataType, AxisDataOption), SeriesTransform)>,
+}
+
+impl AxisControlsRequest {
+    pub fn get_transform(&self, data_type: &AxisDataType, data_option: &AxisDataOption) -> Option<SeriesTransform> {
+        self.transforms.iter()
+            .find(|((transform_type, transform_option), _)| transform_type == data_type && transform_option == data_option)
+            .map(|(_, transform)| *transform)
+    }
+
+    //None removes the series' transform.
+    pub fn set_transform(&mut self, data_type: &AxisDataType, data_option: &AxisDataOption, transform: Option<SeriesTransform>) {
+        self.transforms.retain(|((transform_type, transform_option), _)| !(transform_type == data_type && transform_option == data_option));
+        if let Some(transform) = transform {
+            self.transforms.push(((data_type.clone(), data_option.clone()), transform));
+        }
+    }
+
+    //Changes which percentile a requested percentile series takes, keeping its transform. Returns false if the series isn't a
+    // percentile, or the new one is already requested.
+    pub fn set_percentile(&mut self, index: usize, percentile: u8) -> bool {
+        let (data_type, data_option) = match self.requests.get(index) {
+            Some((data_type, data_option @ AxisDataOption::Percentile(_))) => (data_type.clone(), data_option.clone()),
+            _ => return false,
+        };
+        let new_option = AxisDataOption::Percentile(percentile.min(100));
+        if self.requests.contains(&(data_type.clone(), new_option.clone())) {
+            return false
+        }
+        let transform = self.get_transform(&data_type, &data_option);
+        self.set_transform(&data_type, &data_option, None);
+        self.set_transform(&data_type, &new_option, transform);
+        self.requests[index] = (data_type, new_option);
+        true
+    }
+
+    //Drops transforms for series that are no longer requested, so they don't come back if the series is picked again later.
+    pub fn retain_requested_transforms(&mut self) {
+        let requests = &self.requests;
+        self.transforms.retain(|(series, _)| requests.contains(series));
+    }
 }
 
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum AxisDataType {
     Time,
-    PeriodicTime,
+    //Time folded onto a repeating period, see graph_fold.
+    PeriodicTime(Period),
     BatteryVoltage,
     BatteryAmps,
     SolarWatts,
@@ -23,14 +67,36 @@ pub enum AxisDataType {
     ControllerPanelVoltage(u16),
     ControllerAmps(u16),
     ControllerTemperatureF(u16),
-    // Custom(String),
+    //Energy is integrated from the matching power channel, so it can't be read from a single line.
+    SolarEnergy,
+    LoadEnergy,
+    SolarEnergyTotal,
+    LoadEnergyTotal,
+    //Cell balance, see solar_data::cell_balance.
+    CellSpread,
+    HighestCell,
+    LowestCell,
+    CellDeviation(u16),
+    //Monthly capacity estimates, see solar_data::capacity. State of health needs the nominal capacity from the settings.
+    BatteryCapacity,
+    StateOfHealthPercent,
+    //Clear sky expectations from the site in the settings, see solar_data::clear_sky. Performance ratio has one point per day.
+    ExpectedSolarWatts,
+    ExpectedControllerAmps(u16),
+    PerformanceRatioPercent,
+    //Inferred from the controller's output over time, see solar_data::charge_stage.
+    ChargeStage(u16),
+    //A derived channel, by name. The expression is saved in the settings, see solar_data::expression.
+    Custom(String),
 }
 
 impl AxisDataType {
     pub fn get_name(&self) -> String {
         match self {
             AxisDataType::Time => "Time".to_owned(),
-            AxisDataType::PeriodicTime => "Time".to_owned(),
+            AxisDataType::PeriodicTime(Period::Day) => "Time of Day".to_owned(),
+            AxisDataType::PeriodicTime(Period::Week) => "Day of Week".to_owned(),
+            AxisDataType::PeriodicTime(Period::Year) => "Day of Year".to_owned(),
             AxisDataType::BatteryVoltage => "Battery Voltage".to_owned(),
             AxisDataType::BatteryAmps => "Battery Amps".to_owned(),
             AxisDataType::SolarWatts => "Solar Watts".to_owned(),
@@ -40,24 +106,52 @@ impl AxisDataType {
             AxisDataType::ControllerPanelVoltage(controller) => format!("Controller #{} Voltage", {controller}),
             AxisDataType::ControllerAmps(controller) => format!("Controller #{} Amps", {controller}),
             AxisDataType::ControllerTemperatureF(controller) => format!("Controller #{} TemperatureF", {controller}),
-            // AxisDataType::Custom(s) => todo!(),
+            AxisDataType::SolarEnergy => "Solar Energy".to_owned(),
+            AxisDataType::LoadEnergy => "Load Energy".to_owned(),
+            AxisDataType::SolarEnergyTotal => "Solar Energy Total".to_owned(),
+            AxisDataType::LoadEnergyTotal => "Load Energy Total".to_owned(),
+            AxisDataType::CellSpread => "Cell Spread".to_owned(),
+            AxisDataType::HighestCell => "Highest Cell".to_owned(),
+            AxisDataType::LowestCell => "Lowest Cell".to_owned(),
+            AxisDataType::CellDeviation(cell) => format!("Cell #{} Deviation", {cell}),
+            AxisDataType::BatteryCapacity => "Battery Capacity".to_owned(),
+            AxisDataType::StateOfHealthPercent => "S.O.H. %".to_owned(),
+            AxisDataType::ExpectedSolarWatts => "Expected Solar Watts".to_owned(),
+            AxisDataType::ExpectedControllerAmps(controller) => format!("Controller #{} Expected Amps", {controller}),
+            AxisDataType::PerformanceRatioPercent => "Performance Ratio %".to_owned(),
+            AxisDataType::ChargeStage(controller) => format!("Controller #{} Charge Stage", {controller}),
+            AxisDataType::Custom(name) => name.clone(),
         }
     }
 
     pub fn get_unit(&self) -> DataUnit {
         match self {
             AxisDataType::Time => DataUnit::Time,
-            AxisDataType::PeriodicTime => DataUnit::PeriodicTime,
+            AxisDataType::PeriodicTime(_) => DataUnit::PeriodicTime,
             AxisDataType::BatteryVoltage
             | AxisDataType::CellVoltage(_) 
             | AxisDataType::ControllerPanelVoltage(_) => DataUnit::Voltage,
             AxisDataType::BatteryAmps 
-            | AxisDataType::ControllerAmps(_) => DataUnit::Amps,
+            | AxisDataType::ControllerAmps(_)
+            | AxisDataType::ExpectedControllerAmps(_) => DataUnit::Amps,
             AxisDataType::SolarWatts 
-            | AxisDataType::LoadWatts => DataUnit::Watts,
-            AxisDataType::StateOfChargePercent => DataUnit::Percent,
+            | AxisDataType::LoadWatts
+            | AxisDataType::ExpectedSolarWatts => DataUnit::Watts,
+            AxisDataType::StateOfChargePercent
+            | AxisDataType::StateOfHealthPercent
+            | AxisDataType::PerformanceRatioPercent => DataUnit::Percent,
+            AxisDataType::BatteryCapacity => DataUnit::AmpHours,
             AxisDataType::ControllerTemperatureF(_) => DataUnit::Farenheight,
-            // AxisDataType::Custom(s) => todo!(),
+            AxisDataType::SolarEnergy
+            | AxisDataType::LoadEnergy
+            | AxisDataType::SolarEnergyTotal
+            | AxisDataType::LoadEnergyTotal => DataUnit::KilowattHours,
+            AxisDataType::CellSpread
+            | AxisDataType::CellDeviation(_) => DataUnit::Voltage,
+            AxisDataType::HighestCell
+            | AxisDataType::LowestCell => DataUnit::Cell,
+            AxisDataType::ChargeStage(_) => DataUnit::ChargeStage,
+            AxisDataType::Custom(_) => DataUnit::Derived,
         }
     }
 }
@@ -71,6 +165,12 @@ pub enum DataUnit {
     Watts,
     Percent,
     Farenheight,
+    KilowattHours,
+    Derived,
+    Cell,
+    AmpHours,
+    //0 idle, 1 bulk, 2 absorption, 3 float.
+    ChargeStage,
 }
 
 impl DataUnit {
@@ -83,6 +183,11 @@ impl DataUnit {
             DataUnit::Watts => "Watts",
             DataUnit::Percent => "Percent",
             DataUnit::Farenheight => "°Fahrenheit",
+            DataUnit::KilowattHours => "kWh",
+            DataUnit::Derived => "Derived",
+            DataUnit::Cell => "Cell #",
+            DataUnit::AmpHours => "Amp Hours",
+            DataUnit::ChargeStage => "Charge Stage",
         }
     }
 }
@@ -94,28 +199,76 @@ pub enum AxisZoom {
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum AxisDataOption {
-    // Sample,
     Average,
     Minimum,
     Maximum,
+    Median,
+    Percentile(u8),
+    Sum,
+    Count,
+    StdDev,
+    First,
+    Last,
+    Sample,
 }
 
+impl AxisDataOption {
+    //All options that can be picked in the axis controls. Percentiles start from these presets, and can then be set to any
+    // percentile next to the series, see AxisControlsRequest::set_percentile.
+    pub fn get_selectable_options() -> Vec<AxisDataOption> {
+        vec![
+            AxisDataOption::Average,
+            AxisDataOption::Minimum,
+            AxisDataOption::Maximum,
+            AxisDataOption::Median,
+            AxisDataOption::Percentile(5),
+            AxisDataOption::Percentile(25),
+            AxisDataOption::Percentile(75),
+            AxisDataOption::Percentile(95),
+            AxisDataOption::Sum,
+            AxisDataOption::Count,
+            AxisDataOption::StdDev,
+            AxisDataOption::First,
+            AxisDataOption::Last,
+            AxisDataOption::Sample,
+        ]
+    }
 
-#[derive(PartialEq, Serialize, Deserialize, Default, Debug)]
+    pub fn get_name(&self) -> String {
+        match self {
+            AxisDataOption::Average => "Avg".to_owned(),
+            AxisDataOption::Minimum => "Min".to_owned(),
+            AxisDataOption::Maximum => "Max".to_owned(),
+            AxisDataOption::Median => "Median".to_owned(),
+            AxisDataOption::Percentile(p) => format!("P{}", p),
+            AxisDataOption::Sum => "Sum".to_owned(),
+            AxisDataOption::Count => "Count".to_owned(),
+            AxisDataOption::StdDev => "Std Dev".to_owned(),
+            AxisDataOption::First => "First".to_owned(),
+            AxisDataOption::Last => "Last".to_owned(),
+            AxisDataOption::Sample => "Sample".to_owned(),
+        }
+    }
+}
+
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
 pub struct LineSeriesHolder {
     pub series: Vec<LineSeriesData>,
     pub secondary_series: Vec<LineSeriesData>,
 }
 
-#[derive(PartialEq, Serialize, Deserialize, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct LineSeriesData {
     pub name: String,
     pub data_points: Vec<(f64, f64)>,
+    //The start of the time bucket each data point was built from, in the same order as data_points.
+    pub timestamps: Vec<i64>,
     pub x_axis: LineSeriesAxisData,
     pub y_axis: LineSeriesAxisData,
 }
 
-#[derive(PartialEq, Serialize, Deserialize, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct LineSeriesAxisData {
     pub data_type: AxisDataType,
     pub data_option: AxisDataOption,
@@ -126,9 +279,21 @@ pub struct AxisTimeRequest {
     pub start: i64,
     pub end: i64,
     pub manual_resolution: Option<Resolution>,
+    #[serde(default)]
+    pub fill_policy: BucketFillPolicy,
+    #[serde(default)]
+    pub periodic_display: PeriodicDisplay,
+    //Earlier periods overlaid on this one, lined up with its start.
+    #[serde(default)]
+    pub comparisons: Vec<ComparisonShift>,
 }
 
 impl AxisTimeRequest {
+    //The time frame of every compared period. Shifts that can't be made, from the edge of the representable dates, are left out.
+    pub fn get_comparison_frames(&self) -> Vec<ComparisonFrame> {
+        self.comparisons.iter().filter_map(|shift| shift.get_frame(self.start, self.end)).collect()
+    }
+
     pub fn get_resolution(&self) -> Resolution {
         match &self.manual_resolution {
             Some(resolution) => resolution.clone(),
@@ -153,4 +318,34 @@ impl AxisTimeRequest {
             },
         }
     }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_percentile() {
+        let mut request = AxisControlsRequest {
+            requests: vec![
+                (AxisDataType::SolarWatts, AxisDataOption::Percentile(95)),
+                (AxisDataType::SolarWatts, AxisDataOption::Percentile(25)),
+                (AxisDataType::LoadWatts, AxisDataOption::Average),
+            ],
+            transforms: Vec::new(),
+        };
+        request.set_transform(&AxisDataType::SolarWatts, &AxisDataOption::Percentile(95), Some(SeriesTransform::Derivative));
+        assert!(request.set_percentile(0, 99));
+        assert_eq!(request.requests[0], (AxisDataType::SolarWatts, AxisDataOption::Percentile(99)));
+        //The transform follows the series.
+        assert_eq!(request.get_transform(&AxisDataType::SolarWatts, &AxisDataOption::Percentile(99)), Some(SeriesTransform::Derivative));
+        assert_eq!(request.transforms.len(), 1);
+        assert!(request.set_percentile(1, 200));
+        assert_eq!(request.requests[1].1, AxisDataOption::Percentile(100));
+        //Already requested, and not a percentile.
+        assert!(!request.set_percentile(1, 99));
+        assert!(!request.set_percentile(2, 50));
+        assert!(!request.set_percentile(3, 50));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,120 @@
+use serde::{Serialize, Deserialize};
+use time::{Date, OffsetDateTime, macros::format_description};
+
+use super::graph_axis::{AxisDataType, LineSeriesData};
+
+
+//How far back an earlier period is from the graph's own time frame. Months and years are calendar shifts, so this July is compared
+// with last July even though the years are different lengths.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub enum ComparisonShift {
+    Days(u16),
+    Weeks(u16),
+    Months(u16),
+    Years(u16),
+}
+
+//One period being compared, and how far its data has to move to line up with the graph's own time frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ComparisonFrame {
+    pub start: i64,
+    pub end: i64,
+    pub offset: i64,
+}
+
+impl ComparisonShift {
+    pub fn get_name(&self) -> String {
+        let (count, unit) = match self {
+            ComparisonShift::Days(count) => (count, "Day"),
+            ComparisonShift::Weeks(count) => (count, "Week"),
+            ComparisonShift::Months(count) => (count, "Month"),
+            ComparisonShift::Years(count) => (count, "Year"),
+        };
+        match count {
+            1 => format!("1 {} Earlier", unit),
+            _ => format!("{} {}s Earlier", count, unit),
+        }
+    }
+
+    //The comparisons offered by the controls.
+    pub fn get_presets() -> Vec<ComparisonShift> {
+        vec![
+            ComparisonShift::Days(1),
+            ComparisonShift::Weeks(1),
+            ComparisonShift::Months(1),
+            ComparisonShift::Years(1),
+            ComparisonShift::Years(2),
+        ]
+    }
+
+    //The timestamp this far back. Days past the end of a shorter month land on its last day, so March 31st a month back is the last day of February.
+    pub fn shift(&self, timestamp: i64) -> Option<i64> {
+        let months = match self {
+            ComparisonShift::Days(days) => return Some(timestamp - *days as i64 * 86400),
+            ComparisonShift::Weeks(weeks) => return Some(timestamp - *weeks as i64 * 7 * 86400),
+            ComparisonShift::Months(months) => *months as i32,
+            ComparisonShift::Years(years) => *years as i32 * 12,
+        };
+        let date_time = OffsetDateTime::from_unix_timestamp(timestamp).ok()?;
+        let month_index = date_time.year() * 12 + date_time.month() as i32 - 1 - months;
+        let (year, month) = (month_index.div_euclid(12), time::Month::try_from((month_index.rem_euclid(12) + 1) as u8).ok()?);
+        let day = date_time.day().min(time::util::days_in_month(month, year));
+        Some(Date::from_calendar_date(year, month, day).ok()?.with_time(date_time.time()).assume_utc().unix_timestamp())
+    }
+
+    //The same span of the calendar this far back. Both ends are shifted, so a month compared with the month before covers that whole month.
+    pub fn get_frame(&self, start: i64, end: i64) -> Option<ComparisonFrame> {
+        let shifted_start = self.shift(start)?;
+        Some(ComparisonFrame { start: shifted_start, end: self.shift(end)?, offset: start - shifted_start })
+    }
+}
+
+//The legend label for a period.
+pub fn get_frame_name(start: i64, end: i64) -> String {
+    let format = format_description!("[year]-[month]-[day]");
+    let format_date = |timestamp: i64| OffsetDateTime::from_unix_timestamp(timestamp).ok()
+        .and_then(|date_time| date_time.format(format).ok())
+        .unwrap_or_else(|| timestamp.to_string());
+    //The end of a time frame is exclusive, so the last day shown is the one before it.
+    format!("{} to {}", format_date(start), format_date((end - 1).max(start)))
+}
+
+//Moves a series from an earlier period onto the graph's own time frame and labels it with its period. Only time x values move, other x
+// axes are already shared between periods. Timestamps keep the real time each point came from.
+pub fn shift_series(series: LineSeriesData, frame: &ComparisonFrame) -> LineSeriesData {
+    let data_points = match series.x_axis.data_type {
+        AxisDataType::Time => series.data_points.into_iter().map(|(x, y)| (x + frame.offset as f64, y)).collect(),
+        _ => series.data_points,
+    };
+    LineSeriesData {
+        name: format!("{} ({})", series.name, get_frame_name(frame.start, frame.end)),
+        data_points,
+        ..series
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn calendar_shifts() {
+        let march_31 = datetime!(2024-03-31 12:00).assume_utc().unix_timestamp();
+        assert_eq!(ComparisonShift::Months(1).shift(march_31), Some(datetime!(2024-02-29 12:00).assume_utc().unix_timestamp()));
+        assert_eq!(ComparisonShift::Years(1).shift(march_31), Some(datetime!(2023-03-31 12:00).assume_utc().unix_timestamp()));
+        assert_eq!(ComparisonShift::Months(3).shift(march_31), Some(datetime!(2023-12-31 12:00).assume_utc().unix_timestamp()));
+        assert_eq!(ComparisonShift::Weeks(1).shift(march_31), Some(march_31 - 7 * 86400));
+    }
+
+    #[test]
+    fn frames_line_up_with_the_time_frame() {
+        let start = datetime!(2023-07-01 0:00).assume_utc().unix_timestamp();
+        let end = datetime!(2023-08-01 0:00).assume_utc().unix_timestamp();
+        let frame = ComparisonShift::Years(1).get_frame(start, end).unwrap();
+        assert_eq!(frame.start, datetime!(2022-07-01 0:00).assume_utc().unix_timestamp());
+        assert_eq!(frame.end + frame.offset, end);
+        assert_eq!(get_frame_name(frame.start, frame.end), "2022-07-01 to 2022-07-31");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,913 @@
+use std::ops::Range;
+use plotters::{prelude::*, coord::{ranged1d::{ValueFormatter, NoDefaultFormatting, KeyPointHint, KeyPointWeight}, types::RangedCoordf64, Shift}};
+use serde::{Serialize, Deserialize};
+use time::{OffsetDateTime, PrimitiveDateTime, Time, Date};
+
+use crate::solar_data::{clear_sky::SolarSite, anomalies::Anomaly};
+use super::{graph_axis::{AxisDataType, AxisDataOption, LineSeriesHolder}, graph_state_request::{GraphStateRequest, NightShading}, graph_theme::ThemeData, graph_type::GraphType, graph_regression::get_series_trend_fits, graph_draw_utils::{other_axis_label_formatter, relative_time_axis_label_formatter, time_axis_label_formatter, time_of_day_axis_label_formatter, day_of_week_axis_label_formatter, day_of_year_axis_label_formatter, get_x_range, get_y_range}, graph_fold::Period};
+
+pub const CHART_MARGIN_SIZE: u32 = 10;
+pub const CHART_LABEL_SIZE: u32 = 50;
+//Beyond this many days on screen, the nights blur into stripes that hide the data rather than explain it.
+pub const MAX_NIGHT_SHADING_DAYS: f64 = 93f64;
+
+//The visible ranges of each axis. These are owned by whoever is displaying the graph, since they change as the user pans and zooms.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct GraphRanges {
+    pub x_range: Range<f64>,
+    pub y_range: Range<f64>,
+    pub sec_y_range: Range<f64>,
+}
+
+//Things drawn behind the data that don't come from the graph's own series.
+#[derive(Clone, Copy, Default)]
+pub struct GraphOverlays<'a> {
+    //Needed to shade night.
+    pub solar_site: Option<&'a SolarSite>,
+    pub anomalies: &'a [Anomaly],
+}
+
+impl GraphRanges {
+    //Ranges that fit all of the data in the given line series.
+    pub fn from_line_series(line_series: &LineSeriesHolder) -> Self {
+        GraphRanges {
+            x_range: get_x_range(&line_series.series),
+            y_range: get_y_range(&line_series.series),
+            sec_y_range: get_y_range(&line_series.secondary_series),
+        }
+    }
+}
+
+pub struct GraphDataRange {
+    pub range: RangedCoordf64,
+    pub data_type: AxisDataType,
+}
+
+impl Ranged for GraphDataRange {
+    type FormatOption = NoDefaultFormatting;
+    type ValueType = f64;
+
+    fn map(&self, value: &Self::ValueType, limit: (i32, i32)) -> i32 {
+        self.range.map(value, limit)
+    }
+
+    // Function implementation invariants:
+    // 1. Stored unix timestamps must be valid.
+    // 2. The range must be within Time's limit of +- 9999 years (inclusive).
+    fn key_points<Hint: KeyPointHint>(&self, hint: Hint) -> Vec<Self::ValueType> {
+        //This function gets called twice, once for bold lines, once for light lines. Check which type with the KeyPointH
+        
+        match self.data_type {
+            //Folded periodic time sits in the first period of 1970, so the same calendar divisions work for it. A folded day gets hour lines,
+            // a folded week gets day lines, and a folded year gets month lines.
+            AxisDataType::Time | AxisDataType::PeriodicTime(_) => {
+                let start = self.range.range().start;
+                let end = self.range.range().end;
+                //We want even divisions of:
+                //1 Year,
+                //1 Month,
+                //1 Week,
+                //1 Day,
+                //6 Hour,
+                //1 Hour,
+                //15 Minutes,
+                //5 Minutes,
+                //All of these line up as nice subdivisions of the larger unit, with the exception of weeks
+                //There are a fractional number of weeks per month, and furthermore not all months have the same number of days.
+                //We want to show two of these divisions at all times, in both bold and regular lines. For example, if years are bold, then months are normal
+                //If Months are bold, then weeks are normal.
+                //If 15 minutes are bold, then 5 minutes are normal. Etc.
+                //Transition from one unit to a smaller unit if less than 1 whole unit is visible.
+                //Transition from one unit to a larger unit if at least 1 whole larger unit is visible.
+                let mut key_points: Vec<f64> = Vec::new();
+                // The start time stored as a OffsetDateTime for convenience.
+                let start_time = OffsetDateTime::from_unix_timestamp(start as i64).expect("All stored timestamps are valid");
+                match end - start {
+                    //If we contain more than 10 years. This is necessary so we don't overflow the max amount of dividing lines as a user zooms out excessively.
+                    range if range >= (60*60*24*365*10) as f64 => {
+                        // Largest power of 10 (years) that fits inside the range (in years)
+                        let bold_interval = 10i32.pow((range / (60*60*24*365*10) as f64).log10().floor() as u32);
+                        match hint.weight() {
+                            // Bold lines will be the highest possible division that is less than 10. ie a 50 year span will have 10 year divisions.
+                            KeyPointWeight::Bold => {
+                                let bold_line_year = start_time.year() - (start_time.year() % bold_interval) + bold_interval;
+                                let mut bold_line_time = OffsetDateTime::UNIX_EPOCH.replace_year(bold_line_year)
+                                    .expect("Year should be within Time's +-9999 year limit");
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = bold_line_time.replace_year(bold_line_time.year() + bold_interval)
+                                        .expect("Year should be within Time's +-9999 year limit");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will subdivide the bold sections 10 times
+                            KeyPointWeight::Any => {
+                                // Subdivide the bold lines into 10 sections
+                                let light_interval = (bold_interval / 10).max(1);
+                                let light_line_year = start_time.year() - (start_time.year() % light_interval) + light_interval;
+                                let mut light_line_time = OffsetDateTime::UNIX_EPOCH.replace_year(light_line_year)
+                                    .expect("Year should be within Time's +-9999 year limit");
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = light_line_time.replace_year(light_line_time.year() + light_interval)
+                                        .expect("Year should be within Time's +-9999 year limit");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //If we contain at least a whole year* (365 days)
+                    range if range >= (60*60*24*365) as f64 => {      
+                        match hint.weight() {
+                            // Bold lines will be every year
+                            KeyPointWeight::Bold => {
+                                let bold_line = start_time.year() + 1;
+                                let mut bold_line_time = OffsetDateTime::UNIX_EPOCH.replace_year(bold_line)
+                                    .expect("Year should be within Time's +-9999 year limit");
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = bold_line_time.replace_year(bold_line_time.year() + 1)
+                                        .expect("Year should be within Time's +-9999 year limit");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every month
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = if start_time.month() == time::Month::December {
+                                    start_time.replace_date_time(
+                                        PrimitiveDateTime::new(
+                                            Date::from_calendar_date(start_time.year() + 1, start_time.month().next(), 1).expect("Valid date format"), 
+                                            Time::MIDNIGHT
+                                        )
+                                    )
+                                } else {
+                                    start_time.replace_date_time(
+                                        PrimitiveDateTime::new(
+                                            Date::from_calendar_date(start_time.year(), start_time.month().next(), 1).expect("Valid date format"), 
+                                            Time::MIDNIGHT
+                                        )
+                                    )
+                                };
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = if light_line_time.month() == time::Month::December {
+                                        light_line_time.replace_month(light_line_time.month().next())
+                                        .expect("Month should be valid")
+                                        .replace_year(light_line_time.year() + 1)
+                                        .expect("Year should be within Time's +-9999 year limit")
+                                    } else {
+                                        light_line_time.replace_month(light_line_time.month().next())
+                                        .expect("Month should be valid")
+                                    };
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //If we contain at least a whole month* (30 days)
+                    range if range >= (60*60*24*30) as f64 => { 
+                        match hint.weight() {
+                            // Bold lines will be every month
+                            KeyPointWeight::Bold => {
+                                let mut bold_line_time = if start_time.month() == time::Month::December {
+                                    start_time.replace_date_time(
+                                        PrimitiveDateTime::new(
+                                            Date::from_calendar_date(start_time.year() + 1, start_time.month().next(), 1).expect("Valid date format"), 
+                                            Time::MIDNIGHT
+                                        )
+                                    )
+                                } else {
+                                    start_time.replace_date_time(
+                                        PrimitiveDateTime::new(
+                                            Date::from_calendar_date(start_time.year(), start_time.month().next(), 1).expect("Valid date format"), 
+                                            Time::MIDNIGHT
+                                        )
+                                    )
+                                };
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = if bold_line_time.month() == time::Month::December {
+                                        bold_line_time.replace_month(bold_line_time.month().next())
+                                        .expect("Month should be valid")
+                                        .replace_year(bold_line_time.year() + 1)
+                                        .expect("Year should be within Time's +-9999 year limit")
+                                    } else {
+                                        bold_line_time.replace_month(bold_line_time.month().next())
+                                        .expect("Month should be valid")
+                                    };
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every week
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60*24*7) + (60*60*24*7)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = light_line_time.checked_add(time::Duration::SECOND * 60*60*24*7)
+                                        .expect("Checked add should be valid");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //If we contain at least a whole week
+                    range if range >= (60*60*24*7) as f64 => { 
+                        match hint.weight() {
+                            // Bold lines will be every week
+                            KeyPointWeight::Bold => {
+                                let mut bold_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60*24*7) + (60*60*24*7)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = bold_line_time.checked_add(time::Duration::SECOND * 60*60*24*7)
+                                        .expect("Checked add should be valid");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every day
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60*24) + (60*60*24)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = light_line_time.checked_add(time::Duration::SECOND * 60*60*24)
+                                        .expect("Checked add should be valid");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //If we contain at least a whole day
+                    range if range >= (60*60*24) as f64 => { 
+                        match hint.weight() {
+                            // Bold lines will be every day
+                            KeyPointWeight::Bold => {
+                                let mut bold_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60*24) + (60*60*24)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = bold_line_time.checked_add(time::Duration::SECOND * 60*60*24)
+                                        .expect("Checked add should be valid");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every 6 hours
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60*6) + (60*60*6)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = light_line_time.checked_add(time::Duration::SECOND * 60*60*6)
+                                        .expect("Checked add should be valid");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //If we contain at least 6 hours
+                    range if range >= (60*60*6) as f64 => { 
+                        match hint.weight() {
+                            // Bold lines will be every 6 hours
+                            KeyPointWeight::Bold => {
+                                let mut bold_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60*6) + (60*60*6)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = bold_line_time.checked_add(time::Duration::SECOND * 60*60*6)
+                                        .expect("Checked add should be valid");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every hour
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60) + (60*60)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = light_line_time.checked_add(time::Duration::SECOND * 60*60)
+                                        .expect("Checked add should be valid");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //If we contain at least 1 hour
+                    range if range >= (60*60) as f64 => { 
+                        match hint.weight() {
+                            // Bold lines will be every hour
+                            KeyPointWeight::Bold => {
+                                let mut bold_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*60) + (60*60)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(bold_line_time.unix_timestamp() as f64);
+                                loop {
+                                    bold_line_time = bold_line_time.checked_add(time::Duration::SECOND * 60*60)
+                                        .expect("Checked add should be valid");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every 15 minutes
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*15) + (60*15)
+                                ).expect("Unix timestamp should be valid");
+                                key_points.push(light_line_time.unix_timestamp() as f64);
+                                loop {
+                                    light_line_time = light_line_time.checked_add(time::Duration::SECOND * 60*15)
+                                        .expect("Checked add should be valid");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    //Assume we contain at least 15 minutes, since there are no smaller subdivision pairs left
+                    range if range < (60*15) as f64 => { 
+                        match hint.weight() {
+                            // Bold lines will be every 15 minutes
+                            KeyPointWeight::Bold => {
+                                let mut bold_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*15) + (60*15)
+                                ).expect("Unix timestamp should be valid");
+                                if bold_line_time.unix_timestamp() <= end as i64 {
+                                    key_points.push(bold_line_time.unix_timestamp() as f64);
+                                }
+                                loop {
+                                    bold_line_time = bold_line_time.checked_add(time::Duration::SECOND * 60*15)
+                                        .expect("Checked add should be valid");
+                                    if bold_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(bold_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                            // Light lines will be every 5 minutes
+                            KeyPointWeight::Any => {
+                                let mut light_line_time = OffsetDateTime::from_unix_timestamp(
+                                    start_time.unix_timestamp() - start_time.unix_timestamp().rem_euclid(60*5) + (60*5)
+                                ).expect("Unix timestamp should be valid");
+                                if light_line_time.unix_timestamp() <= end as i64 {
+                                    key_points.push(light_line_time.unix_timestamp() as f64);
+                                }
+                                loop {
+                                    light_line_time = light_line_time.checked_add(time::Duration::SECOND * 60*5)
+                                        .expect("Checked add should be valid");
+                                    if light_line_time.unix_timestamp() >= end as i64 {
+                                        break;
+                                    } else {
+                                        key_points.push(light_line_time.unix_timestamp() as f64)
+                                    }
+                                }
+                            },
+                        }
+                    },
+                    _ => {// All points are covered, but rust doesn't know that, so this is here to satisfy rust's matching rules.
+                    },
+                }
+                //returned vector needs to contain elements within (or close to?) the range given.
+                key_points
+            },
+            _ => self.range.key_points(hint),
+        }
+    }
+
+    fn range(&self) -> std::ops::Range<Self::ValueType> {
+        self.range.range()
+    }
+}
+
+impl ValueFormatter<f64> for GraphDataRange {
+    fn format(_value: &f64) -> String {
+        RangedCoordf64::format(_value)
+    }
+
+    fn format_ext(&self, value: &f64) -> String {
+        Self::format(value)
+    }
+}
+
+//Draws the graph onto any plotters backend. The canvas in the frontend and the image exporters both use this, so a graph looks the same wherever it ends up.
+pub fn draw_graph<DB>(root: &DrawingArea<DB, Shift>, graph_state: &GraphStateRequest, line_series: &LineSeriesHolder, ranges: &GraphRanges, markpoints: &[(f64, f64)], theme: &ThemeData, 
+                      overlays: GraphOverlays) -> Result<(), Box<dyn std::error::Error>> 
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let caption_font = FontDesc::new(FontFamily::from("sans-serif"), 20.0, FontStyle::Normal);
+    let label_font = FontDesc::new(FontFamily::from("sans-serif"), 12.0, FontStyle::Normal);
+    
+    root.fill(&RGBColor::from(&theme.theme_graph_background))?;
+    
+    let x_axis_range = ranges.x_range.clone();
+    let y_axis_range = ranges.y_range.clone();
+    let secondary_y_axis_range = ranges.sec_y_range.clone();
+    
+    let graph_type = graph_state.get_graph_type();
+    //A histogram's x axis holds the values of the y axis channels.
+    let x_axis_data_type = match graph_type {
+        GraphType::Histogram(_) => graph_state.y_axis.0.requests.first().map(|(data_type, _data_option)| data_type.clone()).unwrap_or(AxisDataType::BatteryVoltage),
+        GraphType::Heatmap => AxisDataType::Time,
+        _ => graph_state.x_axis.requests.first().unwrap_or(&(AxisDataType::Time, AxisDataOption::Average)).0.clone(),
+    };
+    //A heatmap's y axis is the hour of day.
+    let y_axis_data_type = match graph_type {
+        GraphType::Heatmap => AxisDataType::PeriodicTime(Period::Day),
+        _ => graph_state.y_axis.0.requests.first().unwrap_or(&(AxisDataType::BatteryVoltage, AxisDataOption::Average)).0.clone(),
+    };
+
+    let mut chart = ChartBuilder::on(root)
+        .margin(CHART_MARGIN_SIZE)
+        .caption("temp caption", caption_font.clone().with_color(RGBColor::from(&theme.theme_text)))
+        .x_label_area_size(CHART_LABEL_SIZE)
+        .y_label_area_size(CHART_LABEL_SIZE)
+        .right_y_label_area_size(CHART_LABEL_SIZE)
+        .build_cartesian_2d(GraphDataRange {
+            range: RangedCoordf64::from(x_axis_range.clone()),
+            data_type: x_axis_data_type.clone(),
+        }, GraphDataRange{
+            range: RangedCoordf64::from(y_axis_range.clone()),
+            data_type: y_axis_data_type.clone(),
+        })?
+        .set_secondary_coord(x_axis_range.clone(), secondary_y_axis_range.clone());
+
+    let base_x_axis_formatter = match x_axis_data_type {
+        AxisDataType::Time => {
+            time_axis_label_formatter
+        },
+        AxisDataType::PeriodicTime(Period::Day) => {
+            time_of_day_axis_label_formatter
+        },
+        AxisDataType::PeriodicTime(Period::Week) => {
+            day_of_week_axis_label_formatter
+        },
+        AxisDataType::PeriodicTime(Period::Year) => {
+            day_of_year_axis_label_formatter
+        },
+        _ => {
+            other_axis_label_formatter
+        }
+    };
+    //Compared periods all have different dates, so the time axis counts from the start of the time frame instead.
+    let relative_start = match (&x_axis_data_type, graph_state.time_frame.comparisons.is_empty()) {
+        (AxisDataType::Time, false) => Some(graph_state.time_frame.start as f64),
+        _ => None,
+    };
+    let x_axis_formatter = |value: &f64| match relative_start {
+        Some(start) => relative_time_axis_label_formatter(&(value - start)),
+        None => base_x_axis_formatter(value),
+    };
+
+    //Night goes behind everything else, so it's drawn first.
+    let night_shading = match (graph_state.night_shading, overlays.solar_site) {
+        (NightShading::Off, _) | (_, None) => None,
+        (night_shading, Some(site)) => Some((night_shading, site)),
+    };
+    if let Some((night_shading, site)) = night_shading {
+        let visible_days = (x_axis_range.end - x_axis_range.start) / 86400f64;
+        if graph_type == GraphType::XAxisLine && x_axis_data_type == AxisDataType::Time && visible_days <= MAX_NIGHT_SHADING_DAYS {
+            let nights = site.get_night_periods(x_axis_range.start.floor() as i64..x_axis_range.end.ceil() as i64);
+            let night_style = RGBColor::from(&theme.theme_background_tertiary).mix(0.4).filled();
+            chart.draw_series(nights.iter().map(|night| {
+                Rectangle::new([(night.start as f64, y_axis_range.start), (night.end as f64, y_axis_range.end)], night_style)
+            }))?;
+            if night_shading == NightShading::ShadeAndMark {
+                //Nights are clipped to the visible range, so only edges inside it are real sunrises and sunsets.
+                let mark_style = RGBColor::from(&theme.theme_secondary).mix(0.6).stroke_width(1);
+                let edges = nights.iter().flat_map(|night| [night.start, night.end])
+                    .map(|edge| edge as f64)
+                    .filter(|edge| *edge > x_axis_range.start && *edge < x_axis_range.end);
+                chart.draw_series(edges.map(|edge| {
+                    PathElement::new(vec![(edge, y_axis_range.start), (edge, y_axis_range.end)], mark_style)
+                }))?;
+            }
+        }
+    }
+
+    //Anomalies are marked behind the data too, as a band over their duration and a line at their start so that single lines still show.
+    if graph_type == GraphType::XAxisLine && x_axis_data_type == AxisDataType::Time {
+        let visible_anomalies = overlays.anomalies.iter()
+            .filter(|anomaly| anomaly.end as f64 >= x_axis_range.start && anomaly.start as f64 <= x_axis_range.end)
+            .collect::<Vec<_>>();
+        let anomaly_color = RGBColor::from(&theme.theme_graph_anomaly);
+        chart.draw_series(visible_anomalies.iter().map(|anomaly| {
+            Rectangle::new([(anomaly.start as f64, y_axis_range.start), (anomaly.end as f64, y_axis_range.end)], anomaly_color.mix(0.15).filled())
+        }))?;
+        chart.draw_series(visible_anomalies.iter().map(|anomaly| {
+            PathElement::new(vec![(anomaly.start as f64, y_axis_range.start), (anomaly.start as f64, y_axis_range.end)], anomaly_color.mix(0.5).stroke_width(1))
+        }))?;
+    }
+
+    //Do all plotting based on graph type for primary and secondary axis.
+    match &graph_type {
+        GraphType::XAxisLine => {
+            //Draw the data series
+            line_series.series.iter().enumerate().for_each(|series| {
+                let name = series.1.name.clone();
+                //If drawn as is, the line series "line" for data outside the range of the graph is drawn such that the data is clamped to the graph bounds.
+                //This causes it to display an incorrect slope, and thus incorrect values.
+                //Here we will modify the data for display such that any data going from in bounds to out of bounds, or from out of bounds to in bounds
+                // hits the y (or x) axis at the right location by adding points on the line from one point to the next.
+                let data = series.1.data_points.windows(2).flat_map(|point| {
+                    let current_point = point[0];
+                    let next_point = point[1];
+                    let slope = (next_point.1 - current_point.1) / (next_point.0 - current_point.0);
+                    let mut valid_points = vec![current_point];
+
+                    //Since all x values are sorted in ascending x, we only care about points that are of higher x value than the current point
+                    //Furthermore, since we are only interested in lines of shorter length, and all lines share the same slope, any line with
+                    // an x value greater than the x value of the next point is longer, and thus invalid
+
+                    //Solve for the y_max intercept line
+                    let y_max_intercept_x = (y_axis_range.end - current_point.1) / slope + current_point.0;
+                    if y_max_intercept_x > current_point.0 && y_max_intercept_x < next_point.0 {
+                        //We don't care about the Ok case since there's no point in inserting identical points.
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&y_max_intercept_x)) {
+                            valid_points.insert(index, (y_max_intercept_x, y_axis_range.end));
+                        }
+                    } 
+
+                    //Solve for the y_min intercept line
+                    let y_min_intercept_x = (y_axis_range.start - current_point.1) / slope + current_point.0;
+                    if y_min_intercept_x > current_point.0 && y_min_intercept_x < next_point.0 {
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&y_min_intercept_x)) {
+                            valid_points.insert(index, (y_min_intercept_x, y_axis_range.start));
+                        }
+                    } 
+
+                    //Solve for the x_max intercept line
+                    if x_axis_range.end > current_point.0 && x_axis_range.end < next_point.0 {
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&x_axis_range.end)) {
+                            valid_points.insert(index, (x_axis_range.end, slope * (x_axis_range.end - current_point.0) + current_point.1));
+                        }
+                    }
+
+                    //Solve for the x_min intercept line
+                    if x_axis_range.start > current_point.0 && x_axis_range.start < next_point.0 {
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&x_axis_range.start)) {
+                            valid_points.insert(index, (x_axis_range.start, slope * (x_axis_range.start - current_point.0) + current_point.1));
+                        }
+                    }
+
+                    valid_points
+                }).collect::<Vec<_>>();
+                if let Ok(line_series) = chart.draw_series(LineSeries::new(data, Palette99::pick(series.0))) {
+                    //Configure labels and legend here
+                    line_series
+                        .label(name)
+                        .legend(move |(x,y)| {PathElement::new(vec![(x, y), (x + 20, y)], Palette99::pick(series.0))});
+                }
+            }); 
+
+            //Drawing secondary series
+            line_series.secondary_series.iter().enumerate().for_each(|series| {
+                let name = series.1.name.clone();
+                //If drawn as is, the line series "line" for data outside the range of the graph is drawn such that the data is clamped to the graph bounds.
+                //This causes it to display an incorrect slope, and thus incorrect values.
+                //Here we will modify the data for display such that any data going from in bounds to out of bounds, or from out of bounds to in bounds
+                // hits the y (or x) axis at the right location by adding points on the line from one point to the next.
+                let data = series.1.data_points.windows(2).flat_map(|point| {
+                    let current_point = point[0];
+                    let next_point = point[1];
+                    let slope = (next_point.1 - current_point.1) / (next_point.0 - current_point.0);
+                    let mut valid_points = vec![current_point];
+
+                    //Since all x values are sorted in ascending x, we only care about points that are of higher x value than the current point
+                    //Furthermore, since we are only interested in lines of shorter length, and all lines share the same slope, any line with
+                    // an x value greater than the x value of the next point is longer, and thus invalid
+
+                    //Solve for the y_max intercept line
+                    let y_max_intercept_x = (y_axis_range.end - current_point.1) / slope + current_point.0;
+                    if y_max_intercept_x > current_point.0 && y_max_intercept_x < next_point.0 {
+                        //We don't care about the Ok case since there's no point in inserting identical points.
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&y_max_intercept_x)) {
+                            valid_points.insert(index, (y_max_intercept_x, y_axis_range.end));
+                        }
+                    } 
+
+                    //Solve for the y_min intercept line
+                    let y_min_intercept_x = (y_axis_range.start - current_point.1) / slope + current_point.0;
+                    if y_min_intercept_x > current_point.0 && y_min_intercept_x < next_point.0 {
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&y_min_intercept_x)) {
+                            valid_points.insert(index, (y_min_intercept_x, y_axis_range.start));
+                        }
+                    } 
+
+                    //Solve for the x_max intercept line
+                    if x_axis_range.end > current_point.0 && x_axis_range.end < next_point.0 {
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&x_axis_range.end)) {
+                            valid_points.insert(index, (x_axis_range.end, slope * (x_axis_range.end - current_point.0) + current_point.1));
+                        }
+                    }
+
+                    //Solve for the x_min intercept line
+                    if x_axis_range.start > current_point.0 && x_axis_range.start < next_point.0 {
+                        if let Err(index) = valid_points.binary_search_by(|element| element.0.total_cmp(&x_axis_range.start)) {
+                            valid_points.insert(index, (x_axis_range.start, slope * (x_axis_range.start - current_point.0) + current_point.1));
+                        }
+                    }
+
+                    valid_points
+                }).collect::<Vec<_>>();
+                let primary_series = line_series.series.len();
+                if let Ok(line_series) = chart.draw_secondary_series(LineSeries::new(data, Palette99::pick(series.0 + primary_series))) {
+                    //Configure labels and legend here
+                    line_series
+                        .label(name)
+                        .legend(move |(x,y)| {PathElement::new(vec![(x, y), (x + 20, y)], Palette99::pick(series.0 + primary_series))});
+                }
+            });
+
+            //Drawing markpoints for primary and secondary series
+            line_series.series.iter().chain(line_series.secondary_series.iter()).enumerate().for_each(|series| {
+                let mut points = Vec::new();
+                markpoints.iter().for_each(|markpoint| {
+                    match series.1.data_points.binary_search_by(|e| {
+                        e.0.total_cmp(&markpoint.0)
+                    }) {
+                        Ok(index) => {
+                            let point = series.1.data_points[index];
+                            //Only do anything if the markpoint fits inside the limits of our data
+                            if x_axis_range.contains(&point.0) && y_axis_range.contains(&point.1) {
+                                points.push(point);
+                            }
+                        },
+                        Err(index) => {
+                            //Only do anything if the markpoint fits inside the limits of our data
+                            if index > 0 && index < series.1.data_points.len() {
+                                let final_point = series.1.data_points[index];
+                                let initial_point = series.1.data_points[index-1];
+                                let interpolated_point = (
+                                    markpoint.0,
+                                    (final_point.1 - initial_point.1)/(final_point.0 - initial_point.0)*(markpoint.0 - initial_point.0) + initial_point.1
+                                );
+                                if x_axis_range.contains(&interpolated_point.0) && y_axis_range.contains(&interpolated_point.1) {
+                                    points.push(interpolated_point);
+                                } 
+                            }
+                        },
+                    }
+                    //Draw a vertical line
+                    let _result = chart.draw_series(
+                        LineSeries::new([(markpoint.0, y_axis_range.start), (markpoint.0, y_axis_range.end)], &BLACK)
+                    );
+                });
+
+                //Draw the markpoint circles
+                if !points.is_empty() {
+                    let _result = chart.draw_series(PointSeries::of_element(points, 5, &Palette99::pick(series.0), &|coord, size, style| {
+                        EmptyElement::at(coord)
+                            + Circle::new((0,0), size, style)
+                            + Text::new(format!("({}, {:.2})", x_axis_formatter(&coord.0), coord.1), (0,15), ("sans-serif", 15).into_font().color(&RGBColor::from(&theme.theme_text)))
+                    }));
+                }
+            });
+        },
+        GraphType::XYScatter => {
+            // A bit of a misnomer, line_series is just a list of (x,y) pairs, and so is suitable for XY scatter plots too.
+            let size = 3;
+            line_series.series.iter().enumerate().for_each(|series| {
+                let name = series.1.name.clone();
+                if let Ok(point_series) = chart.draw_series(PointSeries::of_element(series.1.data_points.clone(), size, ShapeStyle::from(Palette99::pick(series.0)).filled(), &|coord, size, style| {
+                    EmptyElement::at(coord) + Rectangle::new([(-size,-size), (size,size)], style)
+                })) {
+                    point_series
+                        .label(name)
+                        .legend(move |(x,y)| {Rectangle::new([(x-size+10,y-size), (x+size+10,y+size)], ShapeStyle::from(Palette99::pick(series.0)).filled())});
+                }
+            });
+
+            //Draw secondary series
+            let primary_series = line_series.series.len();
+            line_series.secondary_series.iter().enumerate().for_each(|series| {
+                let name = series.1.name.clone();
+                if let Ok(point_series) = chart.draw_secondary_series(PointSeries::of_element(series.1.data_points.clone(), size, ShapeStyle::from(Palette99::pick(series.0 + primary_series)), &|coord, size, style| {
+                    EmptyElement::at(coord) + Rectangle::new([(-size,-size), (size,size)], style)
+                })) {
+                    point_series
+                        .label(name)
+                        .legend(move |(x,y)| {Rectangle::new([(x-size+10,y-size), (x+size+10,y+size)], ShapeStyle::from(Palette99::pick(series.0 + primary_series)))});
+                }
+            });
+            //The same fits the graph lists next to it, see graph_regression.
+            get_series_trend_fits(line_series, &graph_state.trend_lines, &x_axis_range, &y_axis_range, &secondary_y_axis_range).into_iter().for_each(|trend_fit| {
+                let colour = if trend_fit.secondary { trend_fit.index + primary_series } else { trend_fit.index };
+                let data = get_trend_line_points(&x_axis_range).map(|x| (x, trend_fit.fit.evaluate(x)));
+                let drawn = if trend_fit.secondary {
+                    chart.draw_secondary_series(LineSeries::new(data, Palette99::pick(colour)))
+                } else {
+                    chart.draw_series(LineSeries::new(data, Palette99::pick(colour)))
+                };
+                if let Ok(trend_series) = drawn {
+                    trend_series
+                        .label(trend_fit.get_label())
+                        .legend(move |(x,y)| {PathElement::new(vec![(x, y), (x + 20, y)], Palette99::pick(colour))});
+                }
+            });
+        },
+        GraphType::Histogram(_) => {
+            //Each bar is a pair of points, the start and end of its bin, see graph_histogram. Series overlap, so the bars are see through.
+            line_series.series.iter().enumerate().for_each(|series| {
+                let name = series.1.name.clone();
+                let style = ShapeStyle::from(Palette99::pick(series.0).mix(0.5)).filled();
+                let bars = series.1.data_points.chunks_exact(2).filter(|bin| bin[0].1 > 0f64).map(|bin| {
+                    Rectangle::new([(bin[0].0, 0f64), (bin[1].0, bin[1].1)], style)
+                });
+                if let Ok(bar_series) = chart.draw_series(bars) {
+                    bar_series
+                        .label(name)
+                        .legend(move |(x,y)| {Rectangle::new([(x,y-5), (x+20,y+5)], style)});
+                }
+            });
+        },
+        GraphType::Heatmap => {
+            //Points are hourly buckets, (start of the hour, value). Each one becomes a cell in its day's column.
+            if let Some(series) = line_series.series.first() {
+                let (min, max) = series.data_points.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), point| {
+                    (min.min(point.1), max.max(point.1))
+                });
+                let spread = max - min;
+                let cells = series.data_points.iter().map(|(time, value)| {
+                    let day = (time / 86400f64).floor() * 86400f64;
+                    let hour = time - day;
+                    let fraction = if spread > 0f64 { (value - min) / spread } else { 0.5 };
+                    let color = RGBColor::from(&theme.get_heatmap_color(fraction));
+                    Rectangle::new([(day, hour), (day + 86400f64, hour + 3600f64)], color.filled())
+                });
+                let name = format!("{} ({:.2} to {:.2})", series.name, min, max);
+                let high_color = RGBColor::from(&theme.get_heatmap_color(1f64));
+                if let Ok(cell_series) = chart.draw_series(cells) {
+                    cell_series
+                        .label(name)
+                        .legend(move |(x,y)| {Rectangle::new([(x,y-5), (x+20,y+5)], high_color.filled())});
+                }
+            }
+        },
+    }
+
+
+    
+    
+
+    let y_axis_formatter = match (&graph_type, graph_state.y_axis.0.requests.first()) {
+        (GraphType::Heatmap, _) => time_of_day_axis_label_formatter,
+        (_, Some((data_type, _data_option))) => {
+            match data_type {
+                AxisDataType::Time => {
+                    time_axis_label_formatter
+                },
+                _ => {
+                    other_axis_label_formatter
+                }
+            }
+        },
+        (_, None) => other_axis_label_formatter,
+    };
+    let secondary_y_axis_formatter = match graph_state.y_axis.1.requests.first() {
+        Some((data_type, _data_option)) => {
+            match data_type {
+                AxisDataType::Time => {
+                    time_axis_label_formatter
+                },
+                _ => {
+                    other_axis_label_formatter
+                }
+            }
+        },
+        None => other_axis_label_formatter,
+    };
+
+    let x_axis_description = match (&graph_type, graph_state.x_axis.requests.first()) {
+        (GraphType::Histogram(_), _) => x_axis_data_type.get_unit().get_name(),
+        (GraphType::Heatmap, _) => "Day",
+        (_, Some((data_type, _data_option))) => {
+            data_type.get_unit().get_name()
+        },
+        (_, None) => "",
+    };
+    let y_axis_description = match (&graph_type, graph_state.y_axis.0.requests.first()) {
+        (GraphType::Histogram(options), _) => options.measure.get_name(),
+        (GraphType::Heatmap, _) => "Hour of Day",
+        (_, Some((data_type, _data_option))) => {
+            data_type.get_unit().get_name()
+        },
+        (_, None) => "",
+    };
+    let secondary_y_axis_description = match graph_state.y_axis.1.requests.first() {
+        Some((data_type, _data_option)) => {
+            data_type.get_unit().get_name()
+        },
+        None => "",
+    };
+
+
+    chart.configure_mesh()
+        .light_line_style(RGBColor::from(&theme.theme_graph_mesh_light))
+        .bold_line_style(RGBColor::from(&theme.theme_graph_mesh_dark))
+        .axis_style(RGBColor::from(&theme.theme_graph_border))
+        .x_desc(x_axis_description)
+        .x_label_style(label_font.clone())
+        .x_labels(4)
+        .x_label_formatter(&x_axis_formatter)
+        .y_desc(y_axis_description)
+        .y_label_style(label_font.clone())
+        .y_labels(4)
+        .y_label_formatter(&y_axis_formatter)
+        .label_style(&RGBColor::from(&theme.theme_text))
+        .draw()?;
+
+    chart.configure_series_labels()
+        .label_font(label_font.clone().color(&RGBColor::from(&theme.theme_text)))
+        .background_style(RGBColor::from(&theme.theme_graph_background))
+        .border_style(RGBColor::from(&theme.theme_graph_border))
+        .position(SeriesLabelPosition::UpperRight)
+        .draw()?;
+
+    //Draw and configure secondary axis, if it is present. Histograms and heatmaps only show the primary y axis channels.
+    match graph_state.y_axis.1.requests.first() {
+        Some(_) if !matches!(graph_type, GraphType::Histogram(_) | GraphType::Heatmap) => {
+            chart
+                .configure_secondary_axes()
+                .axis_desc_style(&RGBColor::from(&theme.theme_text))
+                .label_style(&RGBColor::from(&theme.theme_text))
+                .axis_style(RGBColor::from(&theme.theme_graph_border))
+                .y_desc(secondary_y_axis_description)
+                .y_label_formatter(&secondary_y_axis_formatter)
+                .y_labels(4)
+                .draw()?;
+        },
+        _ => {},
+    };
+    
+    root.present()?;
+    Ok(())
+}
+
+//Evenly spaced x values across the visible range, enough for curved fits to look smooth.
+fn get_trend_line_points(x_range: &Range<f64>) -> impl Iterator<Item = f64> + '_ {
+    const TREND_LINE_POINTS: usize = 100;
+    (0..=TREND_LINE_POINTS).map(move |index| x_range.start + (x_range.end - x_range.start) * index as f64 / TREND_LINE_POINTS as f64)
+}
@@ -0,0 +1,109 @@
+use std::ops::Range;
+
+use super::{graph_axis::LineSeriesData, graph_fold::Period};
+
+
+pub fn time_axis_label_formatter(unix_time: &f64) -> String {
+    time::OffsetDateTime::from_unix_timestamp(*unix_time as i64)
+        .expect("All stored timestamps are valid")
+        .format(time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]"))
+        .expect("Given format is verified during compilation")
+}
+
+pub fn time_of_day_axis_label_formatter(folded_time: &f64) -> String {
+    Period::Day.format_folded(*folded_time as i64)
+}
+
+pub fn day_of_week_axis_label_formatter(folded_time: &f64) -> String {
+    Period::Week.format_folded(*folded_time as i64)
+}
+
+pub fn day_of_year_axis_label_formatter(folded_time: &f64) -> String {
+    Period::Year.format_folded(*folded_time as i64)
+}
+
+//Time since the start of the time frame, for comparisons where the dates differ between series.
+pub fn relative_time_axis_label_formatter(offset: &f64) -> String {
+    let offset = *offset as i64;
+    format!("Day {} {:02}:{:02}", offset.div_euclid(86400) + 1, offset.rem_euclid(86400) / 3600, offset.rem_euclid(3600) / 60)
+}
+
+pub fn other_axis_label_formatter(data: &f64) -> String {
+    format!("{:.0}", data)
+}
+
+//The range of x values covered by all of the given series.
+pub fn get_x_range(series: &[LineSeriesData]) -> Range<f64> {
+    let mut x_range = series.iter().map(|series| {
+        let max_point = series.data_points.iter().reduce(|accumulator, e| {
+            if accumulator.0 < e.0 {
+                e
+            } else {
+                accumulator
+            }
+        }).unwrap_or(&(0f64,0f64));
+        let min_point = series.data_points.iter().reduce(|accumulator, e| {
+            if accumulator.0 > e.0 {
+                e
+            } else {
+                accumulator
+            }
+        }).unwrap_or(&(0f64,0f64));
+
+        min_point.0 .. max_point.0
+    }).reduce(|accumulator, series_range| {
+        let mut range = accumulator.clone();
+        if range.start > series_range.start {
+            range.start = series_range.start;
+        }
+        if range.end < series_range.end {
+            range.end = series_range.end;
+        }
+        range
+    }).unwrap_or(0f64..0f64);
+    x_range.end += 0.000001f64;
+    x_range
+}
+
+//A heatmap always shows the whole day, in seconds since midnight.
+pub const HEATMAP_Y_RANGE: Range<f64> = 0f64..86400f64;
+
+//Whole days covering every point of the given series, so the first and last day's cells aren't cut off.
+pub fn get_heatmap_x_range(series: &[LineSeriesData]) -> Range<f64> {
+    let x_range = get_x_range(series);
+    let day = 86400f64;
+    (x_range.start / day).floor() * day .. (x_range.end / day).floor() * day + day
+}
+
+//The range of y values covered by all of the given series.
+pub fn get_y_range(series: &[LineSeriesData]) -> Range<f64> {
+    let mut y_range = series.iter().map(|series| {
+        let max_point = series.data_points.iter().reduce(|accumulator, e| {
+            if accumulator.1 < e.1 {
+                e
+            } else {
+                accumulator
+            }
+        }).unwrap_or(&(0f64,0f64));
+        let min_point = series.data_points.iter().reduce(|accumulator, e| {
+            if accumulator.1 > e.1 {
+                e
+            } else {
+                accumulator
+            }
+        }).unwrap_or(&(0f64,0f64));
+
+        min_point.1 .. max_point.1
+    }).reduce(|accumulator, series_range| {
+        let mut range = accumulator.clone();
+        if range.start > series_range.start {
+            range.start = series_range.start;
+        }
+        if range.end < series_range.end {
+            range.end = series_range.end;
+        }
+        range
+    }).unwrap_or(0f64..0f64);
+    y_range.end += 0.000001f64;
+    y_range
+}
@@ -0,0 +1,237 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use plotters::prelude::*;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use thiserror::Error;
+
+use crate::solar_data::{clear_sky::SolarSite, anomalies::Anomaly};
+use super::{graph_axis::LineSeriesHolder, graph_draw::{draw_graph, GraphRanges, GraphOverlays}, graph_state_request::GraphStateRequest, graph_theme::ThemeData, graph_transport::compact};
+
+//Four times 3840x2160. A PNG is drawn into a 3 byte per pixel buffer first, so a bad request could otherwise ask for more
+// memory than there is.
+pub const MAX_EXPORT_PIXELS: u64 = 7680 * 4320;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum ExportFormat {
+    Svg,
+    Png,
+}
+
+impl ExportFormat {
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+        }
+    }
+
+    pub fn get_mime_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "image/svg+xml",
+            ExportFormat::Png => "image/png",
+        }
+    }
+}
+
+//Everything needed to redraw a graph exactly as it is currently displayed, at a chosen size.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphExportRequest {
+    pub file_name: String,
+    pub format: ExportFormat,
+    pub width: u32,
+    pub height: u32,
+    pub graph_state: GraphStateRequest,
+    #[serde(with = "compact")]
+    pub line_series: LineSeriesHolder,
+    pub ranges: GraphRanges,
+    pub markpoints: Vec<(f64, f64)>,
+    pub theme: ThemeData,
+    //The site the graph was shown with, so the export shades the same nights.
+    #[serde(default)]
+    pub solar_site: Option<SolarSite>,
+    //The anomalies marked on the graph when it was exported.
+    #[serde(default)]
+    pub anomalies: Vec<Anomaly>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GraphExportReturnValue {
+    pub file_name: String,
+    pub mime_type: String,
+    //As a JSON array of numbers a PNG would be several times its own size, so the bytes are sent as base64.
+    #[serde(with = "base64_bytes")]
+    pub data: Vec<u8>,
+}
+
+mod base64_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        STANDARD.decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GraphExportError {
+    #[error("Invalid export size, expected non-zero width and height and at most {MAX_EXPORT_PIXELS} pixels, found {0}x{1}.")]
+    InvalidSize(u32, u32),
+    #[error("Failed to draw graph: {0}")]
+    DrawError(String),
+    #[error("Failed to encode image: {0}")]
+    EncodingError(String),
+}
+
+impl From<png::EncodingError> for GraphExportError {
+    fn from(value: png::EncodingError) -> Self {
+        GraphExportError::EncodingError(value.to_string())
+    }
+}
+
+pub fn export_graph(request: &GraphExportRequest) -> Result<GraphExportReturnValue, GraphExportError> {
+    let data = match request.format {
+        ExportFormat::Svg => export_svg(request)?.into_bytes(),
+        ExportFormat::Png => export_png(request)?,
+    };
+    Ok(GraphExportReturnValue {
+        file_name: format!("{}.{}", request.file_name, request.format.get_extension()),
+        mime_type: request.format.get_mime_type().to_owned(),
+        data,
+    })
+}
+
+pub fn export_svg(request: &GraphExportRequest) -> Result<String, GraphExportError> {
+    check_size(request)?;
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, (request.width, request.height)).into_drawing_area();
+        draw_graph(&root, &request.graph_state, &request.line_series, &request.ranges, &request.markpoints, &request.theme, GraphOverlays { solar_site: request.solar_site.as_ref(), anomalies: &request.anomalies })
+            .map_err(|e| GraphExportError::DrawError(e.to_string()))?;
+    }
+    Ok(svg)
+}
+
+pub fn export_png(request: &GraphExportRequest) -> Result<Vec<u8>, GraphExportError> {
+    check_size(request)?;
+    //The bitmap backend draws into a raw RGB buffer, which then needs to be encoded.
+    let mut buffer = vec![0u8; request.width as usize * request.height as usize * 3];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (request.width, request.height)).into_drawing_area();
+        draw_graph(&root, &request.graph_state, &request.line_series, &request.ranges, &request.markpoints, &request.theme, GraphOverlays { solar_site: request.solar_site.as_ref(), anomalies: &request.anomalies })
+            .map_err(|e| GraphExportError::DrawError(e.to_string()))?;
+    }
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, request.width, request.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&buffer)?;
+    }
+    Ok(png_data)
+}
+
+fn check_size(request: &GraphExportRequest) -> Result<(), GraphExportError> {
+    if request.width == 0 || request.height == 0 || request.width as u64 * request.height as u64 > MAX_EXPORT_PIXELS {
+        return Err(GraphExportError::InvalidSize(request.width, request.height))
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::{graph_axis::{AxisDataOption, AxisDataType, LineSeriesAxisData, LineSeriesData}, graph_theme::Color}, solar_data::anomalies::AnomalyKind};
+
+    fn get_request(format: ExportFormat, width: u32, height: u32) -> GraphExportRequest {
+        let graph_state = GraphStateRequest::default_with_name("export".to_owned());
+        let data_points = (0..48).map(|hour| (graph_state.time_frame.start as f64 + hour as f64 * 3600.0, 13.0 + (hour as f64 / 4.0).sin())).collect::<Vec<_>>();
+        let line_series = LineSeriesHolder {
+            series: vec![LineSeriesData {
+                name: "Battery Voltage".to_owned(),
+                timestamps: data_points.iter().map(|(x, _)| *x as i64).collect(),
+                data_points,
+                x_axis: LineSeriesAxisData { data_type: AxisDataType::Time, data_option: AxisDataOption::Average },
+                y_axis: LineSeriesAxisData { data_type: AxisDataType::BatteryVoltage, data_option: AxisDataOption::Average },
+            }],
+            secondary_series: Vec::new(),
+        };
+        let ranges = GraphRanges {
+            x_range: graph_state.time_frame.start as f64..graph_state.time_frame.start as f64 + 48.0 * 3600.0,
+            y_range: 11.0..15.0,
+            sec_y_range: 0.0..1.0,
+        };
+        let color = |shade: u8| Color(shade, shade, shade);
+        GraphExportRequest {
+            file_name: "export".to_owned(),
+            format,
+            width,
+            height,
+            graph_state,
+            line_series,
+            ranges,
+            markpoints: Vec::new(),
+            theme: ThemeData {
+                theme_primary: Color(33, 150, 243),
+                theme_secondary: Color(233, 30, 99),
+                theme_background_primary: color(255),
+                theme_background_secondary: color(240),
+                theme_background_tertiary: color(220),
+                theme_text: color(0),
+                theme_graph_background: color(255),
+                theme_graph_mesh_light: color(230),
+                theme_graph_mesh_dark: color(200),
+                theme_graph_border: color(100),
+                theme_graph_anomaly: Color(211, 47, 47),
+            },
+            solar_site: None,
+            anomalies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn headless_exports() {
+        let svg = export_svg(&get_request(ExportFormat::Svg, 640, 480)).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Battery Voltage"));
+        let png = export_png(&get_request(ExportFormat::Png, 640, 480)).unwrap();
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']));
+        let exported = export_graph(&get_request(ExportFormat::Png, 320, 240)).unwrap();
+        assert_eq!((exported.file_name.as_str(), exported.mime_type.as_str()), ("export.png", "image/png"));
+    }
+
+    #[test]
+    fn exported_bytes_are_base64() {
+        let exported = export_graph(&get_request(ExportFormat::Png, 320, 240)).unwrap();
+        let json = serde_json::to_string(&exported).unwrap();
+        assert!(json.contains(&format!("\"data\":\"{}\"", STANDARD.encode(&exported.data))));
+        let decoded = serde_json::from_str::<GraphExportReturnValue>(&json).unwrap();
+        assert_eq!(decoded.data, exported.data);
+    }
+
+    #[test]
+    fn anomalies_use_the_theme() {
+        let mut request = get_request(ExportFormat::Svg, 640, 480);
+        let anomaly_color = "#D32F2F";
+        assert!(!export_svg(&request).unwrap().to_uppercase().contains(anomaly_color));
+        let start = request.graph_state.time_frame.start;
+        request.anomalies.push(Anomaly { kind: AnomalyKind::LoadAboveBaseline, start: start + 6 * 3600, end: start + 9 * 3600, value: 900.0, expected: 300.0, score: 5.0 });
+        assert!(export_svg(&request).unwrap().to_uppercase().contains(anomaly_color));
+    }
+
+    #[test]
+    fn zero_size_is_an_error() {
+        assert!(matches!(export_svg(&get_request(ExportFormat::Svg, 0, 480)), Err(GraphExportError::InvalidSize(0, 480))));
+        assert!(matches!(export_png(&get_request(ExportFormat::Png, 640, 0)), Err(GraphExportError::InvalidSize(640, 0))));
+    }
+
+    #[test]
+    fn oversized_is_an_error() {
+        assert!(matches!(export_png(&get_request(ExportFormat::Png, u32::MAX, u32::MAX)), Err(GraphExportError::InvalidSize(u32::MAX, u32::MAX))));
+        assert!(matches!(export_png(&get_request(ExportFormat::Png, 7681, 4320)), Err(GraphExportError::InvalidSize(7681, 4320))));
+        assert!(matches!(export_svg(&get_request(ExportFormat::Svg, 100_000, 1000)), Err(GraphExportError::InvalidSize(100_000, 1000))));
+    }
+}
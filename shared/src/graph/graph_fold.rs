@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use time::{Date, OffsetDateTime, format_description::FormatItem, macros::format_description};
+
+use super::graph_state_request::Resolution;
+
+
+//1970-01-05 was the first Monday after the epoch, so folded weeks start there.
+const FIRST_MONDAY: i64 = 4 * 86400;
+const WEEK: i64 = 7 * 86400;
+
+//The cycle that periodic time folds the data onto.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Period {
+    Day,
+    Week,
+    Year,
+}
+
+//How the folded periods are shown. Overlay draws every period as its own series, envelope reduces them to the min, mean and max at each point in the period.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Debug)]
+pub enum PeriodicDisplay {
+    #[default]
+    Overlay,
+    Envelope,
+}
+
+//All of the points that fell in one period. Points are (position in period, value), ascending by position.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FoldedPeriod {
+    pub start: i64,
+    pub timestamps: Vec<i64>,
+    pub data_points: Vec<(f64, f64)>,
+}
+
+//The min, mean and max of every period at each position in the period.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Envelope {
+    pub minimum: Vec<(f64, f64)>,
+    pub mean: Vec<(f64, f64)>,
+    pub maximum: Vec<(f64, f64)>,
+}
+
+impl Period {
+    //The automatic resolution is picked from the length of the whole time frame, which would leave only a few points per period.
+    //Folded data takes its resolution from the length of the period instead.
+    pub fn get_default_resolution(&self) -> Resolution {
+        match self {
+            Period::Day => Resolution::FifteenMinute,
+            Period::Week => Resolution::OneHour,
+            Period::Year => Resolution::OneDay,
+        }
+    }
+
+    pub fn get_start(&self, timestamp: i64) -> Option<i64> {
+        match self {
+            Period::Day => Some(timestamp - timestamp.rem_euclid(86400)),
+            Period::Week => Some(timestamp - (timestamp - FIRST_MONDAY).rem_euclid(WEEK)),
+            Period::Year => {
+                let year = OffsetDateTime::from_unix_timestamp(timestamp).ok()?.year();
+                Some(Date::from_calendar_date(year, time::Month::January, 1).ok()?.midnight().assume_utc().unix_timestamp())
+            },
+        }
+    }
+
+    //Where the timestamp falls in its period. The result is itself a timestamp in the first period of 1970, so the time axis divisions and
+    // formatting line up with the period without any special casing. 1970 isn't a leap year, so in leap years every day after February 28th
+    // is labelled one day late.
+    pub fn fold(&self, timestamp: i64) -> Option<i64> {
+        let offset = timestamp - self.get_start(timestamp)?;
+        match self {
+            Period::Day | Period::Year => Some(offset),
+            Period::Week => Some(FIRST_MONDAY + offset),
+        }
+    }
+
+    //The label for one period in the legend.
+    pub fn get_period_name(&self, start: i64) -> String {
+        let format: &[FormatItem] = match self {
+            Period::Day => format_description!("[year]-[month]-[day]"),
+            Period::Week => format_description!("Week of [year]-[month]-[day]"),
+            Period::Year => format_description!("[year]"),
+        };
+        OffsetDateTime::from_unix_timestamp(start).ok()
+            .and_then(|date_time| date_time.format(format).ok())
+            .unwrap_or_else(|| start.to_string())
+    }
+
+    //Formats a folded value for the axis labels.
+    pub fn format_folded(&self, folded: i64) -> String {
+        let format: &[FormatItem] = match self {
+            Period::Day => format_description!("[hour]:[minute]"),
+            Period::Week => format_description!("[weekday repr:short] [hour]:[minute]"),
+            Period::Year => format_description!("[month repr:short] [day]"),
+        };
+        OffsetDateTime::from_unix_timestamp(folded).ok()
+            .and_then(|date_time| date_time.format(format).ok())
+            .unwrap_or_default()
+    }
+}
+
+//Splits time stamped values into periods. Input must be ascending by timestamp, which bucketed data always is.
+pub fn fold_periods(timestamps: &[i64], values: &[f64], period: Period) -> Vec<FoldedPeriod> {
+    let mut periods: Vec<FoldedPeriod> = Vec::new();
+    timestamps.iter().zip(values.iter()).for_each(|(timestamp, value)| {
+        let (start, folded) = match (period.get_start(*timestamp), period.fold(*timestamp)) {
+            (Some(start), Some(folded)) => (start, folded),
+            _ => return,
+        };
+        if periods.last().map(|folded_period| folded_period.start) != Some(start) {
+            periods.push(FoldedPeriod { start, timestamps: Vec::new(), data_points: Vec::new() });
+        }
+        let folded_period = periods.last_mut().expect("A period was just pushed");
+        folded_period.timestamps.push(*timestamp);
+        folded_period.data_points.push((folded as f64, *value));
+    });
+    periods
+}
+
+pub fn get_envelope(periods: &[FoldedPeriod]) -> Envelope {
+    //Keyed on the position as an integer so that floating point positions from different periods group together.
+    let mut positions: BTreeMap<i64, (f64, f64, f64, usize)> = BTreeMap::new();
+    periods.iter().flat_map(|folded_period| folded_period.data_points.iter()).for_each(|(position, value)| {
+        positions.entry(*position as i64)
+            .and_modify(|(min, max, sum, count)| {
+                *min = min.min(*value);
+                *max = max.max(*value);
+                *sum += value;
+                *count += 1;
+            })
+            .or_insert((*value, *value, *value, 1));
+    });
+    let mut minimum = Vec::with_capacity(positions.len());
+    let mut mean = Vec::with_capacity(positions.len());
+    let mut maximum = Vec::with_capacity(positions.len());
+    positions.into_iter().for_each(|(position, (min, max, sum, count))| {
+        let position = position as f64;
+        minimum.push((position, min));
+        mean.push((position, sum / count as f64));
+        maximum.push((position, max));
+    });
+    Envelope { minimum, mean, maximum }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn day_folding() {
+        let first = datetime!(2023-06-07 6:00).assume_utc().unix_timestamp();
+        let timestamps = [first, first + 12 * 3600, first + 86400];
+        let periods = fold_periods(&timestamps, &[1.0, 2.0, 3.0], Period::Day);
+        assert_eq!(periods.len(), 2);
+        assert_eq!(periods[0].start, first - 6 * 3600);
+        assert_eq!(periods[0].data_points, vec![(6.0 * 3600.0, 1.0), (18.0 * 3600.0, 2.0)]);
+        assert_eq!(periods[1].data_points, vec![(6.0 * 3600.0, 3.0)]);
+        //Timestamps keep the real time of each point.
+        assert_eq!(periods[1].timestamps, vec![first + 86400]);
+        assert_eq!(Period::Day.format_folded(periods[0].data_points[1].0 as i64), "18:00");
+    }
+
+    #[test]
+    fn week_folding_starts_on_monday() {
+        let wednesday = datetime!(2023-06-07 12:00).assume_utc().unix_timestamp();
+        let monday = datetime!(2023-06-05 0:00).assume_utc().unix_timestamp();
+        assert_eq!(Period::Week.get_start(wednesday), Some(monday));
+        assert_eq!(Period::Week.fold(wednesday), Some(FIRST_MONDAY + 2 * 86400 + 12 * 3600));
+        assert_eq!(Period::Week.format_folded(Period::Week.fold(wednesday).unwrap()), "Wed 12:00");
+        //Sunday night is still the same week, midnight Monday starts the next one at the anchor.
+        let sunday = datetime!(2023-06-11 23:00).assume_utc().unix_timestamp();
+        assert_eq!(Period::Week.get_start(sunday), Some(monday));
+        assert_eq!(Period::Week.fold(monday + WEEK), Some(FIRST_MONDAY));
+        assert_eq!(fold_periods(&[wednesday, sunday, monday + WEEK], &[1.0, 2.0, 3.0], Period::Week).len(), 2);
+        assert_eq!(Period::Week.get_period_name(monday), "Week of 2023-06-05");
+    }
+
+    #[test]
+    fn year_folding_around_leap_days() {
+        let leap_day = datetime!(2024-02-29 12:00).assume_utc().unix_timestamp();
+        let start = datetime!(2024-01-01 0:00).assume_utc().unix_timestamp();
+        assert_eq!(Period::Year.get_start(leap_day), Some(start));
+        //1970 has no February 29th, so the leap day lands on March 1st and everything after it a day late.
+        assert_eq!(Period::Year.fold(leap_day), Some(datetime!(1970-03-01 12:00).assume_utc().unix_timestamp()));
+        assert_eq!(Period::Year.format_folded(Period::Year.fold(leap_day).unwrap()), "Mar 01");
+        let march_first = datetime!(2024-03-01 0:00).assume_utc().unix_timestamp();
+        assert_eq!(Period::Year.format_folded(Period::Year.fold(march_first).unwrap()), "Mar 02");
+        //Non-leap years line up with 1970 exactly.
+        let march_first = datetime!(2023-03-01 0:00).assume_utc().unix_timestamp();
+        assert_eq!(Period::Year.format_folded(Period::Year.fold(march_first).unwrap()), "Mar 01");
+        let periods = fold_periods(&[march_first, leap_day], &[1.0, 2.0], Period::Year);
+        assert_eq!(periods.iter().map(|period| Period::Year.get_period_name(period.start)).collect::<Vec<_>>(), vec!["2023", "2024"]);
+    }
+
+    #[test]
+    fn envelope_min_mean_max() {
+        let periods = vec![
+            FoldedPeriod { start: 0, timestamps: vec![0, 3600], data_points: vec![(0.0, 1.0), (3600.0, 4.0)] },
+            FoldedPeriod { start: 86400, timestamps: vec![86400, 90000], data_points: vec![(0.0, 3.0), (3600.0, 2.0)] },
+            //Missing the first position, so it only counts towards the second.
+            FoldedPeriod { start: 172800, timestamps: vec![176400, 180000], data_points: vec![(3600.0, 9.0), (7200.0, 5.0)] },
+        ];
+        let envelope = get_envelope(&periods);
+        assert_eq!(envelope.minimum, vec![(0.0, 1.0), (3600.0, 2.0), (7200.0, 5.0)]);
+        assert_eq!(envelope.mean, vec![(0.0, 2.0), (3600.0, 5.0), (7200.0, 5.0)]);
+        assert_eq!(envelope.maximum, vec![(0.0, 3.0), (3600.0, 9.0), (7200.0, 5.0)]);
+        assert_eq!(get_envelope(&[]), Envelope { minimum: Vec::new(), mean: Vec::new(), maximum: Vec::new() });
+    }
+}
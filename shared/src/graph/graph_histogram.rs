@@ -0,0 +1,139 @@
+use super::graph_type::{HistogramBins, HistogramMeasure};
+
+
+//Fixed width bins over a wide range could otherwise ask for millions of bars.
+pub const MAX_BIN_COUNT: usize = 1000;
+
+//Equally sized bins, the first starting at start.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HistogramEdges {
+    pub start: f64,
+    pub width: f64,
+    pub count: usize,
+}
+
+impl HistogramEdges {
+    //Bins that cover all of the values, or None if there are no values. Every series of a histogram shares the same bins, so they can be compared.
+    pub fn from_values<T: IntoIterator<Item = f64>>(values: T, bins: &HistogramBins) -> Option<Self> {
+        let (min, max) = values.into_iter()
+            .filter(|value| value.is_finite())
+            .fold(None, |range: Option<(f64, f64)>, value| {
+                Some(range.map_or((value, value), |(min, max)| (min.min(value), max.max(value))))
+            })?;
+
+        if let HistogramBins::Width(width) = bins {
+            if *width > 0f64 && width.is_finite() {
+                let start = (min / width).floor() * width;
+                let count = ((max - start) / width).floor() as usize + 1;
+                if count <= MAX_BIN_COUNT {
+                    return Some(HistogramEdges { start, width: *width, count })
+                }
+            }
+        }
+        let count = match bins {
+            HistogramBins::Count(count) => (*count as usize).clamp(1, MAX_BIN_COUNT),
+            HistogramBins::Width(_) => MAX_BIN_COUNT,
+        };
+        if max == min {
+            //Every value is the same, so there is nothing to spread the bins over.
+            return Some(HistogramEdges { start: min - 0.5, width: 1f64, count: 1 })
+        }
+        Some(HistogramEdges { start: min, width: (max - min) / count as f64, count })
+    }
+
+    fn get_bin(&self, value: f64) -> Option<usize> {
+        let bin = ((value - self.start) / self.width).floor();
+        if bin < 0f64 || !bin.is_finite() {
+            return None
+        }
+        match bin as usize {
+            bin if bin < self.count => Some(bin),
+            //The edges are built from the same values, so only the maximum can land here, on the far edge of the last bin.
+            bin if bin == self.count => Some(bin - 1),
+            _ => None,
+        }
+    }
+}
+
+//Counts the time stamped values into the bins. The time a value lasts is the time until the next value, up to max_duration.
+//The last value lasts as long as the one before it.
+//Each bar comes out as two points, the start and end of the bin at the bar's height, so the bin widths travel with the data.
+pub fn get_histogram(points: &[(i64, f64)], edges: &HistogramEdges, measure: HistogramMeasure, max_duration: i64) -> Vec<(f64, f64)> {
+    let mut heights = vec![0f64; edges.count];
+    let mut previous_duration = 0i64;
+    points.iter().enumerate().for_each(|(index, (timestamp, value))| {
+        let bin = match edges.get_bin(*value) {
+            Some(bin) => bin,
+            None => return,
+        };
+        match measure {
+            HistogramMeasure::Count => heights[bin] += 1f64,
+            HistogramMeasure::Hours => {
+                let duration = match points.get(index + 1) {
+                    Some((next_timestamp, _)) => (next_timestamp - timestamp).clamp(0, max_duration),
+                    None => previous_duration,
+                };
+                previous_duration = duration;
+                heights[bin] += duration as f64 / 3600f64;
+            },
+        }
+    });
+    heights.into_iter().enumerate().flat_map(|(bin, height)| {
+        let bin_start = edges.start + bin as f64 * edges.width;
+        [(bin_start, height), (bin_start + edges.width, height)]
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_heights(histogram: &[(f64, f64)]) -> Vec<f64> {
+        histogram.iter().step_by(2).map(|(_, height)| *height).collect()
+    }
+
+    #[test]
+    fn bin_count_is_clamped() {
+        let values = [0.0, 10.0];
+        assert_eq!(HistogramEdges::from_values(values, &HistogramBins::Count(5000)).unwrap().count, MAX_BIN_COUNT);
+        assert_eq!(HistogramEdges::from_values(values, &HistogramBins::Count(0)).unwrap().count, 1);
+        //Too narrow for the range, so it falls back to the most bins allowed spread over the values.
+        let edges = HistogramEdges::from_values(values, &HistogramBins::Width(0.001)).unwrap();
+        assert_eq!(edges, HistogramEdges { start: 0.0, width: 10.0 / MAX_BIN_COUNT as f64, count: MAX_BIN_COUNT });
+        let edges = HistogramEdges::from_values([0.25, 9.5], &HistogramBins::Width(2.0)).unwrap();
+        assert_eq!(edges, HistogramEdges { start: 0.0, width: 2.0, count: 5 });
+    }
+
+    #[test]
+    fn maximum_lands_in_the_last_bin() {
+        let values = [0.0, 1.0, 2.0, 4.0];
+        let edges = HistogramEdges::from_values(values, &HistogramBins::Count(4)).unwrap();
+        assert_eq!(edges, HistogramEdges { start: 0.0, width: 1.0, count: 4 });
+        let points = values.iter().enumerate().map(|(index, value)| (index as i64 * 60, *value)).collect::<Vec<_>>();
+        let histogram = get_histogram(&points, &edges, HistogramMeasure::Count, 900);
+        assert_eq!(get_heights(&histogram), vec![1.0, 1.0, 1.0, 1.0]);
+        //Each bar is its two edges.
+        assert_eq!(&histogram[6..], &[(3.0, 1.0), (4.0, 1.0)]);
+        //Values outside the edges aren't counted at all.
+        assert_eq!(get_heights(&get_histogram(&[(0, -1.0), (60, 5.5), (120, f64::NAN)], &edges, HistogramMeasure::Count, 900)), vec![0.0; 4]);
+    }
+
+    #[test]
+    fn equal_and_empty_values() {
+        let edges = HistogramEdges::from_values([2.0, 2.0, 2.0], &HistogramBins::Count(10)).unwrap();
+        assert_eq!(edges, HistogramEdges { start: 1.5, width: 1.0, count: 1 });
+        assert_eq!(get_histogram(&[(0, 2.0), (60, 2.0)], &edges, HistogramMeasure::Count, 900), vec![(1.5, 2.0), (2.5, 2.0)]);
+        assert_eq!(HistogramEdges::from_values(Vec::new(), &HistogramBins::Count(10)), None);
+        assert_eq!(HistogramEdges::from_values([f64::NAN, f64::INFINITY], &HistogramBins::Count(10)), None);
+        assert_eq!(get_histogram(&[], &edges, HistogramMeasure::Hours, 900), vec![(1.5, 0.0), (2.5, 0.0)]);
+    }
+
+    #[test]
+    fn hours_in_each_bin() {
+        let edges = HistogramEdges { start: 0.0, width: 1.0, count: 2 };
+        //Half an hour in the first bin, then a gap capped at the max duration, then the last value lasts as long as the one before it.
+        let points = [(0, 0.5), (1800, 1.5), (1800 + 7200, 0.5)];
+        assert_eq!(get_heights(&get_histogram(&points, &edges, HistogramMeasure::Hours, 3600)), vec![1.5, 1.0]);
+    }
+}
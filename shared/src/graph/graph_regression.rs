@@ -0,0 +1,336 @@
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use super::graph_axis::LineSeriesHolder;
+
+
+pub const MAX_POLYNOMIAL_DEGREE: u8 = 6;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum TrendLine {
+    Linear,
+    //Degree of the polynomial, from 2 to MAX_POLYNOMIAL_DEGREE.
+    Polynomial(u8),
+    //y = a * e^(b * x), fit on the logarithm of y, so every y value must be positive.
+    Exponential,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RegressionError {
+    #[error("At least {needed} points are needed for this fit, found {found}")]
+    NotEnoughPoints { needed: usize, found: usize },
+    #[error("Polynomial degree must be between 2 and {MAX_POLYNOMIAL_DEGREE}, found {0}")]
+    InvalidDegree(u8),
+    #[error("An exponential fit needs every y value to be above zero, found {0}")]
+    NonPositiveValue(f64),
+    #[error("The points don't have enough distinct x values for this fit")]
+    Singular,
+}
+
+//A fitted trend line. Polynomial coefficients are in ascending powers of (x - x_offset), so [c, m] is y = c + m * (x - x_offset).
+//Exponential coefficients are [a, b] for y = a * e^(b * (x - x_offset)).
+//The offset is zero unless x is so far from zero compared to its spread, timestamps for example, that the powers of x would lose all precision.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct TrendFit {
+    pub trend_line: TrendLine,
+    pub coefficients: Vec<f64>,
+    pub x_offset: f64,
+    //Coefficient of determination, measured against the y values themselves, including for exponential fits.
+    pub r_squared: f64,
+}
+
+//A trend line fitted to one series of the graph, listed alongside it so the numbers can be read off.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SeriesTrendFit {
+    pub name: String,
+    //Position of the series in its list, which picks its colour.
+    pub index: usize,
+    pub secondary: bool,
+    pub fit: TrendFit,
+}
+
+impl TrendLine {
+    pub fn get_name(&self) -> String {
+        match self {
+            TrendLine::Linear => "Linear".to_owned(),
+            TrendLine::Polynomial(degree) => format!("Polynomial (degree {})", degree),
+            TrendLine::Exponential => "Exponential".to_owned(),
+        }
+    }
+}
+
+impl TrendFit {
+    pub fn evaluate(&self, x: f64) -> f64 {
+        let x = x - self.x_offset;
+        match self.trend_line {
+            TrendLine::Linear | TrendLine::Polynomial(_) => {
+                self.coefficients.iter().rev().fold(0f64, |accumulator, coefficient| accumulator * x + coefficient)
+            },
+            TrendLine::Exponential => self.coefficients[0] * (self.coefficients[1] * x).exp(),
+        }
+    }
+
+    //The equation as shown in the legend, highest power first.
+    pub fn get_equation(&self) -> String {
+        let x = if self.x_offset == 0f64 {
+            "x".to_owned()
+        } else {
+            format!("(x - {})", format_coefficient(self.x_offset))
+        };
+        match self.trend_line {
+            TrendLine::Linear | TrendLine::Polynomial(_) => {
+                let terms = self.coefficients.iter().enumerate().rev().map(|(power, coefficient)| {
+                    match power {
+                        0 => format_coefficient(*coefficient),
+                        1 => format!("{}{}", format_coefficient(*coefficient), x),
+                        _ => format!("{}{}^{}", format_coefficient(*coefficient), x, power),
+                    }
+                }).collect::<Vec<_>>();
+                format!("y = {}", terms.join(" + ").replace("+ -", "- "))
+            },
+            TrendLine::Exponential => format!("y = {}e^({}{})", format_coefficient(self.coefficients[0]), format_coefficient(self.coefficients[1]), x),
+        }
+    }
+}
+
+impl SeriesTrendFit {
+    pub fn get_label(&self) -> String {
+        format!("{} {}, R² = {:.3}", self.name, self.fit.get_equation(), self.fit.r_squared)
+    }
+}
+
+pub fn fit_trend_line(points: &[(f64, f64)], trend_line: TrendLine) -> Result<TrendFit, RegressionError> {
+    let (coefficients, x_offset) = match trend_line {
+        TrendLine::Linear => fit_polynomial(points, 1)?,
+        TrendLine::Polynomial(degree) => {
+            if !(2..=MAX_POLYNOMIAL_DEGREE).contains(&degree) {
+                return Err(RegressionError::InvalidDegree(degree))
+            }
+            fit_polynomial(points, degree as usize)?
+        },
+        TrendLine::Exponential => {
+            if let Some((_, y)) = points.iter().find(|(_, y)| *y <= 0f64) {
+                return Err(RegressionError::NonPositiveValue(*y))
+            }
+            let logarithms = points.iter().map(|(x, y)| (*x, y.ln())).collect::<Vec<_>>();
+            let (line, x_offset) = fit_polynomial(&logarithms, 1)?;
+            (vec![line[0].exp(), line[1]], x_offset)
+        },
+    };
+    let mut fit = TrendFit { trend_line, coefficients, x_offset, r_squared: 0f64 };
+    fit.r_squared = get_r_squared(points, &fit);
+    Ok(fit)
+}
+
+//Only the points inside the visible ranges, so the fit follows what the user is looking at.
+pub fn get_visible_points(points: &[(f64, f64)], x_range: &Range<f64>, y_range: &Range<f64>) -> Vec<(f64, f64)> {
+    points.iter().filter(|(x, y)| x_range.contains(x) && y_range.contains(y)).cloned().collect()
+}
+
+//Every trend line fitted to the visible points of every series, primary axis first. Series with too few points for a fit are left out.
+pub fn get_series_trend_fits(line_series: &LineSeriesHolder, trend_lines: &[TrendLine], x_range: &Range<f64>, y_range: &Range<f64>, secondary_y_range: &Range<f64>) -> Vec<SeriesTrendFit> {
+    let primary = line_series.series.iter().enumerate().map(|(index, series)| (index, false, series, y_range));
+    let secondary = line_series.secondary_series.iter().enumerate().map(|(index, series)| (index, true, series, secondary_y_range));
+    primary.chain(secondary).flat_map(|(index, secondary, series, y_range)| {
+        let visible_points = get_visible_points(&series.data_points, x_range, y_range);
+        trend_lines.iter().filter_map(|trend_line| fit_trend_line(&visible_points, *trend_line).ok()).map(|fit| {
+            SeriesTrendFit { name: series.name.clone(), index, secondary, fit }
+        }).collect::<Vec<_>>()
+    }).collect()
+}
+
+//Least squares through the normal equations, returning the coefficients and the x offset they are relative to.
+//x is centred and scaled first, since raising raw values to the sixth power loses all precision. The coefficients are then
+// expanded back into powers of x, unless x is too far from zero for that to be accurate.
+fn fit_polynomial(points: &[(f64, f64)], degree: usize) -> Result<(Vec<f64>, f64), RegressionError> {
+    if points.len() < degree + 1 {
+        return Err(RegressionError::NotEnoughPoints { needed: degree + 1, found: points.len() })
+    }
+    let count = points.len() as f64;
+    let mean = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let scale = points.iter().map(|(x, _)| (x - mean).abs()).fold(0f64, f64::max);
+    if scale == 0f64 {
+        return Err(RegressionError::Singular)
+    }
+
+    let size = degree + 1;
+    //Augmented matrix of the normal equations, [XᵀX | Xᵀy].
+    let mut matrix = vec![vec![0f64; size + 1]; size];
+    points.iter().for_each(|(x, y)| {
+        let t = (x - mean) / scale;
+        let powers = (0..size * 2).scan(1f64, |power, _| {
+            let current = *power;
+            *power *= t;
+            Some(current)
+        }).collect::<Vec<_>>();
+        for row in 0..size {
+            for column in 0..size {
+                matrix[row][column] += powers[row + column];
+            }
+            matrix[row][size] += powers[row] * y;
+        }
+    });
+    let scaled_coefficients = solve(matrix).ok_or(RegressionError::Singular)?;
+
+    //Expanding multiplies the rounding error by roughly (mean / scale)^degree.
+    let x_offset = if (mean.abs() / scale).powi(degree as i32) < 1e6 {
+        0f64
+    } else {
+        mean
+    };
+    let shift = mean - x_offset;
+
+    //Expand Σ aₖ((x - mean) / scale)ᵏ into Σ cⱼ(x - x_offset)ʲ.
+    let mut coefficients = vec![0f64; size];
+    scaled_coefficients.iter().enumerate().for_each(|(power, coefficient)| {
+        let factor = coefficient / scale.powi(power as i32);
+        let mut binomial = 1f64;
+        for x_power in (0..=power).rev() {
+            coefficients[x_power] += factor * binomial * (-shift).powi((power - x_power) as i32);
+            //C(power, x_power - 1) from C(power, x_power)
+            binomial = binomial * x_power as f64 / (power - x_power + 1) as f64;
+        }
+    });
+    Ok((coefficients, x_offset))
+}
+
+//Gaussian elimination with partial pivoting. None if the matrix is singular.
+fn solve(mut matrix: Vec<Vec<f64>>) -> Option<Vec<f64>> {
+    let size = matrix.len();
+    for column in 0..size {
+        let pivot = (column..size).max_by(|a, b| matrix[*a][column].abs().total_cmp(&matrix[*b][column].abs()))?;
+        if matrix[pivot][column].abs() < 1e-12 {
+            return None
+        }
+        matrix.swap(column, pivot);
+        let pivot_row = matrix[column].clone();
+        matrix.iter_mut().skip(column + 1).for_each(|row| {
+            let factor = row[column] / pivot_row[column];
+            row.iter_mut().zip(pivot_row.iter()).skip(column).for_each(|(entry, pivot_entry)| *entry -= factor * pivot_entry);
+        });
+    }
+    let mut solution = vec![0f64; size];
+    for row in (0..size).rev() {
+        let known = (row + 1..size).map(|column| matrix[row][column] * solution[column]).sum::<f64>();
+        solution[row] = (matrix[row][size] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+fn get_r_squared(points: &[(f64, f64)], fit: &TrendFit) -> f64 {
+    let mean = points.iter().map(|(_, y)| y).sum::<f64>() / points.len() as f64;
+    let (residual, total) = points.iter().fold((0f64, 0f64), |(residual, total), (x, y)| {
+        (residual + (y - fit.evaluate(*x)).powi(2), total + (y - mean).powi(2))
+    });
+    //Every y is the same, so any fit through them explains all there is to explain.
+    if total == 0f64 {
+        return 1f64
+    }
+    1f64 - residual / total
+}
+
+fn format_coefficient(coefficient: f64) -> String {
+    if coefficient != 0f64 && (coefficient.abs() >= 1e4 || coefficient.abs() < 1e-3) {
+        format!("{:.3e}", coefficient)
+    } else {
+        format!("{:.3}", coefficient)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::graph_axis::{AxisDataOption, AxisDataType, LineSeriesAxisData, LineSeriesData};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "expected {}, found {}", expected, actual);
+    }
+
+    #[test]
+    fn exact_linear_fit() {
+        let points = (0..10).map(|x| (x as f64, 3.0 * x as f64 - 2.0)).collect::<Vec<_>>();
+        let fit = fit_trend_line(&points, TrendLine::Linear).unwrap();
+        assert_close(fit.coefficients[0], -2.0);
+        assert_close(fit.coefficients[1], 3.0);
+        assert_close(fit.r_squared, 1.0);
+        assert_eq!(fit.get_equation(), "y = 3.000x - 2.000");
+    }
+
+    #[test]
+    fn polynomial_far_from_origin() {
+        //Large x values, like timestamps, would lose their precision without centring.
+        let points = (0..20).map(|i| {
+            let x = 1_700_000_000.0 + i as f64 * 60.0;
+            let t = i as f64;
+            (x, 0.5 * t * t - t + 4.0)
+        }).collect::<Vec<_>>();
+        let fit = fit_trend_line(&points, TrendLine::Polynomial(2)).unwrap();
+        assert!(fit.x_offset != 0.0);
+        assert_close(fit.r_squared, 1.0);
+        points.iter().for_each(|(x, y)| assert!((fit.evaluate(*x) - y).abs() < 1e-3));
+    }
+
+    #[test]
+    fn exponential_fit() {
+        let points = (0..10).map(|x| (x as f64, 2.0 * (0.3 * x as f64).exp())).collect::<Vec<_>>();
+        let fit = fit_trend_line(&points, TrendLine::Exponential).unwrap();
+        assert_close(fit.coefficients[0], 2.0);
+        assert_close(fit.coefficients[1], 0.3);
+        assert_close(fit.r_squared, 1.0);
+    }
+
+    #[test]
+    fn r_squared_of_noisy_line() {
+        let points = vec![(0.0, 1.0), (1.0, 3.0), (2.0, 2.0), (3.0, 5.0)];
+        let fit = fit_trend_line(&points, TrendLine::Linear).unwrap();
+        //Slope 1.1, intercept 1.1, residuals -0.1, 0.8, -1.3, 0.6 over a total of 8.75.
+        assert_close(fit.coefficients[1], 1.1);
+        assert_close(fit.r_squared, 1.0 - 2.7 / 8.75);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(fit_trend_line(&[(1.0, 1.0)], TrendLine::Linear), Err(RegressionError::NotEnoughPoints { needed: 2, found: 1 }));
+        assert_eq!(fit_trend_line(&[(1.0, 1.0), (1.0, 2.0)], TrendLine::Linear), Err(RegressionError::Singular));
+        assert_eq!(fit_trend_line(&[(0.0, 1.0), (1.0, 0.0)], TrendLine::Exponential), Err(RegressionError::NonPositiveValue(0.0)));
+        assert_eq!(fit_trend_line(&[(0.0, 1.0), (1.0, 0.0)], TrendLine::Polynomial(9)), Err(RegressionError::InvalidDegree(9)));
+    }
+
+    fn get_series(name: &str, data_points: Vec<(f64, f64)>) -> LineSeriesData {
+        LineSeriesData {
+            name: name.to_owned(),
+            timestamps: data_points.iter().map(|(x, _)| *x as i64).collect(),
+            data_points,
+            x_axis: LineSeriesAxisData { data_type: AxisDataType::Time, data_option: AxisDataOption::Average },
+            y_axis: LineSeriesAxisData { data_type: AxisDataType::SolarWatts, data_option: AxisDataOption::Average },
+        }
+    }
+
+    #[test]
+    fn series_trend_fits() {
+        let line_series = LineSeriesHolder {
+            series: vec![
+                get_series("Solar", (0..10).map(|x| (x as f64, 2.0 * x as f64 + 1.0)).collect()),
+                //Only one point is visible, too few for any fit.
+                get_series("Load", vec![(0.0, 5.0), (20.0, 5.0)]),
+            ],
+            //Visible against the secondary range only.
+            secondary_series: vec![get_series("Battery", (0..10).map(|x| (x as f64, 100.0 - x as f64)).collect())],
+        };
+        let fits = get_series_trend_fits(&line_series, &[TrendLine::Linear, TrendLine::Exponential], &(0.0..10.0), &(0.0..50.0), &(50.0..150.0));
+        assert_eq!(fits.len(), 4);
+        assert_eq!((fits[0].name.as_str(), fits[0].index, fits[0].secondary, fits[0].fit.trend_line), ("Solar", 0, false, TrendLine::Linear));
+        assert_close(fits[0].fit.coefficients[0], 1.0);
+        assert_close(fits[0].fit.coefficients[1], 2.0);
+        assert_close(fits[0].fit.r_squared, 1.0);
+        assert_eq!(fits[0].get_label(), "Solar y = 2.000x + 1.000, R² = 1.000");
+        assert_eq!(fits[1].fit.trend_line, TrendLine::Exponential);
+        assert!(fits[1].fit.r_squared < 1.0);
+        assert_eq!((fits[2].name.as_str(), fits[2].index, fits[2].secondary), ("Battery", 0, true));
+        assert_close(fits[2].fit.coefficients[1], -1.0);
+        assert_eq!(fits[3].fit.trend_line, TrendLine::Exponential);
+    }
+}
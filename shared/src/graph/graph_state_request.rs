@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
 use time::{PrimitiveDateTime, macros::{date, time}};
 
-use super::graph_axis::{AxisDataType, AxisDataOption, AxisControlsRequest, AxisTimeRequest};
+use super::{graph_aggregation::BucketFillPolicy, graph_fold::PeriodicDisplay, graph_regression::TrendLine, graph_axis::{AxisDataType, AxisDataOption, AxisControlsRequest, AxisTimeRequest}, graph_type::GraphType};
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct GraphStateRequest {
@@ -9,6 +9,24 @@ pub struct GraphStateRequest {
     pub x_axis: AxisControlsRequest,
     pub y_axis: (AxisControlsRequest, AxisControlsRequest),
     pub time_frame: AxisTimeRequest,
+    //None picks the graph type from the x axis.
+    #[serde(default)]
+    pub graph_type: Option<GraphType>,
+    //Fitted over the visible points of every series on scatter graphs. Other graph types ignore them.
+    #[serde(default)]
+    pub trend_lines: Vec<TrendLine>,
+    //Only drawn on time graphs, and only once a solar site has been entered.
+    #[serde(default)]
+    pub night_shading: NightShading,
+}
+
+//How night is shown behind time graphs. Sunrise and sunset come from the solar site, see solar_data::clear_sky.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, Debug)]
+pub enum NightShading {
+    #[default]
+    Off,
+    Shade,
+    ShadeAndMark,
 }
 
 //Note that Resolution must uphold the invariant that any of its members MUST be evenly divisible into 24 hours.
@@ -39,16 +57,42 @@ impl GraphStateRequest {
             graph_id: name,
             x_axis: AxisControlsRequest {
                 requests: vec![(AxisDataType::Time, AxisDataOption::Average)],
+                transforms: Vec::new(),
             },
             y_axis: (
                 AxisControlsRequest {
                     requests: vec![(AxisDataType::BatteryVoltage, AxisDataOption::Average)],
+                    transforms: Vec::new(),
                 },
                 AxisControlsRequest::default()), 
             time_frame: AxisTimeRequest { 
                 start: PrimitiveDateTime::new(date!(2022-01-01), time!(0:00)).assume_utc().unix_timestamp(),
                 end: PrimitiveDateTime::new(date!(2023-06-01), time!(0:00)).assume_utc().unix_timestamp(), 
                 manual_resolution: Some(Resolution::OneDay),
+                fill_policy: BucketFillPolicy::default(),
+                periodic_display: PeriodicDisplay::default(),
+                comparisons: Vec::new(),
+            },
+            graph_type: None,
+            trend_lines: Vec::new(),
+            night_shading: NightShading::default(),
+        }
+    }
+
+    pub fn get_graph_type(&self) -> GraphType {
+        if let Some(graph_type) = &self.graph_type {
+            return graph_type.clone()
+        }
+        match self.x_axis.requests.first() {
+            Some((data_type, _data_option)) => {
+                match data_type {
+                    AxisDataType::Time
+                    | AxisDataType::PeriodicTime(_) => GraphType::XAxisLine,
+                    _ => GraphType::XYScatter,
+                }
+            },
+            None => {
+                GraphType::XAxisLine //In the case where there is no data to graph, an example line chart will be plotted
             }
         }
     }
@@ -0,0 +1,142 @@
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+
+use crate::solar_data::energy::MAX_INTEGRATION_GAP;
+use super::graph_axis::{AxisDataOption, AxisDataType, DataUnit, LineSeriesData};
+
+
+//Statistics of the points of one series that are inside the visible x range.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SeriesStatistics {
+    pub name: String,
+    pub count: usize,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub mean: f64,
+    //Each point weighted by how long it lasted, so bursts of fast samples don't outweigh long quiet stretches. Only for time x axes.
+    pub time_weighted_mean: Option<f64>,
+    pub standard_deviation: f64,
+    //Area under the series with its unit, for series where that is a quantity, like watts into kilowatt hours.
+    pub integral: Option<(f64, String)>,
+}
+
+//Only aggregations that keep the channel's meaning can be integrated. The integral of a count or a standard deviation isn't anything.
+fn get_integral_unit(series: &LineSeriesData) -> Option<(f64, &'static str)> {
+    let keeps_meaning = matches!(series.y_axis.data_option,
+        AxisDataOption::Average | AxisDataOption::Median | AxisDataOption::First | AxisDataOption::Last | AxisDataOption::Sample);
+    if !keeps_meaning {
+        return None
+    }
+    //Divisors from value seconds to the integral's unit.
+    match series.y_axis.data_type.get_unit() {
+        DataUnit::Watts => Some((3_600_000f64, "kWh")),
+        DataUnit::Amps => Some((3600f64, "Ah")),
+        _ => None,
+    }
+}
+
+//Trapezoidal integral of value seconds, and the seconds it covers. Intervals much longer than usual are gaps, and aren't integrated over.
+fn integrate(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let mut intervals = points.windows(2).map(|pair| pair[1].0 - pair[0].0).collect::<Vec<_>>();
+    if intervals.is_empty() {
+        return None
+    }
+    intervals.sort_by(|a, b| a.total_cmp(b));
+    let max_interval = (intervals[intervals.len() / 2] * 3f64).max(MAX_INTEGRATION_GAP as f64);
+    let (area, duration) = points.windows(2).fold((0f64, 0f64), |(area, duration), pair| {
+        let ((start, start_value), (end, end_value)) = (pair[0], pair[1]);
+        let interval = end - start;
+        match interval > 0f64 && interval <= max_interval {
+            true => (area + (start_value + end_value) / 2f64 * interval, duration + interval),
+            false => (area, duration),
+        }
+    });
+    (duration > 0f64).then_some((area, duration))
+}
+
+pub fn get_series_statistics(series: &LineSeriesData, x_range: &Range<f64>) -> Option<SeriesStatistics> {
+    let points = series.data_points.iter()
+        .filter(|(x, y)| x_range.contains(x) && y.is_finite())
+        .cloned()
+        .collect::<Vec<_>>();
+    if points.is_empty() {
+        return None
+    }
+    let count = points.len();
+    let mean = points.iter().map(|(_, y)| y).sum::<f64>() / count as f64;
+    let variance = points.iter().map(|(_, y)| (y - mean).powi(2)).sum::<f64>() / count as f64;
+    let (minimum, maximum) = points.iter().fold((f64::MAX, f64::MIN), |(minimum, maximum), (_, y)| (minimum.min(*y), maximum.max(*y)));
+
+    let integrated = match series.x_axis.data_type {
+        AxisDataType::Time => integrate(&points),
+        _ => None,
+    };
+    Some(SeriesStatistics {
+        name: series.name.clone(),
+        count,
+        minimum,
+        maximum,
+        mean,
+        time_weighted_mean: integrated.map(|(area, duration)| area / duration),
+        standard_deviation: variance.sqrt(),
+        integral: integrated.zip(get_integral_unit(series)).map(|((area, _), (divisor, unit))| (area / divisor, unit.to_owned())),
+    })
+}
+
+//Every series of the graph with points in the range, primary axis first.
+pub fn get_visible_statistics<'a>(series: impl Iterator<Item = &'a LineSeriesData>, x_range: &Range<f64>) -> Vec<SeriesStatistics> {
+    series.filter_map(|series| get_series_statistics(series, x_range)).collect()
+}
+
+//Tab separated, so it pastes into a spreadsheet as a table.
+pub fn statistics_to_tsv(statistics: &[SeriesStatistics]) -> String {
+    let optional = |value: Option<f64>| value.map(|value| format!("{:.4}", value)).unwrap_or_default();
+    let mut text = "Series\tCount\tMin\tMax\tMean\tTime Weighted Mean\tStd Dev\tIntegral\n".to_owned();
+    statistics.iter().for_each(|row| {
+        let integral = row.integral.as_ref().map(|(value, unit)| format!("{:.4} {}", value, unit)).unwrap_or_default();
+        text.push_str(&format!("{}\t{}\t{:.4}\t{:.4}\t{:.4}\t{}\t{:.4}\t{}\n", row.name, row.count, row.minimum, row.maximum, row.mean,
+            optional(row.time_weighted_mean), row.standard_deviation, integral));
+    });
+    text
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::graph_axis::LineSeriesAxisData;
+
+    fn get_series(data_points: Vec<(f64, f64)>) -> LineSeriesData {
+        LineSeriesData {
+            name: "Solar Watts".to_owned(),
+            timestamps: data_points.iter().map(|(x, _)| *x as i64).collect(),
+            data_points,
+            x_axis: LineSeriesAxisData { data_type: AxisDataType::Time, data_option: AxisDataOption::Average },
+            y_axis: LineSeriesAxisData { data_type: AxisDataType::SolarWatts, data_option: AxisDataOption::Average },
+        }
+    }
+
+    #[test]
+    fn only_visible_points() {
+        let series = get_series(vec![(0.0, 1000.0), (60.0, 2000.0), (120.0, 3000.0), (180.0, 100000.0)]);
+        let statistics = get_series_statistics(&series, &(0.0..150.0)).unwrap();
+        assert_eq!(statistics.count, 3);
+        assert_eq!((statistics.minimum, statistics.maximum, statistics.mean), (1000.0, 3000.0, 2000.0));
+        assert!(get_series_statistics(&series, &(200.0..300.0)).is_none());
+    }
+
+    #[test]
+    fn time_weighting_and_integral() {
+        //A burst of high samples, then one long low stretch. The plain mean is dominated by the burst.
+        let series = get_series(vec![(0.0, 1000.0), (10.0, 1000.0), (20.0, 1000.0), (30.0, 0.0), (630.0, 0.0)]);
+        let statistics = get_series_statistics(&series, &(0.0..4000.0)).unwrap();
+        assert_eq!(statistics.mean, 600.0);
+        assert!(statistics.time_weighted_mean.unwrap() < 50.0);
+        //1000 W for 20 s, plus the ramp down over 10 s.
+        let (integral, unit) = statistics.integral.clone().unwrap();
+        assert_eq!(unit, "kWh");
+        assert!((integral - 25000.0 / 3_600_000.0).abs() < 1e-12);
+        assert!(statistics_to_tsv(&[statistics]).lines().nth(1).unwrap().starts_with("Solar Watts\t5\t"));
+    }
+}
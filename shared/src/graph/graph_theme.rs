@@ -1,6 +1,7 @@
 use plotters::style::RGBColor;
+use serde::{Serialize, Deserialize};
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Color (pub u8, pub u8, pub u8);
 
 #[derive(Debug, thiserror::Error)]
@@ -13,16 +14,14 @@ pub enum HexCodeError {
     InvalidCharacter(char)
 }
 
-type HCE = HexCodeError;
-
 
 impl Color {
     pub fn from_hex_code(code: &str) -> Result<Color, HexCodeError> {
         match code.chars().nth(0) {
             Some(c) => if c != '#' {
-                return Err(HCE::InvalidFirstCharacter(c))
+                return Err(HexCodeError::InvalidFirstCharacter(c))
             },
-            None => return Err(HCE::InvalidLength(0)),
+            None => return Err(HexCodeError::InvalidLength(0)),
         }
 
         match code.len() {
@@ -35,11 +34,11 @@ impl Color {
                             2 => acc.2 = n*16+n,
                             _ => panic!("We have already confirmed the length is exactly 3 characters after skipping the leading #.")
                         },
-                        Err(_) => return Err(HCE::InvalidCharacter(c.1)),
+                        Err(_) => return Err(HexCodeError::InvalidCharacter(c.1)),
                     }
                     Ok(acc)
                 })?;
-                return Ok(Color(numbers.0, numbers.1, numbers.2))
+                Ok(Color(numbers.0, numbers.1, numbers.2))
             },
             7 => {
                 let numbers = code.chars().skip(1).enumerate().try_fold((0u8, 0u8, 0u8), |mut acc, c| {
@@ -53,15 +52,13 @@ impl Color {
                             5 => acc.2 += n,
                             _ => panic!("We have already confirmed the length is exactly 6 characters after skipping the leading #.")
                         },
-                        Err(_) => return Err(HCE::InvalidCharacter(c.1)),
+                        Err(_) => return Err(HexCodeError::InvalidCharacter(c.1)),
                     }
                     Ok(acc)
                 })?;
-                return Ok(Color(numbers.0, numbers.1, numbers.2))
+                Ok(Color(numbers.0, numbers.1, numbers.2))
             },
-            _ => {
-                return Err(HCE::InvalidLength(code.len()))
-            }
+            _ => Err(HexCodeError::InvalidLength(code.len())),
         }
     }
 }
@@ -70,6 +67,7 @@ impl Color {
 
 
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ThemeData {
     pub theme_primary: Color,
     pub theme_secondary: Color,
@@ -81,6 +79,28 @@ pub struct ThemeData {
     pub theme_graph_mesh_light: Color,
     pub theme_graph_mesh_dark: Color,
     pub theme_graph_border: Color,
+    //Bands and lines marking detected anomalies.
+    pub theme_graph_anomaly: Color,
+}
+
+impl Color {
+    //Blends towards the other colour, fraction is clamped to 0..=1.
+    pub fn blend(&self, other: &Color, fraction: f64) -> Color {
+        let fraction = fraction.clamp(0f64, 1f64);
+        let channel = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * fraction).round() as u8;
+        Color(channel(self.0, other.0), channel(self.1, other.1), channel(self.2, other.2))
+    }
+}
+
+impl ThemeData {
+    //The heatmap colour scale, from the tertiary background at the lowest value through secondary to primary at the highest.
+    pub fn get_heatmap_color(&self, fraction: f64) -> Color {
+        if fraction < 0.5 {
+            self.theme_background_tertiary.blend(&self.theme_secondary, fraction * 2f64)
+        } else {
+            self.theme_secondary.blend(&self.theme_primary, (fraction - 0.5) * 2f64)
+        }
+    }
 }
 
 impl From<&Color> for RGBColor {
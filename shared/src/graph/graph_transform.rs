@@ -0,0 +1,129 @@
+use serde::{Serialize, Deserialize};
+
+
+pub const DEFAULT_TRANSFORM_WINDOW: u16 = 5;
+pub const DEFAULT_SMOOTHING_PERCENT: u8 = 20;
+
+//Applied to a series after it has been bucketed, so the window is in points rather than time.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub enum SeriesTransform {
+    //Mean of the window centred on each point.
+    MovingAverage(u16),
+    //Each point moves this percent of the way from the previous smoothed value towards the new value.
+    ExponentialSmoothing(u8),
+    //Median of the window centred on each point. Removes spikes without smearing steps the way an average does.
+    MedianFilter(u16),
+    //Change per hour between each point and the one before it.
+    Derivative,
+}
+
+impl SeriesTransform {
+    pub fn get_name(&self) -> String {
+        match self {
+            SeriesTransform::MovingAverage(window) => format!("Moving Avg {}", window),
+            SeriesTransform::ExponentialSmoothing(percent) => format!("Exp Smoothing {}%", percent),
+            SeriesTransform::MedianFilter(window) => format!("Median Filter {}", window),
+            SeriesTransform::Derivative => "Rate per Hour".to_owned(),
+        }
+    }
+
+    //The same transform with a different window or smoothing percent, kept in range. The derivative has no parameter.
+    pub fn with_parameter(&self, parameter: u16) -> Self {
+        match self {
+            SeriesTransform::MovingAverage(_) => SeriesTransform::MovingAverage(parameter.max(1)),
+            SeriesTransform::ExponentialSmoothing(_) => SeriesTransform::ExponentialSmoothing(parameter.clamp(1, 100) as u8),
+            SeriesTransform::MedianFilter(_) => SeriesTransform::MedianFilter(parameter.max(1)),
+            SeriesTransform::Derivative => SeriesTransform::Derivative,
+        }
+    }
+
+    pub fn get_parameter(&self) -> Option<u16> {
+        match self {
+            SeriesTransform::MovingAverage(window) | SeriesTransform::MedianFilter(window) => Some(*window),
+            SeriesTransform::ExponentialSmoothing(percent) => Some(*percent as u16),
+            SeriesTransform::Derivative => None,
+        }
+    }
+
+    //One of each kind, with default parameters.
+    pub fn get_selectable_transforms() -> Vec<SeriesTransform> {
+        vec![
+            SeriesTransform::MovingAverage(DEFAULT_TRANSFORM_WINDOW),
+            SeriesTransform::ExponentialSmoothing(DEFAULT_SMOOTHING_PERCENT),
+            SeriesTransform::MedianFilter(DEFAULT_TRANSFORM_WINDOW),
+            SeriesTransform::Derivative,
+        ]
+    }
+
+    //Every point gets a value, except the first point of a derivative, which has nothing before it to compare to.
+    //Timestamps must be ascending, as they are after bucketing.
+    pub fn apply(&self, points: &[(i64, f64)]) -> Vec<(i64, f64)> {
+        match self {
+            SeriesTransform::MovingAverage(window) => apply_window(points, *window, |values| values.iter().sum::<f64>() / values.len() as f64),
+            SeriesTransform::MedianFilter(window) => apply_window(points, *window, |values| {
+                values.sort_by(|a, b| a.total_cmp(b));
+                let middle = values.len() / 2;
+                match values.len() % 2 {
+                    0 => (values[middle - 1] + values[middle]) / 2f64,
+                    _ => values[middle],
+                }
+            }),
+            SeriesTransform::ExponentialSmoothing(percent) => {
+                let alpha = (*percent).clamp(1, 100) as f64 / 100f64;
+                let mut smoothed: Option<f64> = None;
+                points.iter().map(|(timestamp, value)| {
+                    let next = match smoothed {
+                        Some(previous) => previous + alpha * (value - previous),
+                        None => *value,
+                    };
+                    smoothed = Some(next);
+                    (*timestamp, next)
+                }).collect()
+            },
+            SeriesTransform::Derivative => points.windows(2).filter_map(|pair| {
+                let ((previous_time, previous_value), (time, value)) = (pair[0], pair[1]);
+                (time > previous_time).then(|| (time, (value - previous_value) / (time - previous_time) as f64 * 3600f64))
+            }).collect(),
+        }
+    }
+}
+
+//Windows are centred, and shrink at the ends of the series rather than dropping points.
+fn apply_window(points: &[(i64, f64)], window: u16, reduce: impl Fn(&mut Vec<f64>) -> f64) -> Vec<(i64, f64)> {
+    let half = (window.max(1) as usize - 1) / 2;
+    let extra = (window.max(1) as usize - 1) - half;
+    points.iter().enumerate().map(|(index, (timestamp, _))| {
+        let start = index.saturating_sub(half);
+        let end = (index + extra + 1).min(points.len());
+        let mut values = points[start..end].iter().map(|(_, value)| *value).collect::<Vec<_>>();
+        (*timestamp, reduce(&mut values))
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_shrink_at_the_ends() {
+        let points = vec![(0, 1.0), (60, 2.0), (120, 3.0), (180, 100.0), (240, 5.0)];
+        let averaged = SeriesTransform::MovingAverage(3).apply(&points);
+        assert_eq!(averaged.len(), points.len());
+        assert_eq!(averaged[0], (0, 1.5));
+        assert_eq!(averaged[1], (60, 2.0));
+        //The median drops the spike, the average spreads it out.
+        let filtered = SeriesTransform::MedianFilter(3).apply(&points);
+        assert_eq!(filtered[3], (180, 5.0));
+        assert!(averaged[2].1 > 30.0);
+    }
+
+    #[test]
+    fn smoothing_and_rate() {
+        let points = vec![(0, 0.0), (1800, 10.0), (3600, 10.0)];
+        let smoothed = SeriesTransform::ExponentialSmoothing(50).apply(&points);
+        assert_eq!(smoothed, vec![(0, 0.0), (1800, 5.0), (3600, 7.5)]);
+        let rate = SeriesTransform::Derivative.apply(&points);
+        assert_eq!(rate, vec![(1800, 20.0), (3600, 0.0)]);
+    }
+}
@@ -0,0 +1,233 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use thiserror::Error;
+
+use super::graph_axis::{LineSeriesAxisData, LineSeriesData, LineSeriesHolder};
+
+
+//Bumped whenever the layout changes, so a mismatched frontend and backend fail clearly rather than misread each other.
+const FORMAT_VERSION: u8 = 1;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum TransportError {
+    #[error("Invalid base64 in series payload: {0}")]
+    Base64(String),
+    #[error("Unsupported series payload version {0}, expected {FORMAT_VERSION}.")]
+    UnsupportedVersion(u8),
+    #[error("Series payload ended early.")]
+    Truncated,
+    #[error("Invalid series header: {0}")]
+    Header(String),
+}
+
+//Everything about a series except its points. It's small, so it stays as JSON.
+#[derive(Serialize, Deserialize)]
+struct SeriesHeader {
+    name: String,
+    x_axis: LineSeriesAxisData,
+    y_axis: LineSeriesAxisData,
+    point_count: usize,
+    timestamp_count: usize,
+    secondary: bool,
+}
+
+//Graph data crosses between the backend and the webview as text. As JSON every float is spelled out in decimal, which makes large series
+// about twice the size of the numbers themselves and slow to parse in wasm. Instead the points are packed into columns of little endian
+// floats, with the timestamps delta encoded as varints, and the whole thing is base64 encoded.
+//Layout: version, header length as a little endian u32, the header as JSON, then each series' timestamps, x column and y column in header order.
+pub fn encode_line_series(holder: &LineSeriesHolder) -> Result<String, TransportError> {
+    let all_series = holder.series.iter().map(|series| (series, false))
+        .chain(holder.secondary_series.iter().map(|series| (series, true)));
+    let headers = all_series.clone().map(|(series, secondary)| SeriesHeader {
+        name: series.name.clone(),
+        x_axis: series.x_axis.clone(),
+        y_axis: series.y_axis.clone(),
+        point_count: series.data_points.len(),
+        timestamp_count: series.timestamps.len(),
+        secondary,
+    }).collect::<Vec<_>>();
+    let header = serde_json::to_vec(&headers).map_err(|e| TransportError::Header(e.to_string()))?;
+
+    let point_count = holder.series.iter().chain(holder.secondary_series.iter()).map(|series| series.data_points.len()).sum::<usize>();
+    let mut bytes = Vec::with_capacity(5 + header.len() + point_count * 18);
+    bytes.push(FORMAT_VERSION);
+    bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&header);
+    all_series.for_each(|(series, _)| {
+        let mut previous = 0i64;
+        series.timestamps.iter().for_each(|timestamp| {
+            write_varint(&mut bytes, timestamp.wrapping_sub(previous));
+            previous = *timestamp;
+        });
+        series.data_points.iter().for_each(|(x, _)| bytes.extend_from_slice(&x.to_le_bytes()));
+        series.data_points.iter().for_each(|(_, y)| bytes.extend_from_slice(&y.to_le_bytes()));
+    });
+    Ok(STANDARD.encode(bytes))
+}
+
+pub fn decode_line_series(encoded: &str) -> Result<LineSeriesHolder, TransportError> {
+    let bytes = STANDARD.decode(encoded).map_err(|e| TransportError::Base64(e.to_string()))?;
+    let mut reader = Reader { bytes: &bytes, position: 0 };
+    let version = reader.take(1)?[0];
+    if version != FORMAT_VERSION {
+        return Err(TransportError::UnsupportedVersion(version))
+    }
+    let header_length = u32::from_le_bytes(reader.take(4)?.try_into().map_err(|_| TransportError::Truncated)?) as usize;
+    let headers = serde_json::from_slice::<Vec<SeriesHeader>>(reader.take(header_length)?).map_err(|e| TransportError::Header(e.to_string()))?;
+
+    let mut holder = LineSeriesHolder::default();
+    for header in headers {
+        let mut previous = 0i64;
+        let timestamps = (0..header.timestamp_count).map(|_| {
+            previous = previous.wrapping_add(reader.read_varint()?);
+            Ok(previous)
+        }).collect::<Result<Vec<_>, TransportError>>()?;
+        let x_values = reader.read_floats(header.point_count)?;
+        let y_values = reader.read_floats(header.point_count)?;
+        let series = LineSeriesData {
+            name: header.name,
+            data_points: x_values.into_iter().zip(y_values).collect(),
+            timestamps,
+            x_axis: header.x_axis,
+            y_axis: header.y_axis,
+        };
+        match header.secondary {
+            true => holder.secondary_series.push(series),
+            false => holder.series.push(series),
+        }
+    }
+    Ok(holder)
+}
+
+//For fields that hold series, so they use the compact encoding inside an otherwise JSON request.
+pub mod compact {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(holder: &LineSeriesHolder, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_line_series(holder).map_err(serde::ser::Error::custom)?)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<LineSeriesHolder, D::Error> {
+        decode_line_series(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+//Zigzag, so small negative deltas are small too, then seven bits per byte with the high bit set on every byte but the last.
+fn write_varint(bytes: &mut Vec<u8>, value: i64) {
+    let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    while zigzag >= 0x80 {
+        bytes.push((zigzag as u8) | 0x80);
+        zigzag >>= 7;
+    }
+    bytes.push(zigzag as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], TransportError> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.bytes.len()).ok_or(TransportError::Truncated)?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn read_varint(&mut self) -> Result<i64, TransportError> {
+        let mut zigzag = 0u64;
+        //Ten bytes hold all 64 bits.
+        for shift in (0..70).step_by(7) {
+            let byte = self.take(1)?[0];
+            zigzag |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+            }
+        }
+        Err(TransportError::Truncated)
+    }
+
+    fn read_floats(&mut self, count: usize) -> Result<Vec<f64>, TransportError> {
+        let bytes = self.take(count.checked_mul(8).ok_or(TransportError::Truncated)?)?;
+        Ok(bytes.chunks_exact(8).map(|chunk| f64::from_le_bytes(chunk.try_into().expect("Chunks are exactly eight bytes"))).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::graph_axis::{AxisDataOption, AxisDataType};
+
+    //A week of one minute buckets for each channel, like a busy graph.
+    fn get_holder(channels: u16) -> LineSeriesHolder {
+        let series = (0..channels).map(|channel| {
+            let timestamps = (0..7 * 1440).map(|minute| 1_686_000_000 + minute * 60).collect::<Vec<i64>>();
+            LineSeriesData {
+                name: format!("Cell {} Voltage", channel),
+                data_points: timestamps.iter().map(|timestamp| (*timestamp as f64 + 29.5, 3.3 + (*timestamp as f64 / 3600.0).sin() * 0.1)).collect(),
+                timestamps,
+                x_axis: LineSeriesAxisData { data_type: AxisDataType::Time, data_option: AxisDataOption::Average },
+                y_axis: LineSeriesAxisData { data_type: AxisDataType::CellVoltage(channel), data_option: AxisDataOption::Average },
+            }
+        }).collect::<Vec<_>>();
+        LineSeriesHolder { secondary_series: series[..1].to_vec(), series }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut holder = get_holder(3);
+        //Out of order timestamps still survive, and so does a series without any.
+        holder.series[1].timestamps.swap(0, 5);
+        holder.series[2].timestamps.clear();
+        let decoded = decode_line_series(&encode_line_series(&holder).unwrap()).unwrap();
+        assert_eq!(decoded, holder);
+        assert_eq!(decode_line_series(&encode_line_series(&LineSeriesHolder::default()).unwrap()).unwrap(), LineSeriesHolder::default());
+    }
+
+    #[test]
+    fn smaller_than_json() {
+        let holder = get_holder(20);
+        let json = serde_json::to_string(&holder).unwrap();
+        let encoded = encode_line_series(&holder).unwrap();
+        assert!(encoded.len() * 5 < json.len() * 3, "compact {} bytes, JSON {} bytes", encoded.len(), json.len());
+    }
+
+    //Timing depends on the machine, so this only runs when asked for, ideally with --release --nocapture.
+    #[test]
+    #[ignore]
+    fn faster_than_json() {
+        let holder = get_holder(20);
+        //The fastest of several runs, the others are mostly noise from the rest of the machine.
+        let time = |round_trip: &dyn Fn()| {
+            (0..5).map(|_| {
+                let start = std::time::Instant::now();
+                round_trip();
+                start.elapsed()
+            }).min().unwrap()
+        };
+        let json = time(&|| {
+            let decoded = serde_json::from_str::<LineSeriesHolder>(&serde_json::to_string(&holder).unwrap()).unwrap();
+            assert_eq!(decoded.series.len(), 20);
+        });
+        let compact = time(&|| {
+            let decoded = decode_line_series(&encode_line_series(&holder).unwrap()).unwrap();
+            assert_eq!(decoded.series.len(), 20);
+        });
+        let sizes = (encode_line_series(&holder).unwrap().len(), serde_json::to_string(&holder).unwrap().len());
+        println!("Encode and decode of 20 series: compact {:?} for {} bytes, JSON {:?} for {} bytes", compact, sizes.0, json, sizes.1);
+        assert!(compact < json, "compact {:?}, JSON {:?}", compact, json);
+    }
+
+    #[test]
+    fn bad_payloads() {
+        let encoded = encode_line_series(&get_holder(1)).unwrap();
+        let bytes = STANDARD.decode(&encoded).unwrap();
+        assert_eq!(decode_line_series(&STANDARD.encode(&bytes[..bytes.len() - 1])), Err(TransportError::Truncated));
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 0;
+        assert_eq!(decode_line_series(&STANDARD.encode(wrong_version)), Err(TransportError::UnsupportedVersion(0)));
+        assert!(matches!(decode_line_series("not base64!"), Err(TransportError::Base64(_))));
+    }
+}
\ No newline at end of file
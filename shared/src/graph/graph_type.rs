@@ -1,7 +1,50 @@
+use serde::{Serialize, Deserialize};
 
 
-
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub enum GraphType {
     XAxisLine,
     XYScatter,
-}
\ No newline at end of file
+    //The distribution of the primary y axis channels. The x axis channels aren't used. See graph_histogram.
+    Histogram(HistogramOptions),
+    //Days along the x axis and hour of day up the y axis, with each hour coloured by the first primary y axis channel.
+    Heatmap,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
+pub struct HistogramOptions {
+    pub bins: HistogramBins,
+    pub measure: HistogramMeasure,
+}
+
+//Either a fixed number of bins spread over the data, or bins of a fixed width lined up on multiples of the width.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum HistogramBins {
+    Count(u16),
+    Width(f64),
+}
+
+impl Default for HistogramBins {
+    fn default() -> Self {
+        HistogramBins::Count(20)
+    }
+}
+
+//What the height of each bar shows.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, Debug)]
+pub enum HistogramMeasure {
+    //The number of points that fell in the bin.
+    #[default]
+    Count,
+    //The time the channel spent in the bin.
+    Hours,
+}
+
+impl HistogramMeasure {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            HistogramMeasure::Count => "Count",
+            HistogramMeasure::Hours => "Hours",
+        }
+    }
+}
@@ -2,6 +2,8 @@ pub mod solar_data;
 pub mod parse;
 pub mod types;
 pub mod graph;
+pub mod settings;
+pub mod command_error;
 
 // pub fn add(left: usize, right: usize) -> usize {
 //     left + right
@@ -11,7 +11,7 @@ pub struct LiveData {
 }
 
 impl TryParse for LiveData {
-    fn try_parse(&mut self, line: &Vec<&str>) -> Result<DataLine, ParseError> {
+    fn try_parse(&mut self, line: &[&str]) -> Result<DataLine, ParseError> {
         let mut data_line_builder = DataLineBuilder::default();
         if let Ok(version) = check_version(line) {
             self.version = version;
@@ -33,7 +33,7 @@ impl TryParse for LiveData {
                 .next()
                 .ok_or(ParseError::InsufficientData)?
                 .as_bytes(),
-            &format_description!("[month padding:none]/[day]/[year repr:last_two]"),
+            format_description!("[month padding:none]/[day]/[year repr:last_two]"),
         )?;
         parsed.set_year(convert_year(
             parsed
@@ -133,7 +133,7 @@ impl TryParse for LiveData {
                 .parse::<f32>()?;
             data_line_builder.add_data(DataValue::ControllerAmps {
                 controller: address,
-                amps: amps,
+                amps,
             });
             let temp = current
                 .next()
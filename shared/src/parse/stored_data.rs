@@ -11,7 +11,7 @@ pub struct StoredData {
 }
 
 impl TryParse for StoredData {
-    fn try_parse(&mut self, line: &Vec<&str>) -> Result<DataLine, ParseError> {
+    fn try_parse(&mut self, line: &[&str]) -> Result<DataLine, ParseError> {
         let data_line_builder = DataLineBuilder::default();
         if let Ok(version) = check_version(line) {
             self.version = version;
@@ -26,7 +26,7 @@ impl TryParse for StoredData {
                 .next()
                 .ok_or(ParseError::InsufficientData)?
                 .as_bytes(),
-            &format_description!("[month padding:none]/[day]/[year repr:last_two]"),
+            format_description!("[month padding:none]/[day]/[year repr:last_two]"),
         )?;
         parsed.set_year(convert_year(
             parsed
@@ -78,12 +78,14 @@ impl TryParse for StoredData {
         
         //Variable number of cell voltage statistics iff there is a delimeter
         let delimeter = current.next();
-        if let None = delimeter {
+        if delimeter.is_none() {
             return Ok(data_line_builder.build());
         }
         let counter = current.clone().count();
         //There should be counter-4 entries related to cell voltage, two for each cell, one for the high voltage, one for the low voltage
-        for n in 0..(counter - 4) / 2 {
+        //Rows cut short don't even have the four statistics that follow the cells.
+        let cell_entries = counter.checked_sub(4).ok_or(ParseError::InsufficientData)?;
+        for n in 0..cell_entries / 2 {
             let cell_voltage_low = current
                 .next()
                 .ok_or(ParseError::InsufficientData)?
@@ -133,4 +135,21 @@ impl TryParse for StoredData {
         
         Ok(data_line_builder.build())
     }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_statistics_rows() {
+        let mut stored_data = StoredData::default();
+        let full = "6/15/23,12:00,13.2,95,10,2,,3.31,3.45,3.30,3.44,120,30,80,100";
+        let line = stored_data.try_parse(&full.split(',').collect::<Vec<_>>()).unwrap();
+        assert!(line.line.contains(&DataValue::StatisticsCellVoltageHigh { cell: 1, voltage: 3.44 }));
+        //Fewer entries after the delimeter than the four statistics.
+        let short = "6/15/23,12:00,13.2,95,10,2,,120,30";
+        assert!(matches!(stored_data.try_parse(&short.split(',').collect::<Vec<_>>()), Err(ParseError::InsufficientData)));
+    }
 }
\ No newline at end of file
@@ -1,16 +1,19 @@
+use std::collections::HashMap;
+
 use crate::solar_data::cell::AvailableCells;
 use crate::solar_data::controllers::AvailableControllers;
+use crate::types::fixed_size_queue::FixedSizeQueue;
 
 use super::parse_error::ParseError;
 use super::version::Version;
 
 
-pub fn check_version(line: &Vec<&str>) -> Result<Version, ParseError> {
-    let last_entry = line.iter().last().ok_or(ParseError::InsufficientData)?;
+pub fn check_version(line: &[&str]) -> Result<Version, ParseError> {
+    let last_entry = line.last().ok_or(ParseError::InsufficientData)?;
     if last_entry.contains("Ver") {
         let mut version_number = last_entry
             .split(' ')
-            .last()
+            .next_back()
             .ok_or(ParseError::ImproperFormat)?
             .split('.');
         let major = version_number
@@ -22,8 +25,8 @@ pub fn check_version(line: &Vec<&str>) -> Result<Version, ParseError> {
             .ok_or(ParseError::ImproperFormat)?
             .parse::<u32>()?;
         Ok(Version {
-            major: major,
-            minor: minor,
+            major,
+            minor,
         })
     } else {
         Err(ParseError::NoVersion)
@@ -51,4 +54,142 @@ pub struct ParseCompleteReturnValue {
     pub name: String,
     pub cell_ids: AvailableCells,
     pub controller_ids: AvailableControllers,
+}
+
+//Where an import has got to. Sent every few thousand lines while parsing, then once more with how it ended.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportState {
+    Parsing,
+    Complete,
+    Cancelled,
+    Failed,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct ImportProgress {
+    pub import_id: u32,
+    pub name: String,
+    pub state: ImportState,
+    pub bytes_processed: usize,
+    pub total_bytes: usize,
+    pub lines_processed: usize,
+    //Lines neither format could read. The few header lines in each file are counted here too.
+    pub lines_rejected: usize,
+}
+
+impl ImportProgress {
+    pub fn new(import_id: u32, name: String, total_bytes: usize) -> Self {
+        Self { import_id, name, state: ImportState::Parsing, bytes_processed: 0, total_bytes, lines_processed: 0, lines_rejected: 0 }
+    }
+
+    pub fn get_fraction(&self) -> f64 {
+        match self.total_bytes {
+            0 => 1.0,
+            total => (self.bytes_processed as f64 / total as f64).min(1.0),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state != ImportState::Parsing
+    }
+}
+
+//How many early cancels and finished imports are remembered. Far more than could be in flight at once.
+const IMPORT_HISTORY_SIZE: usize = 64;
+
+//The imports in progress and whether each has been asked to stop.
+//A cancel can reach the backend before its import registers, or after it finishes. An early one is kept until the import registers
+// and takes it, a late one is dropped, and both are bounded so ids that come round again aren't cancelled by a stale request.
+pub struct ImportRegistry {
+    running: HashMap<u32, bool>,
+    early_cancels: FixedSizeQueue<u32>,
+    finished: FixedSizeQueue<u32>,
+}
+
+impl Default for ImportRegistry {
+    fn default() -> Self {
+        Self {
+            running: HashMap::new(),
+            early_cancels: FixedSizeQueue::new(IMPORT_HISTORY_SIZE),
+            finished: FixedSizeQueue::new(IMPORT_HISTORY_SIZE),
+        }
+    }
+}
+
+impl ImportRegistry {
+    pub fn register(&mut self, import_id: u32) {
+        let cancelled = self.early_cancels.iter().any(|id| *id == import_id);
+        self.early_cancels.retain(|id| *id != import_id);
+        self.finished.retain(|id| *id != import_id);
+        self.running.insert(import_id, cancelled);
+    }
+
+    pub fn is_cancelled(&self, import_id: u32) -> bool {
+        self.running.get(&import_id).copied().unwrap_or(false)
+    }
+
+    pub fn finish(&mut self, import_id: u32) {
+        if self.running.remove(&import_id).is_some() {
+            self.finished.push(import_id);
+        }
+    }
+
+    pub fn cancel(&mut self, import_id: u32) {
+        if let Some(cancelled) = self.running.get_mut(&import_id) {
+            *cancelled = true;
+        } else if !self.finished.iter().any(|id| *id == import_id) && !self.early_cancels.iter().any(|id| *id == import_id) {
+            self.early_cancels.push(import_id);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_while_running() {
+        let mut imports = ImportRegistry::default();
+        imports.register(1);
+        imports.register(2);
+        imports.cancel(1);
+        assert!(imports.is_cancelled(1));
+        assert!(!imports.is_cancelled(2));
+    }
+
+    #[test]
+    fn cancel_before_register() {
+        let mut imports = ImportRegistry::default();
+        imports.cancel(3);
+        imports.register(3);
+        assert!(imports.is_cancelled(3));
+        //Taken by the import, so the id is free again once it finishes.
+        imports.finish(3);
+        imports.register(3);
+        assert!(!imports.is_cancelled(3));
+    }
+
+    #[test]
+    fn cancel_after_finish() {
+        let mut imports = ImportRegistry::default();
+        imports.register(4);
+        imports.finish(4);
+        imports.cancel(4);
+        //An unrelated import reusing the id, after a reload for example, isn't cancelled.
+        imports.register(4);
+        assert!(!imports.is_cancelled(4));
+        assert_eq!((imports.running.len(), imports.early_cancels.len()), (1, 0));
+    }
+
+    #[test]
+    fn stale_cancels_are_bounded() {
+        let mut imports = ImportRegistry::default();
+        (0..1000).for_each(|id| imports.cancel(id));
+        assert_eq!(imports.early_cancels.len(), IMPORT_HISTORY_SIZE);
+        imports.register(0);
+        assert!(!imports.is_cancelled(0));
+        imports.register(999);
+        assert!(imports.is_cancelled(999));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::solar_data::{expression::{DerivedChannel, DerivedChannelError}, clear_sky::{SolarSite, SolarSiteError}, alerts::{AlertRule, AlertRuleError}};
+
+
+//Everything the user has configured that should survive a restart.
+//Missing fields fall back to their defaults, so older settings files still load.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub derived_channels: Vec<DerivedChannel>,
+    //The rated capacity of the battery bank, used to turn capacity estimates into state of health.
+    pub nominal_capacity_ah: Option<f64>,
+    //Location and panels, used to model the expected clear sky production. See solar_data::clear_sky.
+    pub solar_site: Option<SolarSite>,
+    //Checked against every import, see solar_data::alerts.
+    pub alert_rules: Vec<AlertRule>,
+}
+
+impl Settings {
+    //Adds a new derived channel, or replaces the one with the same name if replace is set.
+    pub fn save_derived_channel(&mut self, derived_channel: DerivedChannel, replace: bool) -> Result<(), SettingsError> {
+        derived_channel.validate()?;
+        match self.derived_channels.iter_mut().find(|existing| existing.name == derived_channel.name) {
+            Some(existing) => {
+                if !replace {
+                    return Err(DerivedChannelError::DuplicateName(derived_channel.name).into())
+                }
+                *existing = derived_channel;
+            },
+            None => self.derived_channels.push(derived_channel),
+        }
+        Ok(())
+    }
+
+    pub fn remove_derived_channel(&mut self, name: &str) -> Result<(), SettingsError> {
+        let index = self.derived_channels.iter().position(|existing| existing.name == name)
+            .ok_or_else(|| DerivedChannelError::NotFound(name.to_owned()))?;
+        self.derived_channels.remove(index);
+        Ok(())
+    }
+
+    //Adds a new alert rule, or replaces the one with the same name if replace is set.
+    pub fn save_alert_rule(&mut self, alert_rule: AlertRule, replace: bool) -> Result<(), SettingsError> {
+        alert_rule.validate()?;
+        match self.alert_rules.iter_mut().find(|existing| existing.name == alert_rule.name) {
+            Some(existing) => {
+                if !replace {
+                    return Err(AlertRuleError::DuplicateName(alert_rule.name).into())
+                }
+                *existing = alert_rule;
+            },
+            None => self.alert_rules.push(alert_rule),
+        }
+        Ok(())
+    }
+
+    pub fn remove_alert_rule(&mut self, name: &str) -> Result<(), SettingsError> {
+        let index = self.alert_rules.iter().position(|existing| existing.name == name)
+            .ok_or_else(|| AlertRuleError::NotFound(name.to_owned()))?;
+        self.alert_rules.remove(index);
+        Ok(())
+    }
+
+    pub fn set_nominal_capacity(&mut self, nominal_capacity_ah: Option<f64>) -> Result<(), SettingsError> {
+        if let Some(capacity) = nominal_capacity_ah {
+            if !capacity.is_finite() || capacity <= 0f64 {
+                return Err(SettingsError::InvalidNominalCapacity(capacity))
+            }
+        }
+        self.nominal_capacity_ah = nominal_capacity_ah;
+        Ok(())
+    }
+
+    pub fn set_solar_site(&mut self, solar_site: Option<SolarSite>) -> Result<(), SettingsError> {
+        if let Some(site) = &solar_site {
+            site.validate()?;
+        }
+        self.solar_site = solar_site;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum SettingsError {
+    #[error("Nominal capacity must be a positive number of amp hours, found {0}.")]
+    InvalidNominalCapacity(f64),
+    #[error(transparent)]
+    DerivedChannel(#[from] DerivedChannelError),
+    #[error(transparent)]
+    SolarSite(#[from] SolarSiteError),
+    #[error(transparent)]
+    AlertRule(#[from] AlertRuleError),
+}
\ No newline at end of file
@@ -2,4 +2,14 @@ pub mod storage;
 pub mod line;
 pub mod value;
 pub mod controllers;
-pub mod cell;
\ No newline at end of file
+pub mod cell;
+pub mod energy;
+pub mod summary;
+pub mod expression;
+pub mod cell_balance;
+pub mod capacity;
+pub mod cycles;
+pub mod clear_sky;
+pub mod alerts;
+pub mod anomalies;
+pub mod charge_stage;
\ No newline at end of file
@@ -0,0 +1,214 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use time::{OffsetDateTime, macros::format_description};
+
+use crate::solar_data::{energy::MAX_INTEGRATION_GAP, expression::{Expression, ExpressionError}, line::DataLine};
+
+
+//A threshold on an expression over the channels, the same expressions derived channels use. For example
+// "max(CellVoltage[*])" above 3.65 for 30 seconds, or "StateOfChargePercent" below 20.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct AlertRule {
+    pub name: String,
+    pub expression: String,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    //How long the threshold has to be crossed before the rule fires. Zero fires on a single line.
+    pub duration_seconds: u32,
+    pub severity: AlertSeverity,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum AlertComparison {
+    Above,
+    Below,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+//One stretch of time a rule was firing for.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct AlertEvent {
+    pub rule_name: String,
+    pub severity: AlertSeverity,
+    pub start: i64,
+    pub end: i64,
+    //The highest value for rules that fire above the threshold, the lowest for rules that fire below it.
+    pub extreme_value: f64,
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum AlertRuleError {
+    #[error("Alert rules need a name.")]
+    EmptyName,
+    #[error("An alert rule named {0} already exists.")]
+    DuplicateName(String),
+    #[error("No alert rule named {0} exists.")]
+    NotFound(String),
+    #[error("Alert thresholds must be a number, found {0}.")]
+    InvalidThreshold(f64),
+    #[error(transparent)]
+    InvalidExpression(#[from] ExpressionError),
+}
+
+impl AlertComparison {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            AlertComparison::Above => "Above",
+            AlertComparison::Below => "Below",
+        }
+    }
+
+    fn is_crossed(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            AlertComparison::Above => value > threshold,
+            AlertComparison::Below => value < threshold,
+        }
+    }
+
+    fn get_extreme(&self, current: f64, value: f64) -> f64 {
+        match self {
+            AlertComparison::Above => current.max(value),
+            AlertComparison::Below => current.min(value),
+        }
+    }
+}
+
+impl AlertSeverity {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            AlertSeverity::Info => "Info",
+            AlertSeverity::Warning => "Warning",
+            AlertSeverity::Critical => "Critical",
+        }
+    }
+}
+
+impl AlertRule {
+    //Checks everything that can be wrong with a rule before it is saved.
+    pub fn validate(&self) -> Result<Expression, AlertRuleError> {
+        if self.name.trim().is_empty() {
+            return Err(AlertRuleError::EmptyName)
+        }
+        if !self.threshold.is_finite() {
+            return Err(AlertRuleError::InvalidThreshold(self.threshold))
+        }
+        Ok(Expression::parse(&self.expression)?)
+    }
+
+    //Lines where the expression can't be evaluated are skipped. A gap in the data ends the current event, since nothing is known about
+    // what happened during it.
+    pub fn evaluate(&self, data: &[DataLine]) -> Result<Vec<AlertEvent>, AlertRuleError> {
+        let expression = self.validate()?;
+        let mut events = Vec::new();
+        //(start, last time crossed, extreme value) of the current run of lines over the threshold.
+        let mut run: Option<(i64, i64, f64)> = None;
+        for line in data {
+            let value = match expression.evaluate(line) {
+                Some(value) => value,
+                None => continue,
+            };
+            if let Some((_, last, _)) = run {
+                if line.unix_time - last > MAX_INTEGRATION_GAP {
+                    self.close_run(run.take(), &mut events);
+                }
+            }
+            if self.comparison.is_crossed(value, self.threshold) {
+                run = Some(match run {
+                    Some((start, _, extreme)) => (start, line.unix_time, self.comparison.get_extreme(extreme, value)),
+                    None => (line.unix_time, line.unix_time, value),
+                });
+            } else {
+                self.close_run(run.take(), &mut events);
+            }
+        }
+        self.close_run(run, &mut events);
+        Ok(events)
+    }
+
+    fn close_run(&self, run: Option<(i64, i64, f64)>, events: &mut Vec<AlertEvent>) {
+        if let Some((start, end, extreme_value)) = run {
+            if end - start >= self.duration_seconds as i64 {
+                events.push(AlertEvent { rule_name: self.name.clone(), severity: self.severity, start, end, extreme_value });
+            }
+        }
+    }
+}
+
+impl AlertEvent {
+    //The text of the notification for the event.
+    pub fn get_message(&self) -> String {
+        let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+        let start = OffsetDateTime::from_unix_timestamp(self.start).ok()
+            .and_then(|date_time| date_time.format(format).ok())
+            .unwrap_or_else(|| self.start.to_string());
+        format!("{}: {} at {} for {}s, reaching {:.3}", self.severity.get_name(), self.rule_name, start, self.end - self.start, self.extreme_value)
+    }
+}
+
+//Every event of every rule, in time order. Invalid rules can't be saved, but any that do turn up are skipped rather than hiding the rest.
+pub fn evaluate_alert_rules(rules: &[AlertRule], data: &[DataLine]) -> Vec<AlertEvent> {
+    let mut events = rules.iter()
+        .filter_map(|rule| rule.evaluate(data).ok())
+        .flatten()
+        .collect::<Vec<_>>();
+    events.sort_by(|a, b| a.start.cmp(&b.start).then(b.severity.cmp(&a.severity)));
+    events
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::solar_data::value::DataValue;
+
+    use super::*;
+
+    fn get_lines(values: &[(i64, f32)]) -> Vec<DataLine> {
+        values.iter().map(|(unix_time, state_of_charge)| {
+            DataLine { unix_time: *unix_time, line: vec![DataValue::StateOfChargePercent(*state_of_charge)] }
+        }).collect()
+    }
+
+    fn get_rule(duration_seconds: u32) -> AlertRule {
+        AlertRule {
+            name: "Low".to_owned(),
+            expression: "StateOfChargePercent".to_owned(),
+            comparison: AlertComparison::Below,
+            threshold: 20.0,
+            duration_seconds,
+            severity: AlertSeverity::Warning,
+        }
+    }
+
+    #[test]
+    fn duration_must_be_held() {
+        let data = get_lines(&[(0, 25.0), (10, 19.0), (20, 15.0), (30, 18.0), (40, 22.0), (50, 10.0), (60, 30.0)]);
+        let events = get_rule(20).evaluate(&data).unwrap();
+        //The single line at 50 is below the threshold, but not for long enough.
+        assert_eq!(events, vec![AlertEvent { rule_name: "Low".to_owned(), severity: AlertSeverity::Warning, start: 10, end: 30, extreme_value: 15.0 }]);
+        assert_eq!(get_rule(0).evaluate(&data).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn gaps_end_events() {
+        let data = get_lines(&[(0, 10.0), (60, 10.0), (60 + MAX_INTEGRATION_GAP + 1, 10.0), (60 + MAX_INTEGRATION_GAP + 61, 10.0)]);
+        let events = get_rule(60).evaluate(&data).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].start, 60 + MAX_INTEGRATION_GAP + 1);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let mut rule = get_rule(0);
+        rule.expression = "Nonsense".to_owned();
+        assert!(matches!(rule.validate(), Err(AlertRuleError::InvalidExpression(_))));
+        rule.name = " ".to_owned();
+        assert_eq!(rule.validate(), Err(AlertRuleError::EmptyName));
+        assert!(evaluate_alert_rules(&[rule], &get_lines(&[(0, 10.0)])).is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+use serde::{Serialize, Deserialize};
+
+use crate::solar_data::{cell_balance::calculate_cell_deviation, cell_balance::get_cell_voltages, energy::MAX_INTEGRATION_GAP, line::DataLine, storage::DataStorage, value::DataValue};
+
+
+//How many robust standard deviations away from usual a value has to be before it is flagged.
+const ANOMALY_SCORE_THRESHOLD: f64 = 4.0;
+//Baselines from fewer samples than this are too noisy to judge anything against.
+const MIN_BASELINE_SAMPLES: usize = 30;
+//Scales MAD up to a standard deviation for normally distributed data.
+const MAD_TO_STANDARD_DEVIATION: f64 = 1.4826;
+//Controllers producing less than this in total are idle, and their shares mean nothing.
+const MIN_CONTROLLER_TOTAL_AMPS: f64 = 1.0;
+//Floors on the spread of each baseline. Very steady data would otherwise flag differences too small to matter.
+const MIN_CONTROLLER_SHARE_SPREAD: f64 = 0.05;
+const MIN_CELL_OFFSET_SPREAD: f64 = 0.005;
+const MIN_LOAD_SPREAD_WATTS: f64 = 25.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum AnomalyKind {
+    //The controller's share of the combined controller output, compared to its usual share.
+    ControllerDivergence { controller: u16 },
+    //The cell's voltage minus the pack mean, compared to its usual offset.
+    CellOffset { cell: u16 },
+    //Load compared to what is usual for that hour of the day. Only load above the baseline is flagged.
+    LoadAboveBaseline,
+}
+
+//One stretch of time a channel was behaving unusually for.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub start: i64,
+    pub end: i64,
+    //The value and its baseline at the most unusual line of the stretch.
+    pub value: f64,
+    pub expected: f64,
+    //How many robust standard deviations the most unusual line was away from the baseline.
+    pub score: f64,
+}
+
+impl AnomalyKind {
+    pub fn get_name(&self) -> String {
+        match self {
+            AnomalyKind::ControllerDivergence { controller } => format!("Controller {} Output Share", controller),
+            AnomalyKind::CellOffset { cell } => format!("Cell {} Offset", cell),
+            AnomalyKind::LoadAboveBaseline => "Load Above Baseline".to_owned(),
+        }
+    }
+
+    pub fn get_unit(&self) -> &'static str {
+        match self {
+            AnomalyKind::ControllerDivergence { .. } => "%",
+            AnomalyKind::CellOffset { .. } => "mV",
+            AnomalyKind::LoadAboveBaseline => "W",
+        }
+    }
+
+    //Values are stored in the units they are calculated in, this scales them to the units shown.
+    pub fn get_display_scale(&self) -> f64 {
+        match self {
+            AnomalyKind::ControllerDivergence { .. } => 100.0,
+            AnomalyKind::CellOffset { .. } => 1000.0,
+            AnomalyKind::LoadAboveBaseline => 1.0,
+        }
+    }
+}
+
+//The usual value of a channel, as a median and a robust spread.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Baseline {
+    median: f64,
+    spread: f64,
+}
+
+impl Baseline {
+    fn from_values(values: &[f64], min_spread: f64) -> Option<Self> {
+        if values.len() < MIN_BASELINE_SAMPLES {
+            return None
+        }
+        let median = get_median(values)?;
+        let deviations = values.iter().map(|value| (value - median).abs()).collect::<Vec<_>>();
+        let spread = (get_median(&deviations)? * MAD_TO_STANDARD_DEVIATION).max(min_spread);
+        Some(Self { median, spread })
+    }
+
+    fn get_score(&self, value: f64) -> f64 {
+        (value - self.median) / self.spread
+    }
+}
+
+fn get_median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    match sorted.len() % 2 {
+        0 => Some((sorted[middle - 1] + sorted[middle]) / 2.0),
+        _ => Some(sorted[middle]),
+    }
+}
+
+//Each controller's output as a fraction of the mean controller output. Needs at least two producing controllers to compare.
+fn get_controller_shares(line: &DataLine) -> Vec<(u16, f64)> {
+    let amps = line.line.iter().filter_map(|value| {
+        match value {
+            DataValue::ControllerAmps { controller, amps } => Some((*controller, *amps as f64)),
+            _ => None,
+        }
+    }).collect::<Vec<_>>();
+    let total = amps.iter().map(|(_, amps)| amps).sum::<f64>();
+    if amps.len() < 2 || total < MIN_CONTROLLER_TOTAL_AMPS {
+        return Vec::new()
+    }
+    let mean = total / amps.len() as f64;
+    amps.into_iter().map(|(controller, amps)| (controller, amps / mean)).collect()
+}
+
+fn get_load_watts(line: &DataLine) -> Option<f64> {
+    line.line.iter().find_map(|value| {
+        match value {
+            DataValue::LoadWatts(watts) => Some(*watts as f64),
+            _ => None,
+        }
+    })
+}
+
+//Timestamps are local time stored as if UTC, so this is the local hour.
+fn get_hour_of_day(unix_time: i64) -> usize {
+    (unix_time.rem_euclid(86400) / 3600) as usize
+}
+
+//Groups flagged lines into anomalies. A line that isn't flagged, or a gap in the data, ends the current anomaly.
+struct AnomalyBuilder {
+    kind: AnomalyKind,
+    current: Option<Anomaly>,
+    last_time: Option<i64>,
+    anomalies: Vec<Anomaly>,
+}
+
+impl AnomalyBuilder {
+    fn new(kind: AnomalyKind) -> Self {
+        Self { kind, current: None, last_time: None, anomalies: Vec::new() }
+    }
+
+    fn push(&mut self, unix_time: i64, value: f64, baseline: &Baseline, flagged: bool) {
+        if self.last_time.is_some_and(|last_time| unix_time - last_time > MAX_INTEGRATION_GAP) {
+            self.close();
+        }
+        self.last_time = Some(unix_time);
+        if !flagged {
+            self.close();
+            return
+        }
+        let score = baseline.get_score(value);
+        match &mut self.current {
+            Some(anomaly) => {
+                anomaly.end = unix_time;
+                if score.abs() > anomaly.score.abs() {
+                    anomaly.value = value;
+                    anomaly.expected = baseline.median;
+                    anomaly.score = score;
+                }
+            },
+            None => {
+                self.current = Some(Anomaly { kind: self.kind, start: unix_time, end: unix_time, value, expected: baseline.median, score });
+            },
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(anomaly) = self.current.take() {
+            self.anomalies.push(anomaly);
+        }
+    }
+
+    fn finish(mut self) -> Vec<Anomaly> {
+        self.close();
+        self.anomalies
+    }
+}
+
+//Inserts into a list of (id, values) kept sorted by id, the same way the cell balance summary does.
+fn push_by_id<T>(list: &mut Vec<(u16, Vec<T>)>, id: u16, value: T) {
+    match list.binary_search_by_key(&id, |(list_id, _)| *list_id) {
+        Ok(index) => list[index].1.push(value),
+        Err(index) => list.insert(index, (id, vec![value])),
+    }
+}
+
+fn detect_controller_divergence(data: &[DataLine]) -> Vec<Anomaly> {
+    let mut shares: Vec<(u16, Vec<(i64, f64)>)> = Vec::new();
+    data.iter().for_each(|line| {
+        get_controller_shares(line).into_iter().for_each(|(controller, share)| push_by_id(&mut shares, controller, (line.unix_time, share)));
+    });
+    shares.into_iter().flat_map(|(controller, shares)| {
+        let values = shares.iter().map(|(_, share)| *share).collect::<Vec<_>>();
+        let baseline = match Baseline::from_values(&values, MIN_CONTROLLER_SHARE_SPREAD) {
+            Some(baseline) => baseline,
+            None => return Vec::new(),
+        };
+        let mut builder = AnomalyBuilder::new(AnomalyKind::ControllerDivergence { controller });
+        shares.into_iter().for_each(|(unix_time, share)| {
+            builder.push(unix_time, share, &baseline, baseline.get_score(share).abs() > ANOMALY_SCORE_THRESHOLD);
+        });
+        builder.finish()
+    }).collect()
+}
+
+fn detect_cell_offsets(data: &[DataLine]) -> Vec<Anomaly> {
+    let mut offsets: Vec<(u16, Vec<(i64, f64)>)> = Vec::new();
+    data.iter().for_each(|line| {
+        get_cell_voltages(line).into_iter().for_each(|(cell, _)| {
+            if let Some(deviation) = calculate_cell_deviation(line, cell) {
+                push_by_id(&mut offsets, cell, (line.unix_time, deviation));
+            }
+        });
+    });
+    offsets.into_iter().flat_map(|(cell, offsets)| {
+        let values = offsets.iter().map(|(_, offset)| *offset).collect::<Vec<_>>();
+        let baseline = match Baseline::from_values(&values, MIN_CELL_OFFSET_SPREAD) {
+            Some(baseline) => baseline,
+            None => return Vec::new(),
+        };
+        let mut builder = AnomalyBuilder::new(AnomalyKind::CellOffset { cell });
+        offsets.into_iter().for_each(|(unix_time, offset)| {
+            builder.push(unix_time, offset, &baseline, baseline.get_score(offset).abs() > ANOMALY_SCORE_THRESHOLD);
+        });
+        builder.finish()
+    }).collect()
+}
+
+fn detect_load_above_baseline(data: &[DataLine]) -> Vec<Anomaly> {
+    let loads = data.iter().filter_map(|line| Some((line.unix_time, get_load_watts(line)?))).collect::<Vec<_>>();
+    let mut hours: Vec<Vec<f64>> = vec![Vec::new(); 24];
+    loads.iter().for_each(|(unix_time, watts)| hours[get_hour_of_day(*unix_time)].push(*watts));
+    let baselines = hours.iter().map(|values| Baseline::from_values(values, MIN_LOAD_SPREAD_WATTS)).collect::<Vec<_>>();
+    let mut builder = AnomalyBuilder::new(AnomalyKind::LoadAboveBaseline);
+    loads.into_iter().for_each(|(unix_time, watts)| {
+        //Hours without enough data to know what is usual are left alone.
+        if let Some(baseline) = &baselines[get_hour_of_day(unix_time)] {
+            builder.push(unix_time, watts, baseline, baseline.get_score(watts) > ANOMALY_SCORE_THRESHOLD);
+        }
+    });
+    builder.finish()
+}
+
+//Every anomaly found in the data, in time order. Baselines are taken from all of the data, so what counts as unusual depends on what
+// has been imported.
+pub fn detect_anomalies(storage: &DataStorage) -> Vec<Anomaly> {
+    let data = &storage.data;
+    let mut anomalies = detect_controller_divergence(data);
+    anomalies.extend(detect_cell_offsets(data));
+    anomalies.extend(detect_load_above_baseline(data));
+    anomalies.sort_by(|a, b| a.start.cmp(&b.start).then(b.score.abs().total_cmp(&a.score.abs())));
+    anomalies
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_storage(lines: impl Iterator<Item = (i64, Vec<DataValue>)>) -> DataStorage {
+        DataStorage { data: lines.map(|(unix_time, line)| DataLine { unix_time, line }).collect() }
+    }
+
+    #[test]
+    fn controller_divergence() {
+        //Two controllers sharing evenly, until controller 2 drops out for three lines.
+        let storage = get_storage((0..100).map(|index| {
+            let amps = if (50..53).contains(&index) { 2.0 } else { 10.0 + (index % 3) as f32 * 0.1 };
+            (index * 60, vec![DataValue::ControllerAmps { controller: 1, amps: 10.0 }, DataValue::ControllerAmps { controller: 2, amps }])
+        }));
+        let anomalies = detect_anomalies(&storage);
+        //Both controllers' shares move, but only for the same three lines.
+        assert_eq!(anomalies.len(), 2);
+        let controller_2 = anomalies.iter().find(|anomaly| anomaly.kind == AnomalyKind::ControllerDivergence { controller: 2 }).unwrap();
+        assert_eq!((controller_2.start, controller_2.end), (50 * 60, 52 * 60));
+        assert!(controller_2.score < -ANOMALY_SCORE_THRESHOLD);
+    }
+
+    #[test]
+    fn cell_offset() {
+        let storage = get_storage((0..100).map(|index| {
+            let voltage = if index == 70 { 3.25 } else { 3.30 };
+            (index * 60, vec![
+                DataValue::CellVoltage { cell: 1, voltage: 3.30 },
+                DataValue::CellVoltage { cell: 2, voltage: 3.30 },
+                DataValue::CellVoltage { cell: 3, voltage },
+            ])
+        }));
+        let anomalies = detect_anomalies(&storage);
+        assert!(anomalies.iter().all(|anomaly| anomaly.start == 70 * 60 && anomaly.end == 70 * 60));
+        assert!(anomalies.iter().any(|anomaly| anomaly.kind == AnomalyKind::CellOffset { cell: 3 }));
+    }
+
+    #[test]
+    fn load_uses_time_of_day() {
+        //Load is always high in the evening, and once high in the morning. Only the morning is unusual.
+        let storage = get_storage((0..40).flat_map(|day| {
+            let morning = if day == 20 { 2000.0 } else { 200.0 + (day % 4) as f32 * 10.0 };
+            [
+                (day * 86400 + 8 * 3600, vec![DataValue::LoadWatts(morning)]),
+                (day * 86400 + 19 * 3600, vec![DataValue::LoadWatts(2000.0 + (day % 4) as f32 * 10.0)]),
+            ]
+        }));
+        let anomalies = detect_anomalies(&storage);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::LoadAboveBaseline);
+        assert_eq!(anomalies[0].start, 20 * 86400 + 8 * 3600);
+    }
+}
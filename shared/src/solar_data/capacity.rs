@@ -0,0 +1,179 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{graph::{graph_aggregation::get_month_start, graph_axis::AxisDataType}, solar_data::{energy::MAX_INTEGRATION_GAP, line::DataLine, value::DataValue}};
+
+
+//State of charge has to move at least this far before the charge counted in between is trusted as a capacity estimate.
+//Smaller swings are dominated by the rounding of the state of charge reading.
+pub const MIN_STATE_OF_CHARGE_SWING: f64 = 20f64;
+
+//One capacity estimate, from the charge counted while the state of charge moved between two points.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CapacityEstimate {
+    pub start: i64,
+    pub end: i64,
+    pub capacity_ah: f64,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct MonthlyCapacity {
+    //Unix time of the start of the month.
+    pub month: i64,
+    //Median of the estimates that ended in the month.
+    pub capacity_ah: f64,
+    pub estimate_count: usize,
+    //Only known once a nominal capacity has been entered.
+    pub state_of_health_percent: Option<f64>,
+}
+
+//Coulomb counts the battery current between state of charge readings.
+//Current is integrated with the trapezoidal rule. Where current is missing, the change in amp hours since midnight is used instead.
+//Any gap in the data restarts the count.
+pub fn estimate_capacity(data: &[DataLine]) -> Vec<CapacityEstimate> {
+    let mut estimates = Vec::new();
+    //(time, state of charge, counted amp hours) at the start of the current estimate.
+    let mut anchor: Option<(i64, f64, f64)> = None;
+    let mut amp_hours = 0f64;
+    let mut previous: Option<&DataLine> = None;
+    for line in data {
+        if let Some(previous_line) = previous {
+            match get_counted_amp_hours(previous_line, line) {
+                Some(counted) => amp_hours += counted,
+                None => anchor = None,
+            }
+        }
+        previous = Some(line);
+
+        let state_of_charge = match line.calculate_axis_data(AxisDataType::StateOfChargePercent) {
+            Some(state_of_charge) => state_of_charge,
+            None => continue,
+        };
+        match anchor {
+            None => anchor = Some((line.unix_time, state_of_charge, amp_hours)),
+            Some((anchor_time, anchor_state_of_charge, anchor_amp_hours)) => {
+                let state_of_charge_change = state_of_charge - anchor_state_of_charge;
+                if state_of_charge_change.abs() < MIN_STATE_OF_CHARGE_SWING {
+                    continue;
+                }
+                let amp_hour_change = amp_hours - anchor_amp_hours;
+                //Charge and state of charge moving in opposite directions means one of the readings is off, so there's no estimate.
+                if amp_hour_change * state_of_charge_change > 0f64 {
+                    estimates.push(CapacityEstimate {
+                        start: anchor_time,
+                        end: line.unix_time,
+                        capacity_ah: amp_hour_change / state_of_charge_change * 100f64,
+                    });
+                }
+                anchor = Some((line.unix_time, state_of_charge, amp_hours));
+            },
+        }
+    }
+    estimates
+}
+
+//Groups the estimates by the month they ended in, in ascending month order.
+pub fn get_monthly_capacity(estimates: &[CapacityEstimate], nominal_capacity_ah: Option<f64>) -> Vec<MonthlyCapacity> {
+    let mut months: Vec<(i64, Vec<f64>)> = Vec::new();
+    estimates.iter().for_each(|estimate| {
+        let month = match get_month_start(estimate.end) {
+            Some(month) => month,
+            None => return,
+        };
+        match months.binary_search_by_key(&month, |(start, _)| *start) {
+            Ok(index) => months[index].1.push(estimate.capacity_ah),
+            Err(index) => months.insert(index, (month, vec![estimate.capacity_ah])),
+        }
+    });
+    months.into_iter().map(|(month, mut capacities)| {
+        capacities.sort_by(|a, b| a.total_cmp(b));
+        let middle = capacities.len() / 2;
+        let capacity_ah = if capacities.len() % 2 == 0 {
+            (capacities[middle - 1] + capacities[middle]) / 2f64
+        } else {
+            capacities[middle]
+        };
+        MonthlyCapacity {
+            month,
+            capacity_ah,
+            estimate_count: capacities.len(),
+            state_of_health_percent: nominal_capacity_ah
+                .filter(|nominal| *nominal > 0f64)
+                .map(|nominal| capacity_ah / nominal * 100f64),
+        }
+    }).collect()
+}
+
+//Amp hours into the battery between two lines, or None if they can't be counted.
+fn get_counted_amp_hours(previous: &DataLine, current: &DataLine) -> Option<f64> {
+    let duration = current.unix_time - previous.unix_time;
+    if duration <= 0 || duration > MAX_INTEGRATION_GAP {
+        return None
+    }
+    if let (Some(previous_amps), Some(amps)) = (previous.calculate_axis_data(AxisDataType::BatteryAmps), current.calculate_axis_data(AxisDataType::BatteryAmps)) {
+        return Some((previous_amps + amps) / 2f64 * duration as f64 / 3600f64)
+    }
+    //The amp hour counter resets at midnight, so it can only be compared within a day.
+    if previous.unix_time.div_euclid(86400) != current.unix_time.div_euclid(86400) {
+        return None
+    }
+    match (previous.search_data(&DataValue::AmpHoursSinceMidnight(0.0)), current.search_data(&DataValue::AmpHoursSinceMidnight(0.0))) {
+        (Some(DataValue::AmpHoursSinceMidnight(previous_amp_hours)), Some(DataValue::AmpHoursSinceMidnight(amp_hours))) => {
+            Some((amp_hours - previous_amp_hours) as f64)
+        },
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    //One line every three minutes. At 20 A, a 100 Ah battery moves 1% per line.
+    fn get_lines(start: i64, values: impl Iterator<Item = (f32, DataValue)>) -> Vec<DataLine> {
+        values.enumerate().map(|(index, (state_of_charge, value))| {
+            let mut line = DataLine::from(start + index as i64 * 180);
+            line.add_data(DataValue::StateOfChargePercent(state_of_charge));
+            line.add_data(value);
+            line
+        }).collect()
+    }
+
+    #[test]
+    fn discharge_and_charge_estimates() {
+        let march = datetime!(2023-03-10 0:00).assume_utc().unix_timestamp();
+        let discharge = get_lines(march, (0..=30).map(|index| (90.0 - index as f32, DataValue::BatteryAmps(-20.0))));
+        //The estimate is made as soon as the swing is large enough, the last 10% don't reach it again.
+        assert_eq!(estimate_capacity(&discharge), vec![CapacityEstimate { start: march, end: march + 20 * 180, capacity_ah: 100.0 }]);
+        //Without current, the amp hour counter is used instead.
+        let charge = get_lines(march + 3600, (0..=20).map(|index| (50.0 + index as f32, DataValue::AmpHoursSinceMidnight(index as f32 * 0.9))));
+        assert_eq!(estimate_capacity(&charge)[0].capacity_ah, 90.0);
+    }
+
+    #[test]
+    fn gaps_restart_the_count() {
+        let mut lines = get_lines(0, (0..=20).map(|index| (90.0 - index as f32, DataValue::BatteryAmps(-20.0))));
+        lines[10..].iter_mut().for_each(|line| line.unix_time += MAX_INTEGRATION_GAP);
+        assert_eq!(estimate_capacity(&lines), Vec::new());
+    }
+
+    #[test]
+    fn monthly_capacity_and_state_of_health() {
+        let march = datetime!(2023-03-01 0:00).assume_utc().unix_timestamp();
+        let april = datetime!(2023-04-01 0:00).assume_utc().unix_timestamp();
+        let mut data = get_lines(march + 86400, (0..=20).map(|index| (90.0 - index as f32, DataValue::BatteryAmps(-20.0))));
+        data.extend(get_lines(march + 2 * 86400, (0..=20).map(|index| (90.0 - index as f32, DataValue::BatteryAmps(-18.0)))));
+        //April only swings 10%, too little for an estimate.
+        data.extend(get_lines(april + 86400, (0..=10).map(|index| (90.0 - index as f32, DataValue::BatteryAmps(-20.0)))));
+        let estimates = estimate_capacity(&data);
+        let months = get_monthly_capacity(&estimates, Some(125.0));
+        assert_eq!(months.len(), 1);
+        assert_eq!((months[0].month, months[0].estimate_count), (march, 2));
+        assert!((months[0].capacity_ah - 95.0).abs() < 1e-9);
+        assert!((months[0].state_of_health_percent.unwrap() - 76.0).abs() < 1e-9);
+        assert_eq!(months.iter().find(|month| month.month == april), None);
+        assert_eq!(get_monthly_capacity(&estimates, None)[0].state_of_health_percent, None);
+        assert_eq!(get_monthly_capacity(&estimates, Some(0.0))[0].state_of_health_percent, None);
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Serialize, Deserialize};
+
+use crate::solar_data::{line::DataLine, value::DataValue};
+
+
+//How balanced the cells are at one moment.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CellBalance {
+    //Highest cell voltage minus lowest cell voltage.
+    pub spread: f64,
+    pub mean: f64,
+    pub highest_cell: u16,
+    pub lowest_cell: u16,
+}
+
+//One row of the cell balance summary, one per cell.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CellBalanceRow {
+    pub cell: u16,
+    pub times_highest: u32,
+    pub times_lowest: u32,
+    //Share of all balanced samples in which this cell was the highest or the lowest.
+    pub outlier_percent: f64,
+    //Average of the cell voltage minus the pack mean, in millivolts.
+    pub average_deviation_mv: f64,
+    //How fast the deviation from the pack mean is changing, in millivolts per 30 days. A cell drifting away from the pack shows up here first.
+    pub drift_mv_per_30_days: Option<f64>,
+}
+
+pub fn get_cell_voltages(line: &DataLine) -> Vec<(u16, f64)> {
+    line.line.iter().filter_map(|value| {
+        match value {
+            DataValue::CellVoltage { cell, voltage } => Some((*cell, *voltage as f64)),
+            _ => None,
+        }
+    }).collect()
+}
+
+//Balance needs at least two cells to compare.
+pub fn calculate_cell_balance(line: &DataLine) -> Option<CellBalance> {
+    let voltages = get_cell_voltages(line);
+    if voltages.len() < 2 {
+        return None
+    }
+    let highest = voltages.iter().cloned().reduce(|a, b| if b.1 > a.1 { b } else { a })?;
+    let lowest = voltages.iter().cloned().reduce(|a, b| if b.1 < a.1 { b } else { a })?;
+    Some(CellBalance {
+        spread: highest.1 - lowest.1,
+        mean: voltages.iter().map(|(_, voltage)| voltage).sum::<f64>() / voltages.len() as f64,
+        highest_cell: highest.0,
+        lowest_cell: lowest.0,
+    })
+}
+
+//The cell's voltage minus the mean of all cells in the same line.
+pub fn calculate_cell_deviation(line: &DataLine, cell: u16) -> Option<f64> {
+    let balance = calculate_cell_balance(line)?;
+    let voltage = get_cell_voltages(line).into_iter().find(|(id, _)| *id == cell)?.1;
+    Some(voltage - balance.mean)
+}
+
+//Ranks the cells by how often they are the outlier, most often first.
+pub fn get_cell_balance_summary(data: &[DataLine]) -> Vec<CellBalanceRow> {
+    struct CellStatistics {
+        times_highest: u32,
+        times_lowest: u32,
+        deviations: Vec<(i64, f64)>,
+    }
+
+    let mut statistics: Vec<(u16, CellStatistics)> = Vec::new();
+    let mut balanced_samples = 0u32;
+    data.iter().for_each(|line| {
+        let balance = match calculate_cell_balance(line) {
+            Some(balance) => balance,
+            None => return,
+        };
+        balanced_samples += 1;
+        get_cell_voltages(line).into_iter().for_each(|(cell, voltage)| {
+            let index = match statistics.binary_search_by_key(&cell, |(id, _)| *id) {
+                Ok(index) => index,
+                Err(index) => {
+                    statistics.insert(index, (cell, CellStatistics { times_highest: 0, times_lowest: 0, deviations: Vec::new() }));
+                    index
+                },
+            };
+            let cell_statistics = &mut statistics[index].1;
+            if cell == balance.highest_cell {
+                cell_statistics.times_highest += 1;
+            }
+            if cell == balance.lowest_cell {
+                cell_statistics.times_lowest += 1;
+            }
+            cell_statistics.deviations.push((line.unix_time, voltage - balance.mean));
+        });
+    });
+
+    let mut rows = statistics.into_iter().map(|(cell, cell_statistics)| {
+        let average_deviation = cell_statistics.deviations.iter().map(|(_, deviation)| deviation).sum::<f64>() / cell_statistics.deviations.len() as f64;
+        CellBalanceRow {
+            cell,
+            times_highest: cell_statistics.times_highest,
+            times_lowest: cell_statistics.times_lowest,
+            outlier_percent: (cell_statistics.times_highest + cell_statistics.times_lowest) as f64 / balanced_samples as f64 * 100f64,
+            average_deviation_mv: average_deviation * 1000f64,
+            drift_mv_per_30_days: get_slope(&cell_statistics.deviations).map(|slope| slope * 1000f64 * 86400f64 * 30f64),
+        }
+    }).collect::<Vec<_>>();
+    rows.sort_by(|a, b| b.outlier_percent.total_cmp(&a.outlier_percent).then(a.cell.cmp(&b.cell)));
+    rows
+}
+
+//Least squares slope of value over time, in units per second. Needs at least two distinct times.
+fn get_slope(points: &[(i64, f64)]) -> Option<f64> {
+    if points.len() < 2 {
+        return None
+    }
+    //Offset the times so the squares stay small enough to keep their precision.
+    let origin = points[0].0;
+    let count = points.len() as f64;
+    let mean_time = points.iter().map(|(time, _)| (time - origin) as f64).sum::<f64>() / count;
+    let mean_value = points.iter().map(|(_, value)| value).sum::<f64>() / count;
+    let (covariance, variance) = points.iter().fold((0f64, 0f64), |(covariance, variance), (time, value)| {
+        let time = (time - origin) as f64 - mean_time;
+        (covariance + time * (value - mean_value), variance + time * time)
+    });
+    (variance > 0f64).then(|| covariance / variance)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_line(unix_time: i64, voltages: &[f32]) -> DataLine {
+        let mut line = DataLine::from(unix_time);
+        voltages.iter().enumerate().for_each(|(cell, voltage)| line.add_data(DataValue::CellVoltage { cell: cell as u16, voltage: *voltage }));
+        line
+    }
+
+    #[test]
+    fn deviation_from_the_pack_mean() {
+        let line = get_line(0, &[3.25, 3.5, 3.0]);
+        assert_eq!(calculate_cell_deviation(&line, 0), Some(0.0));
+        assert_eq!(calculate_cell_deviation(&line, 1), Some(0.25));
+        assert_eq!(calculate_cell_deviation(&line, 2), Some(-0.25));
+        assert_eq!(calculate_cell_deviation(&line, 3), None);
+        //One cell has nothing to deviate from.
+        assert_eq!(calculate_cell_deviation(&get_line(0, &[3.25]), 0), None);
+    }
+
+    #[test]
+    fn slope_of_a_linear_series() {
+        //Starting at a realistic timestamp, so the offset to the first time matters.
+        let points = (0..100).map(|index| (1_700_000_000 + index * 600, 2.0 + 0.001 * index as f64 * 600.0)).collect::<Vec<_>>();
+        assert!((get_slope(&points).unwrap() - 0.001).abs() < 1e-12);
+        assert_eq!(get_slope(&points[..1]), None);
+        assert_eq!(get_slope(&[(5, 1.0), (5, 2.0)]), None);
+    }
+
+    #[test]
+    fn summary_drift_and_outliers() {
+        //Cell 2 sinks by 1 mV a day while the others hold, so it drifts away from the pack mean at 2/3 of that.
+        let data = (1..=30).map(|day| get_line(day * 86400, &[3.3, 3.3, 3.3 - 0.001 * day as f32])).collect::<Vec<_>>();
+        let rows = get_cell_balance_summary(&data);
+        assert_eq!(rows.iter().map(|row| row.cell).collect::<Vec<_>>(), vec![0, 2, 1]);
+        assert_eq!((rows[1].times_lowest, rows[1].outlier_percent), (30, 100.0));
+        assert!((rows[1].drift_mv_per_30_days.unwrap() + 20.0).abs() < 0.1);
+        assert!((rows[2].drift_mv_per_30_days.unwrap() - 10.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,210 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{graph::{graph_aggregation::get_bucket_start, graph_state_request::Resolution}, solar_data::{energy::MAX_INTEGRATION_GAP, line::DataLine, value::DataValue}};
+
+
+//Below this a controller isn't charging at all.
+const IDLE_AMPS: f64 = 0.5;
+//How far back to look when deciding whether the controller is holding the voltage steady.
+const REGULATION_WINDOW_SECONDS: i64 = 300;
+//Fewer lines than this in the window can't show that the voltage is being held.
+const MIN_REGULATION_SAMPLES: usize = 3;
+//A held voltage varies by less than this fraction of itself over the window.
+const REGULATION_TOLERANCE: f64 = 0.005;
+//Held voltages within this fraction below the absorption voltage are absorption, further below they are float.
+const ABSORPTION_MARGIN: f64 = 0.02;
+//Held voltages further than this fraction below the absorption voltage aren't a charge setpoint at all, the controller is in bulk
+// with the voltage just rising slowly.
+const FLOAT_RANGE: f64 = 0.1;
+//The absorption voltage is taken as this percentile of the held voltages, so a few spikes don't set it.
+const ABSORPTION_PERCENTILE: f64 = 0.95;
+
+//The charge stages of a controller, inferred from its output since the controllers don't log them.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub enum ChargeStage {
+    Idle,
+    //Charging at whatever current is available, with the voltage rising.
+    Bulk,
+    //Holding the battery at the highest setpoint while the current tapers.
+    Absorption,
+    //Holding the battery at a lower setpoint once charged.
+    Float,
+}
+
+//How long one controller spent in each stage over one day, in seconds.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ChargeStageDay {
+    //Unix time of the start of the day.
+    pub day: i64,
+    pub controller: u16,
+    pub idle_seconds: i64,
+    pub bulk_seconds: i64,
+    pub absorption_seconds: i64,
+    pub float_seconds: i64,
+    //Whether the battery was held at the absorption voltage at all that day, which is what fully charges it.
+    pub reached_absorption: bool,
+}
+
+impl ChargeStage {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ChargeStage::Idle => "Idle",
+            ChargeStage::Bulk => "Bulk",
+            ChargeStage::Absorption => "Absorption",
+            ChargeStage::Float => "Float",
+        }
+    }
+
+    //The value of the stage channel on graphs.
+    pub fn get_value(&self) -> f64 {
+        match self {
+            ChargeStage::Idle => 0.0,
+            ChargeStage::Bulk => 1.0,
+            ChargeStage::Absorption => 2.0,
+            ChargeStage::Float => 3.0,
+        }
+    }
+}
+
+//The controller's battery voltage, or the battery voltage if the controller doesn't report one, and its output current.
+fn get_controller_point(line: &DataLine, controller: u16) -> Option<(f64, f64)> {
+    let mut battery_voltage = None;
+    let mut controller_voltage = None;
+    let mut amps = None;
+    line.line.iter().for_each(|value| {
+        match value {
+            DataValue::BatteryVoltage(voltage) => battery_voltage = Some(*voltage as f64),
+            DataValue::ControllerBatteryVoltage { controller: id, voltage } if *id == controller => controller_voltage = Some(*voltage as f64),
+            DataValue::ControllerAmps { controller: id, amps: value } if *id == controller => amps = Some(*value as f64),
+            _ => (),
+        }
+    });
+    Some((controller_voltage.or(battery_voltage)?, amps?))
+}
+
+//The stage of the controller at each line it has data for. Absorption and float are told apart by comparing the held voltage to the
+// highest voltage the controller usually holds, so the setpoints don't need to be known.
+pub fn classify_charge_stages(data: &[DataLine], controller: u16) -> Vec<(i64, ChargeStage)> {
+    let points = data.iter()
+        .filter_map(|line| get_controller_point(line, controller).map(|(voltage, amps)| (line.unix_time, voltage, amps)))
+        .collect::<Vec<_>>();
+
+    //Whether the voltage was held steady over the window leading up to each line.
+    let mut window_start = 0;
+    let held = points.iter().enumerate().map(|(index, (unix_time, voltage, amps))| {
+        while points[window_start].0 < unix_time - REGULATION_WINDOW_SECONDS {
+            window_start += 1;
+        }
+        let window = &points[window_start..=index];
+        if *amps < IDLE_AMPS || window.len() < MIN_REGULATION_SAMPLES || window.windows(2).any(|pair| pair[1].0 - pair[0].0 > MAX_INTEGRATION_GAP) {
+            return false
+        }
+        let (min, max) = window.iter().fold((f64::MAX, f64::MIN), |(min, max), (_, voltage, _)| (min.min(*voltage), max.max(*voltage)));
+        max - min <= voltage * REGULATION_TOLERANCE
+    }).collect::<Vec<_>>();
+
+    let mut held_voltages = points.iter().zip(held.iter())
+        .filter_map(|((_, voltage, _), held)| held.then_some(*voltage))
+        .collect::<Vec<_>>();
+    held_voltages.sort_by(|a, b| a.total_cmp(b));
+    let absorption_voltage = match held_voltages.is_empty() {
+        true => None,
+        false => Some(held_voltages[((held_voltages.len() - 1) as f64 * ABSORPTION_PERCENTILE).round() as usize]),
+    };
+
+    let mut previous: Option<(f64, ChargeStage)> = None;
+    points.iter().zip(held).map(|((unix_time, voltage, amps), held)| {
+        let stage = match (absorption_voltage, held, previous) {
+            _ if *amps < IDLE_AMPS => ChargeStage::Idle,
+            (Some(absorption_voltage), true, _) if *voltage >= absorption_voltage * (1.0 - ABSORPTION_MARGIN) => ChargeStage::Absorption,
+            (Some(absorption_voltage), true, _) if *voltage >= absorption_voltage * (1.0 - FLOAT_RANGE) => ChargeStage::Float,
+            //Bulk only ever raises the voltage. Falling away from a held voltage is the controller dropping to float.
+            (_, false, Some((previous_voltage, ChargeStage::Absorption | ChargeStage::Float))) if *voltage <= previous_voltage => ChargeStage::Float,
+            _ => ChargeStage::Bulk,
+        };
+        previous = Some((*voltage, stage));
+        (*unix_time, stage)
+    }).collect()
+}
+
+//Each line's stage lasts until the next line, unless the next line is too far away to know what happened in between.
+pub fn get_charge_stage_days(data: &[DataLine], controllers: &[u16]) -> Vec<ChargeStageDay> {
+    let mut days = controllers.iter().flat_map(|controller| {
+        let stages = classify_charge_stages(data, *controller);
+        let mut days: Vec<ChargeStageDay> = Vec::new();
+        stages.iter().enumerate().for_each(|(index, (unix_time, stage))| {
+            let day = get_bucket_start(*unix_time, &Resolution::OneDay);
+            if days.last().map(|last| last.day) != Some(day) {
+                days.push(ChargeStageDay {
+                    day,
+                    controller: *controller,
+                    idle_seconds: 0,
+                    bulk_seconds: 0,
+                    absorption_seconds: 0,
+                    float_seconds: 0,
+                    reached_absorption: false,
+                });
+            }
+            let current = days.last_mut().expect("A day was just pushed if there wasn't one");
+            let duration = match stages.get(index + 1) {
+                Some((next_time, _)) if next_time - unix_time <= MAX_INTEGRATION_GAP => next_time - unix_time,
+                _ => 0,
+            };
+            match stage {
+                ChargeStage::Idle => current.idle_seconds += duration,
+                ChargeStage::Bulk => current.bulk_seconds += duration,
+                ChargeStage::Absorption => {
+                    current.absorption_seconds += duration;
+                    current.reached_absorption = true;
+                },
+                ChargeStage::Float => current.float_seconds += duration,
+            }
+        });
+        days
+    }).collect::<Vec<_>>();
+    days.sort_by(|a, b| a.day.cmp(&b.day).then(a.controller.cmp(&b.controller)));
+    days
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A typical sunny day: rising voltage, held at 14.4 while the current tapers, then held at 13.6 with little current, then dark.
+    fn get_day() -> Vec<DataLine> {
+        (0..200).map(|minute| {
+            let (voltage, amps) = match minute {
+                0..=59 => (13.0 + minute as f32 * 0.02, 20.0),
+                60..=119 => (14.4, 20.0 - (minute - 60) as f32 * 0.3),
+                120..=179 => (13.6, 1.0),
+                _ => (13.3, 0.0),
+            };
+            DataLine { unix_time: minute * 60, line: vec![
+                DataValue::ControllerBatteryVoltage { controller: 1, voltage },
+                DataValue::ControllerAmps { controller: 1, amps },
+            ] }
+        }).collect()
+    }
+
+    #[test]
+    fn stages_follow_the_charge() {
+        let stages = classify_charge_stages(&get_day(), 1);
+        assert_eq!(stages[30].1, ChargeStage::Bulk);
+        assert_eq!(stages[90].1, ChargeStage::Absorption);
+        assert_eq!(stages[150].1, ChargeStage::Float);
+        assert_eq!(stages[190].1, ChargeStage::Idle);
+        //Other controllers have no data.
+        assert!(classify_charge_stages(&get_day(), 2).is_empty());
+    }
+
+    #[test]
+    fn daily_stage_times() {
+        let days = get_charge_stage_days(&get_day(), &[1]);
+        assert_eq!(days.len(), 1);
+        assert!(days[0].reached_absorption);
+        let total = days[0].idle_seconds + days[0].bulk_seconds + days[0].absorption_seconds + days[0].float_seconds;
+        assert_eq!(total, 199 * 60);
+        assert!(days[0].absorption_seconds > 50 * 60 && days[0].float_seconds > 50 * 60);
+    }
+}
@@ -0,0 +1,328 @@
+use std::{f64::consts::PI, ops::Range};
+
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use crate::{graph::{graph_axis::AxisDataType, graph_state_request::Resolution}, solar_data::{energy::integrate_energy, line::DataLine}};
+
+
+//Extraterrestrial beam irradiance (W/m²) used by the Meinel clear sky model.
+const SOLAR_CONSTANT: f64 = 1353f64;
+//Diffuse irradiance on a clear day, as a fraction of the beam irradiance.
+const DIFFUSE_FRACTION: f64 = 0.1;
+//Fraction of the global irradiance reflected by the ground onto tilted panels.
+const GROUND_ALBEDO: f64 = 0.2;
+//Days where less than this was expected (kWh) are left out of the performance ratio, since a tiny expected value makes the ratio meaningless.
+pub const MIN_EXPECTED_DAILY_ENERGY: f64 = 0.01;
+//The zenith at sunrise and sunset, allowing for refraction and the size of the sun's disc.
+const SUNRISE_ZENITH: f64 = 90.833;
+
+//Where the panels are. Timestamps in the data are the logger's local time stored as if it were UTC, so the offset from UTC is needed
+// to find the real position of the sun. Daylight saving time isn't followed, so pick the offset the logger's clock was set to.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SolarSite {
+    //Degrees, north positive.
+    pub latitude: f64,
+    //Degrees, east positive.
+    pub longitude: f64,
+    pub utc_offset_hours: f64,
+    pub arrays: Vec<SolarArray>,
+}
+
+//One set of panels feeding a controller. A controller can have several arrays facing different ways.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SolarArray {
+    pub controller: u16,
+    //Degrees from horizontal.
+    pub tilt: f64,
+    //Degrees clockwise from north, so 180 faces south.
+    pub azimuth: f64,
+    //Rated peak power in kilowatts, at 1000 W/m².
+    pub kwp: f64,
+}
+
+//Both in degrees. Azimuth is clockwise from north.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SunPosition {
+    pub zenith: f64,
+    pub azimuth: f64,
+}
+
+//When the sun is up on one day. Times are timestamps in the same local time as the data.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Daylight {
+    SunUp { sunrise: i64, sunset: i64 },
+    PolarNight,
+    MidnightSun,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct DailyPerformance {
+    //Unix time of the start of the day.
+    pub day: i64,
+    pub actual_kwh: f64,
+    pub expected_kwh: f64,
+    pub performance_ratio_percent: f64,
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum SolarSiteError {
+    #[error("Latitude must be between -90 and 90 degrees, found {0}.")]
+    InvalidLatitude(f64),
+    #[error("Longitude must be between -180 and 180 degrees, found {0}.")]
+    InvalidLongitude(f64),
+    #[error("UTC offset must be between -12 and 14 hours, found {0}.")]
+    InvalidUtcOffset(f64),
+    #[error("Array tilt must be between 0 and 90 degrees, found {0}.")]
+    InvalidTilt(f64),
+    #[error("Array azimuth must be between 0 and 360 degrees, found {0}.")]
+    InvalidAzimuth(f64),
+    #[error("Array peak power must be a positive number of kW, found {0}.")]
+    InvalidPeakPower(f64),
+}
+
+impl SolarSite {
+    pub fn validate(&self) -> Result<(), SolarSiteError> {
+        if !(-90f64..=90f64).contains(&self.latitude) {
+            return Err(SolarSiteError::InvalidLatitude(self.latitude))
+        }
+        if !(-180f64..=180f64).contains(&self.longitude) {
+            return Err(SolarSiteError::InvalidLongitude(self.longitude))
+        }
+        if !(-12f64..=14f64).contains(&self.utc_offset_hours) {
+            return Err(SolarSiteError::InvalidUtcOffset(self.utc_offset_hours))
+        }
+        self.arrays.iter().try_for_each(|array| {
+            if !(0f64..=90f64).contains(&array.tilt) {
+                return Err(SolarSiteError::InvalidTilt(array.tilt))
+            }
+            if !(0f64..=360f64).contains(&array.azimuth) {
+                return Err(SolarSiteError::InvalidAzimuth(array.azimuth))
+            }
+            if !array.kwp.is_finite() || array.kwp <= 0f64 {
+                return Err(SolarSiteError::InvalidPeakPower(array.kwp))
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_sun_position(&self, timestamp: i64) -> Option<SunPosition> {
+        let utc_timestamp = timestamp - (self.utc_offset_hours * 3600f64).round() as i64;
+        get_sun_position(utc_timestamp, self.latitude, self.longitude)
+    }
+
+    //Sunrise and sunset on the day starting at day_start, from the sun's position at local noon.
+    pub fn get_daylight(&self, day_start: i64) -> Option<Daylight> {
+        let utc_noon = day_start + 43200 - (self.utc_offset_hours * 3600f64).round() as i64;
+        let date_time = OffsetDateTime::from_unix_timestamp(utc_noon).ok()?;
+        let (equation_of_time, declination) = get_solar_angles(date_time.ordinal(), 12f64);
+        let latitude = self.latitude.to_radians();
+        let cos_hour_angle = SUNRISE_ZENITH.to_radians().cos() / (latitude.cos() * declination.cos()) - latitude.tan() * declination.tan();
+        if cos_hour_angle > 1f64 {
+            return Some(Daylight::PolarNight)
+        }
+        if cos_hour_angle < -1f64 || cos_hour_angle.is_nan() {
+            return Some(Daylight::MidnightSun)
+        }
+        let hour_angle = cos_hour_angle.acos().to_degrees();
+        //Minutes after midnight UTC, moved onto the local clock.
+        let to_timestamp = |utc_minutes: f64| day_start + ((utc_minutes + self.utc_offset_hours * 60f64) * 60f64).round() as i64;
+        Some(Daylight::SunUp {
+            sunrise: to_timestamp(720f64 - 4f64 * (self.longitude + hour_angle) - equation_of_time),
+            sunset: to_timestamp(720f64 - 4f64 * (self.longitude - hour_angle) - equation_of_time),
+        })
+    }
+
+    //Every stretch of night that overlaps the range, clipped to it. Nights that cross midnight come out as one period.
+    pub fn get_night_periods(&self, range: Range<i64>) -> Vec<Range<i64>> {
+        let mut nights: Vec<Range<i64>> = Vec::new();
+        let mut day_start = range.start - range.start.rem_euclid(86400);
+        while day_start < range.end {
+            let day_end = day_start + 86400;
+            let day_nights = match self.get_daylight(day_start) {
+                Some(Daylight::SunUp { sunrise, sunset }) => vec![day_start..sunrise, sunset..day_end],
+                Some(Daylight::PolarNight) => vec![Range { start: day_start, end: day_end }],
+                Some(Daylight::MidnightSun) | None => Vec::new(),
+            };
+            day_nights.into_iter().for_each(|night| {
+                match nights.last_mut() {
+                    Some(last) if last.end >= night.start => last.end = last.end.max(night.end),
+                    _ => nights.push(night),
+                }
+            });
+            day_start = day_end;
+        }
+        nights.into_iter()
+            .map(|night| night.start.max(range.start)..night.end.min(range.end))
+            .filter(|night| night.start < night.end)
+            .collect()
+    }
+
+    //Expected clear sky output of every array, in watts.
+    pub fn get_expected_watts(&self, timestamp: i64) -> Option<f64> {
+        let sun_position = self.get_sun_position(timestamp)?;
+        Some(self.arrays.iter().map(|array| array.get_expected_watts(&sun_position)).sum())
+    }
+
+    //Expected clear sky output of the arrays on one controller, in watts. None if the controller has no arrays.
+    pub fn get_expected_controller_watts(&self, timestamp: i64, controller: u16) -> Option<f64> {
+        if !self.arrays.iter().any(|array| array.controller == controller) {
+            return None
+        }
+        let sun_position = self.get_sun_position(timestamp)?;
+        Some(self.arrays.iter().filter(|array| array.controller == controller).map(|array| array.get_expected_watts(&sun_position)).sum())
+    }
+
+    //Expected controller current into the battery, from the expected power and the battery voltage at the time.
+    pub fn get_expected_controller_amps(&self, line: &DataLine, controller: u16) -> Option<f64> {
+        let battery_voltage = line.calculate_axis_data(AxisDataType::BatteryVoltage).filter(|voltage| *voltage > 0f64)?;
+        Some(self.get_expected_controller_watts(line.unix_time, controller)? / battery_voltage)
+    }
+}
+
+impl SolarArray {
+    pub fn get_expected_watts(&self, sun_position: &SunPosition) -> f64 {
+        //Peak power is rated at 1000 W/m², so kW of peak power times W/m² of irradiance is watts.
+        self.kwp * get_plane_of_array_irradiance(sun_position, self.tilt, self.azimuth)
+    }
+}
+
+//The NOAA approximations of the sun's declination and the equation of time, accurate to within a degree or so, which is
+// plenty for an expected production curve.
+pub fn get_sun_position(utc_timestamp: i64, latitude: f64, longitude: f64) -> Option<SunPosition> {
+    let date_time = OffsetDateTime::from_unix_timestamp(utc_timestamp).ok()?;
+    let hours = date_time.hour() as f64 + date_time.minute() as f64 / 60f64 + date_time.second() as f64 / 3600f64;
+    let (equation_of_time, declination) = get_solar_angles(date_time.ordinal(), hours);
+
+    let true_solar_minutes = hours * 60f64 + equation_of_time + 4f64 * longitude;
+    let hour_angle = (true_solar_minutes / 4f64 - 180f64).to_radians();
+    let latitude = latitude.to_radians();
+
+    let cos_zenith = (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos()).clamp(-1f64, 1f64);
+    //Measured from south towards west, then turned to be from north.
+    let azimuth = hour_angle.sin().atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos()).to_degrees() + 180f64;
+    Some(SunPosition { zenith: cos_zenith.acos().to_degrees(), azimuth })
+}
+
+//The equation of time in minutes and the sun's declination in radians, at a UTC hour of a day of the year.
+fn get_solar_angles(ordinal: u16, hours: f64) -> (f64, f64) {
+    //Fractional year, in radians.
+    let gamma = 2f64 * PI / 365f64 * (ordinal as f64 - 1f64 + (hours - 12f64) / 24f64);
+    let equation_of_time = 229.18 * (0.000075 + 0.001868 * gamma.cos() - 0.032077 * gamma.sin()
+        - 0.014615 * (2f64 * gamma).cos() - 0.040849 * (2f64 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2f64 * gamma).cos() + 0.000907 * (2f64 * gamma).sin()
+        - 0.002697 * (3f64 * gamma).cos() + 0.00148 * (3f64 * gamma).sin();
+    (equation_of_time, declination)
+}
+
+//Clear sky irradiance on a tilted surface, in W/m². Beam irradiance follows the Meinel model with the Kasten-Young air mass.
+//Diffuse light is treated as coming evenly from the whole sky.
+pub fn get_plane_of_array_irradiance(sun_position: &SunPosition, tilt: f64, azimuth: f64) -> f64 {
+    if sun_position.zenith >= 90f64 {
+        return 0f64
+    }
+    let zenith = sun_position.zenith.to_radians();
+    let air_mass = 1f64 / (zenith.cos() + 0.50572 * (96.07995 - sun_position.zenith).powf(-1.6364));
+    let beam = SOLAR_CONSTANT * 0.7f64.powf(air_mass.powf(0.678));
+    let diffuse = beam * DIFFUSE_FRACTION;
+    let global = beam * zenith.cos() + diffuse;
+
+    let tilt = tilt.to_radians();
+    let cos_incidence = zenith.cos() * tilt.cos() + zenith.sin() * tilt.sin() * (sun_position.azimuth - azimuth).to_radians().cos();
+    beam * cos_incidence.max(0f64)
+        + diffuse * (1f64 + tilt.cos()) / 2f64
+        + global * GROUND_ALBEDO * (1f64 - tilt.cos()) / 2f64
+}
+
+//Actual solar energy against the clear sky expectation, day by day. The expected power is only integrated where there is actual data,
+// so gaps in the data don't drag the ratio down. Actual energy is all of the solar watts, so every controller needs its arrays entered.
+pub fn get_daily_performance(data: &[DataLine], site: &SolarSite) -> Vec<DailyPerformance> {
+    let (actual, expected): (Vec<_>, Vec<_>) = data.iter().filter_map(|line| {
+        let watts = line.calculate_axis_data(AxisDataType::SolarWatts)?;
+        let expected_watts = site.get_expected_watts(line.unix_time)?;
+        Some(((line.unix_time, watts), (line.unix_time, expected_watts)))
+    }).unzip();
+    let actual = integrate_energy(actual, &Resolution::OneDay);
+    let expected = integrate_energy(expected, &Resolution::OneDay);
+
+    //Both are integrated over the same timestamps, so they have the same days.
+    actual.into_iter().zip(expected).filter_map(|((day, actual_kwh), (_day, expected_kwh))| {
+        if expected_kwh < MIN_EXPECTED_DAILY_ENERGY {
+            return None
+        }
+        Some(DailyPerformance { day, actual_kwh, expected_kwh, performance_ratio_percent: actual_kwh / expected_kwh * 100f64 })
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn sun_position() {
+        //Solar noon on the equator at the March equinox, about seven minutes after noon UTC.
+        let position = get_sun_position(datetime!(2023-03-20 12:07 UTC).unix_timestamp(), 0.0, 0.0).unwrap();
+        assert!(position.zenith < 1.0, "zenith {}", position.zenith);
+
+        //Solar noon at 40°N on the June solstice, with the sun 23.4° north of the equator.
+        let position = get_sun_position(datetime!(2023-06-21 12:02 UTC).unix_timestamp(), 40.0, 0.0).unwrap();
+        assert!((position.zenith - 16.6).abs() < 0.5, "zenith {}", position.zenith);
+        assert!((position.azimuth - 180.0).abs() < 2.0, "azimuth {}", position.azimuth);
+
+        //Mid morning the sun is in the east, and at midnight it's down.
+        let position = get_sun_position(datetime!(2023-06-21 09:00 UTC).unix_timestamp(), 40.0, 0.0).unwrap();
+        assert!(position.azimuth > 45.0 && position.azimuth < 135.0, "azimuth {}", position.azimuth);
+        let position = get_sun_position(datetime!(2023-06-21 00:00 UTC).unix_timestamp(), 40.0, 0.0).unwrap();
+        assert!(position.zenith > 90.0);
+    }
+
+    #[test]
+    fn expected_power() {
+        let site = SolarSite {
+            latitude: 40.0,
+            longitude: -105.0,
+            utc_offset_hours: -7.0,
+            arrays: vec![SolarArray { controller: 1, tilt: 40.0, azimuth: 180.0, kwp: 2.0 }],
+        };
+        //Timestamps are local time, so local noon is close to solar noon here.
+        let noon = site.get_expected_watts(datetime!(2023-03-20 12:00 UTC).unix_timestamp()).unwrap();
+        assert!(noon > 1800.0 && noon < 2300.0, "noon {}", noon);
+        assert_eq!(site.get_expected_watts(datetime!(2023-03-20 00:00 UTC).unix_timestamp()), Some(0.0));
+        assert_eq!(site.get_expected_controller_watts(datetime!(2023-03-20 12:00 UTC).unix_timestamp(), 2), None);
+        assert!(site.validate().is_ok());
+        let mut invalid = site.clone();
+        invalid.arrays[0].kwp = 0.0;
+        assert_eq!(invalid.validate(), Err(SolarSiteError::InvalidPeakPower(0.0)));
+    }
+
+    #[test]
+    fn sunrise_and_sunset() {
+        let site = SolarSite { latitude: 40.0, longitude: -105.0, utc_offset_hours: -7.0, arrays: Vec::new() };
+        let day = datetime!(2023-03-20 00:00 UTC).unix_timestamp();
+        //About 06:03 and 18:12 by the local clock.
+        let (sunrise, sunset) = match site.get_daylight(day) {
+            Some(Daylight::SunUp { sunrise, sunset }) => (sunrise, sunset),
+            daylight => panic!("expected the sun to rise, found {:?}", daylight),
+        };
+        assert!((sunrise - datetime!(2023-03-20 06:03 UTC).unix_timestamp()).abs() < 300, "sunrise {}", sunrise);
+        assert!((sunset - datetime!(2023-03-20 18:12 UTC).unix_timestamp()).abs() < 300, "sunset {}", sunset);
+
+        //Two days give three nights, the one in the middle crossing midnight.
+        let nights = site.get_night_periods(day..day + 2 * 86400);
+        assert_eq!(nights.len(), 3);
+        assert_eq!(nights[0].start, day);
+        assert_eq!(nights[1].start, sunset);
+        assert_eq!(nights[2].end, day + 2 * 86400);
+
+        let arctic = SolarSite { latitude: 80.0, longitude: 0.0, utc_offset_hours: 0.0, arrays: Vec::new() };
+        assert_eq!(arctic.get_daylight(datetime!(2023-12-21 00:00 UTC).unix_timestamp()), Some(Daylight::PolarNight));
+        assert_eq!(arctic.get_daylight(datetime!(2023-06-21 00:00 UTC).unix_timestamp()), Some(Daylight::MidnightSun));
+        let winter = datetime!(2023-12-21 00:00 UTC).unix_timestamp();
+        assert_eq!(arctic.get_night_periods(winter..winter + 86400), vec![winter..winter + 86400]);
+    }
+}
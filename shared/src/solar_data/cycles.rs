@@ -0,0 +1,228 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{graph::{graph_aggregation::get_month_start, graph_axis::AxisDataType}, solar_data::line::DataLine};
+
+
+//State of charge has to turn around by at least this much to count as a reversal. Smaller wiggles are reading noise.
+pub const REVERSAL_HYSTERESIS: f64 = 1f64;
+//Width of each depth of discharge histogram bin, in percent.
+pub const DEPTH_BIN_WIDTH: f64 = 10f64;
+
+//A full or half cycle found by the rainflow count.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Cycle {
+    //Depth of discharge, in percent of state of charge.
+    pub depth: f64,
+    //1 for a full cycle, 0.5 for a half cycle.
+    pub count: f64,
+    //When the cycle was closed.
+    pub end: i64,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct DepthBin {
+    pub min_depth: f64,
+    pub max_depth: f64,
+    pub cycles: f64,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct MonthlyCycles {
+    //Unix time of the start of the month.
+    pub month: i64,
+    pub cycles: f64,
+    pub equivalent_full_cycles: f64,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Default, Debug)]
+pub struct CycleSummary {
+    //Every cycle weighted by its depth, so two 50% cycles make one equivalent full cycle. This is what warranties count.
+    pub equivalent_full_cycles: f64,
+    pub total_cycles: f64,
+    pub depth_histogram: Vec<DepthBin>,
+    pub monthly: Vec<MonthlyCycles>,
+}
+
+//Counts cycles in the state of charge with the three point rainflow method (ASTM E1049).
+pub fn count_cycles(data: &[DataLine]) -> Vec<Cycle> {
+    let reversals = get_reversals(data.iter().filter_map(|line| {
+        line.calculate_axis_data(AxisDataType::StateOfChargePercent).map(|state_of_charge| (line.unix_time, state_of_charge))
+    }));
+
+    let mut cycles = Vec::new();
+    let mut stack: Vec<(i64, f64)> = Vec::new();
+    for reversal in reversals {
+        stack.push(reversal);
+        while stack.len() >= 3 {
+            let newest_range = (stack[stack.len() - 1].1 - stack[stack.len() - 2].1).abs();
+            let previous_range = (stack[stack.len() - 2].1 - stack[stack.len() - 3].1).abs();
+            if newest_range < previous_range {
+                break;
+            }
+            if stack.len() == 3 {
+                //The range includes the starting point, so it's only half a cycle.
+                cycles.push(Cycle { depth: previous_range, count: 0.5, end: reversal.0 });
+                stack.remove(0);
+            } else {
+                cycles.push(Cycle { depth: previous_range, count: 1f64, end: reversal.0 });
+                let newest = stack.pop().expect("Stack has at least three entries");
+                stack.pop();
+                stack.pop();
+                stack.push(newest);
+            }
+        }
+    }
+    //Whatever is left never closed, and counts as half cycles.
+    stack.windows(2).for_each(|pair| {
+        cycles.push(Cycle { depth: (pair[1].1 - pair[0].1).abs(), count: 0.5, end: pair[1].0 });
+    });
+    cycles
+}
+
+pub fn get_cycle_summary(cycles: &[Cycle]) -> CycleSummary {
+    let bin_count = (100f64 / DEPTH_BIN_WIDTH).ceil() as usize;
+    let mut depth_histogram = (0..bin_count).map(|bin| DepthBin {
+        min_depth: bin as f64 * DEPTH_BIN_WIDTH,
+        max_depth: ((bin + 1) as f64 * DEPTH_BIN_WIDTH).min(100f64),
+        cycles: 0f64,
+    }).collect::<Vec<_>>();
+    let mut monthly: Vec<MonthlyCycles> = Vec::new();
+    let mut summary_cycles = 0f64;
+    let mut summary_equivalent_full_cycles = 0f64;
+
+    cycles.iter().for_each(|cycle| {
+        let equivalent_full_cycles = cycle.count * cycle.depth / 100f64;
+        summary_cycles += cycle.count;
+        summary_equivalent_full_cycles += equivalent_full_cycles;
+
+        let bin = ((cycle.depth / DEPTH_BIN_WIDTH) as usize).min(bin_count - 1);
+        depth_histogram[bin].cycles += cycle.count;
+
+        if let Some(month) = get_month_start(cycle.end) {
+            let index = match monthly.binary_search_by_key(&month, |monthly_cycles| monthly_cycles.month) {
+                Ok(index) => index,
+                Err(index) => {
+                    monthly.insert(index, MonthlyCycles { month, cycles: 0f64, equivalent_full_cycles: 0f64 });
+                    index
+                },
+            };
+            monthly[index].cycles += cycle.count;
+            monthly[index].equivalent_full_cycles += equivalent_full_cycles;
+        }
+    });
+
+    CycleSummary {
+        equivalent_full_cycles: summary_equivalent_full_cycles,
+        total_cycles: summary_cycles,
+        depth_histogram,
+        monthly,
+    }
+}
+
+//The peaks and valleys of the series, including the first and last points.
+fn get_reversals<T: IntoIterator<Item = (i64, f64)>>(data: T) -> Vec<(i64, f64)> {
+    let mut reversals = Vec::new();
+    //The most extreme point since the last reversal, and whether the series is currently rising.
+    let mut extreme: Option<(i64, f64)> = None;
+    let mut rising: Option<bool> = None;
+    for point in data {
+        let current_extreme = match extreme {
+            Some(current_extreme) => current_extreme,
+            None => {
+                reversals.push(point);
+                extreme = Some(point);
+                continue;
+            },
+        };
+        match rising {
+            None => {
+                let start = reversals[0].1;
+                if (point.1 - start).abs() >= REVERSAL_HYSTERESIS {
+                    rising = Some(point.1 > start);
+                    extreme = Some(point);
+                }
+            },
+            Some(true) => {
+                if point.1 >= current_extreme.1 {
+                    extreme = Some(point);
+                } else if current_extreme.1 - point.1 >= REVERSAL_HYSTERESIS {
+                    reversals.push(current_extreme);
+                    rising = Some(false);
+                    extreme = Some(point);
+                }
+            },
+            Some(false) => {
+                if point.1 <= current_extreme.1 {
+                    extreme = Some(point);
+                } else if point.1 - current_extreme.1 >= REVERSAL_HYSTERESIS {
+                    reversals.push(current_extreme);
+                    rising = Some(true);
+                    extreme = Some(point);
+                }
+            },
+        }
+    }
+    if let (Some(last_extreme), Some(_)) = (extreme, rising) {
+        reversals.push(last_extreme);
+    }
+    reversals
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solar_data::value::DataValue;
+    use time::macros::datetime;
+
+    fn get_lines(states_of_charge: &[f64]) -> Vec<DataLine> {
+        states_of_charge.iter().enumerate().map(|(index, state_of_charge)| {
+            let mut line = DataLine::from(index as i64 * 3600);
+            line.add_data(DataValue::StateOfChargePercent(*state_of_charge as f32));
+            line
+        }).collect()
+    }
+
+    #[test]
+    fn astm_reference_sequence() {
+        //The ASTM E1049 rainflow example, -2, 1, -3, 5, -1, 3, -4, 4, -2, scaled by 10 and moved up to 50% state of charge.
+        let reference = [-2.0, 1.0, -3.0, 5.0, -1.0, 3.0, -4.0, 4.0, -2.0].map(|value| 50.0 + value * 10.0);
+        let cycles = count_cycles(&get_lines(&reference));
+        let mut halves_and_fulls = cycles.iter().map(|cycle| (cycle.depth, cycle.count)).collect::<Vec<_>>();
+        halves_and_fulls.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        assert_eq!(halves_and_fulls, vec![(30.0, 0.5), (40.0, 0.5), (40.0, 1.0), (60.0, 0.5), (80.0, 0.5), (80.0, 0.5), (90.0, 0.5)]);
+        //The standard's table: ranges of 3, 4, 6, 8 and 9 with 0.5, 1.5, 0.5, 1 and 0.5 cycles.
+        let totals = [30.0, 40.0, 60.0, 80.0, 90.0].map(|depth| cycles.iter().filter(|cycle| cycle.depth == depth).map(|cycle| cycle.count).sum::<f64>());
+        assert_eq!(totals, [0.5, 1.5, 0.5, 1.0, 0.5]);
+        //The one full cycle, from -1 to 3, closes when the series falls to -4.
+        assert_eq!(cycles.iter().find(|cycle| cycle.count == 1.0).map(|cycle| cycle.end), Some(6 * 3600));
+    }
+
+    #[test]
+    fn small_wiggles_are_not_reversals() {
+        let cycles = count_cycles(&get_lines(&[50.0, 50.5, 50.0, 50.5, 70.0, 69.5, 70.0, 40.0]));
+        assert_eq!(cycles.iter().map(|cycle| (cycle.depth, cycle.count)).collect::<Vec<_>>(), vec![(20.0, 0.5), (30.0, 0.5)]);
+    }
+
+    #[test]
+    fn depth_of_discharge_summary() {
+        let march = datetime!(2023-03-15 0:00).assume_utc().unix_timestamp();
+        let april = datetime!(2023-04-15 0:00).assume_utc().unix_timestamp();
+        let cycles = vec![
+            Cycle { depth: 30.0, count: 1.0, end: march },
+            Cycle { depth: 80.0, count: 0.5, end: march },
+            Cycle { depth: 100.0, count: 1.0, end: april },
+            Cycle { depth: 5.0, count: 0.5, end: april },
+        ];
+        let summary = get_cycle_summary(&cycles);
+        assert_eq!(summary.total_cycles, 3.0);
+        assert!((summary.equivalent_full_cycles - 1.725).abs() < 1e-9);
+        assert_eq!(summary.depth_histogram.len(), 10);
+        //A full depth cycle goes in the last bin rather than one past it.
+        assert_eq!(summary.depth_histogram.iter().map(|bin| bin.cycles).collect::<Vec<_>>(), vec![0.5, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 1.0]);
+        assert_eq!((summary.depth_histogram[9].min_depth, summary.depth_histogram[9].max_depth), (90.0, 100.0));
+        assert_eq!(summary.monthly.iter().map(|monthly| monthly.cycles).collect::<Vec<_>>(), vec![1.5, 1.5]);
+        assert!((summary.monthly[0].equivalent_full_cycles - 0.7).abs() < 1e-9);
+        assert_eq!(get_cycle_summary(&[]).total_cycles, 0.0);
+    }
+}
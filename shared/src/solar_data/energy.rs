@@ -0,0 +1,94 @@
+use crate::graph::{graph_aggregation::get_bucket_start, graph_state_request::Resolution};
+
+
+//Samples further apart than this are treated as a gap in the data, and nothing is integrated across them.
+pub const MAX_INTEGRATION_GAP: i64 = 900;
+
+//Integrates time ordered power samples (watts) into energy (kWh) per time bucket, using the trapezoidal rule.
+//Each interval between two samples is split at bucket boundaries, so energy always lands in the bucket it was produced in.
+//Returns the start time of each bucket along with its energy. Buckets with no integrated intervals are skipped.
+pub fn integrate_energy<T>(power: T, resolution: &Resolution) -> Vec<(i64, f64)>
+where
+    T: IntoIterator<Item = (i64, f64)>,
+{
+    let mut energy: Vec<(i64, f64)> = Vec::new();
+    let mut previous: Option<(i64, f64)> = None;
+    for (time, watts) in power {
+        if let Some((previous_time, previous_watts)) = previous {
+            let duration = time - previous_time;
+            if duration > 0 && duration <= MAX_INTEGRATION_GAP {
+                let mut start = previous_time;
+                while start < time {
+                    let bucket = get_bucket_start(start, resolution);
+                    let end = time.min(bucket + resolution.get_timestamp_offset());
+                    let start_watts = interpolate(previous_time, previous_watts, time, watts, start);
+                    let end_watts = interpolate(previous_time, previous_watts, time, watts, end);
+                    let watt_hours = (start_watts + end_watts) / 2f64 * (end - start) as f64 / 3600f64;
+                    match energy.last_mut() {
+                        Some(last) if last.0 == bucket => last.1 += watt_hours / 1000f64,
+                        _ => energy.push((bucket, watt_hours / 1000f64)),
+                    }
+                    start = end;
+                }
+            }
+        }
+        previous = Some((time, watts));
+    }
+    energy
+}
+
+//Turns energy per bucket into a running total since the first bucket.
+pub fn cumulative_energy(energy: &[(i64, f64)]) -> Vec<(i64, f64)> {
+    let mut total = 0f64;
+    energy.iter().map(|(bucket, kilowatt_hours)| {
+        total += kilowatt_hours;
+        (*bucket, total)
+    }).collect()
+}
+
+fn interpolate(start_time: i64, start_value: f64, end_time: i64, end_value: f64, time: i64) -> f64 {
+    start_value + (end_value - start_value) * (time - start_time) as f64 / (end_time - start_time) as f64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_energy(actual: Vec<(i64, f64)>, expected: Vec<(i64, f64)>) {
+        assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+        actual.iter().zip(expected.iter()).for_each(|(actual, expected)| {
+            assert!(actual.0 == expected.0 && (actual.1 - expected.1).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        });
+    }
+
+    #[test]
+    fn trapezoid_on_a_known_profile() {
+        //Ramps from 0 W to 1000 W over 15 minutes, then holds for 15 minutes: 125 Wh then 250 Wh.
+        let power = vec![(0, 0.0), (900, 1000.0), (1800, 1000.0)];
+        assert_energy(integrate_energy(power, &Resolution::OneHour), vec![(0, 0.375)]);
+    }
+
+    #[test]
+    fn intervals_split_at_bucket_and_day_boundaries() {
+        //The ramp crosses the hour at 600 W, so 25 Wh land before it and 75 Wh after.
+        let power = vec![(3300, 0.0), (3900, 1200.0)];
+        assert_energy(integrate_energy(power, &Resolution::OneHour), vec![(0, 0.025), (3600, 0.075)]);
+        let power = vec![(86400 - 300, 1200.0), (86400 + 300, 1200.0)];
+        assert_energy(integrate_energy(power, &Resolution::OneDay), vec![(0, 0.1), (86400, 0.1)]);
+    }
+
+    #[test]
+    fn gaps_are_skipped() {
+        let power = vec![(0, 1200.0), (MAX_INTEGRATION_GAP, 1200.0), (MAX_INTEGRATION_GAP * 2 + 1, 1200.0), (MAX_INTEGRATION_GAP * 2 + 301, 1200.0)];
+        //Only the first interval and the one after the gap count, 300 Wh and 100 Wh.
+        assert_energy(integrate_energy(power, &Resolution::OneHour), vec![(0, 0.4)]);
+        assert_energy(integrate_energy(vec![(0, 1200.0), (MAX_INTEGRATION_GAP + 1, 1200.0)], &Resolution::OneHour), Vec::new());
+    }
+
+    #[test]
+    fn cumulative_running_total() {
+        assert_energy(cumulative_energy(&[(0, 1.0), (3600, 2.0), (7200, 0.5)]), vec![(0, 1.0), (3600, 3.0), (7200, 3.5)]);
+        assert_energy(cumulative_energy(&[]), Vec::new());
+    }
+}
@@ -0,0 +1,566 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::{graph::graph_axis::AxisDataType, solar_data::{line::DataLine, value::DataValue}};
+
+
+//A channel defined by the user as an expression over the built in channels, for example "SolarWatts - LoadWatts".
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct DerivedChannel {
+    pub name: String,
+    pub expression: String,
+}
+
+impl DerivedChannel {
+    //Checks everything that can be wrong with a derived channel before it is saved.
+    pub fn validate(&self) -> Result<Expression, DerivedChannelError> {
+        if self.name.trim().is_empty() {
+            return Err(DerivedChannelError::EmptyName)
+        }
+        Ok(Expression::parse(&self.expression)?)
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum DerivedChannelError {
+    #[error("Derived channels need a name.")]
+    EmptyName,
+    #[error("A derived channel named {0} already exists.")]
+    DuplicateName(String),
+    #[error("No derived channel named {0} exists.")]
+    NotFound(String),
+    #[error(transparent)]
+    InvalidExpression(#[from] ExpressionError),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum ExpressionError {
+    #[error("The expression is empty.")]
+    Empty,
+    #[error("Unexpected character '{0}' at position {1}.")]
+    UnexpectedCharacter(char, usize),
+    #[error("Unexpected '{0}' at position {1}.")]
+    UnexpectedToken(String, usize),
+    #[error("The expression ended unexpectedly.")]
+    UnexpectedEnd,
+    #[error("Invalid number '{0}' at position {1}.")]
+    InvalidNumber(String, usize),
+    #[error("Unknown channel or function '{0}' at position {1}.")]
+    UnknownName(String, usize),
+    #[error("{0} needs an index, for example {0}[0] or {0}[*].")]
+    MissingIndex(String),
+    #[error("{0} does not take an index.")]
+    UnexpectedIndex(String),
+    #[error("{0}[*] can only be used inside min, max, avg, sum or count.")]
+    WildcardOutsideFunction(String),
+    #[error("{0} expects {1} argument(s), found {2}.")]
+    WrongArgumentCount(String, String, usize),
+    #[error("The expression is nested too deeply, at most {0} levels of parentheses, functions, negation and chained operators are allowed.")]
+    TooDeeplyNested(usize),
+}
+
+//Channels that exist once per cell or controller, and so can be selected all at once with [*].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexedChannel {
+    CellVoltage,
+    ControllerPanelVoltage,
+    ControllerAmps,
+    ControllerTemperatureF,
+}
+
+impl IndexedChannel {
+    fn get_name(&self) -> &'static str {
+        match self {
+            IndexedChannel::CellVoltage => "CellVoltage",
+            IndexedChannel::ControllerPanelVoltage => "ControllerPanelVoltage",
+            IndexedChannel::ControllerAmps => "ControllerAmps",
+            IndexedChannel::ControllerTemperatureF => "ControllerTemperatureF",
+        }
+    }
+
+    fn get_axis_data_type(&self, index: u16) -> AxisDataType {
+        match self {
+            IndexedChannel::CellVoltage => AxisDataType::CellVoltage(index),
+            IndexedChannel::ControllerPanelVoltage => AxisDataType::ControllerPanelVoltage(index),
+            IndexedChannel::ControllerAmps => AxisDataType::ControllerAmps(index),
+            IndexedChannel::ControllerTemperatureF => AxisDataType::ControllerTemperatureF(index),
+        }
+    }
+
+    fn get_values(&self, line: &DataLine) -> Vec<f64> {
+        line.line.iter().filter_map(|value| {
+            match (self, value) {
+                (IndexedChannel::CellVoltage, DataValue::CellVoltage { cell: _, voltage }) => Some(*voltage as f64),
+                (IndexedChannel::ControllerPanelVoltage, DataValue::ControllerPanelVoltage { controller: _, voltage }) => Some(*voltage as f64),
+                (IndexedChannel::ControllerAmps, DataValue::ControllerAmps { controller: _, amps }) => Some(*amps as f64),
+                (IndexedChannel::ControllerTemperatureF, DataValue::ControllerTemperatureF { controller: _, temperature }) => Some(*temperature as f64),
+                _ => None,
+            }
+        }).collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Function {
+    Min,
+    Max,
+    Avg,
+    Sum,
+    Count,
+    Abs,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name.to_ascii_lowercase().as_str() {
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "avg" => Some(Function::Avg),
+            "sum" => Some(Function::Sum),
+            "count" => Some(Function::Count),
+            "abs" => Some(Function::Abs),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, values: &[f64]) -> Option<f64> {
+        match self {
+            Function::Min => values.iter().cloned().reduce(f64::min),
+            Function::Max => values.iter().cloned().reduce(f64::max),
+            Function::Avg => (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64),
+            Function::Sum => Some(values.iter().sum()),
+            Function::Count => Some(values.len() as f64),
+            Function::Abs => values.first().map(|value| value.abs()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Argument {
+    Value(Expression),
+    //Every value of the channel that is present in the line.
+    Wildcard(IndexedChannel),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expression {
+    Number(f64),
+    Channel(AxisDataType),
+    Negate(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+    Function(Function, Vec<Argument>),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Expression, ExpressionError> {
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Err(ExpressionError::Empty)
+        }
+        let mut parser = Parser { tokens, position: 0, depth: 0 };
+        let expression = parser.parse_sum()?;
+        match parser.peek() {
+            Some((token, position)) => Err(ExpressionError::UnexpectedToken(token.to_string(), *position)),
+            None => Ok(expression),
+        }
+    }
+
+    //Returns None if any channel used by the expression is missing from the line, or the result isn't a finite number.
+    pub fn evaluate(&self, line: &DataLine) -> Option<f64> {
+        let value = match self {
+            Expression::Number(value) => *value,
+            Expression::Channel(axis_data_type) => line.calculate_axis_data(axis_data_type.clone())?,
+            Expression::Negate(expression) => -expression.evaluate(line)?,
+            Expression::Binary(left, operator, right) => {
+                let left = left.evaluate(line)?;
+                let right = right.evaluate(line)?;
+                match operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => left / right,
+                }
+            },
+            Expression::Function(function, arguments) => {
+                let mut values = Vec::new();
+                for argument in arguments {
+                    match argument {
+                        Argument::Value(expression) => values.push(expression.evaluate(line)?),
+                        Argument::Wildcard(channel) => values.extend(channel.get_values(line)),
+                    }
+                }
+                function.apply(&values)?
+            },
+        };
+        value.is_finite().then_some(value)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+    Star,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Operator(Operator::Add) => write!(f, "+"),
+            Token::Operator(Operator::Subtract) => write!(f, "-"),
+            Token::Operator(Operator::Multiply) | Token::Star => write!(f, "*"),
+            Token::Operator(Operator::Divide) => write!(f, "/"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenBracket => write!(f, "["),
+            Token::CloseBracket => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let characters = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    //Inside brackets, * is a wildcard rather than multiplication.
+    let mut in_brackets = false;
+    while position < characters.len() {
+        let character = characters[position];
+        let start = position;
+        let token = match character {
+            c if c.is_whitespace() => {
+                position += 1;
+                continue;
+            },
+            c if c.is_ascii_digit() || c == '.' => {
+                while position < characters.len() && (characters[position].is_ascii_digit() || characters[position] == '.') {
+                    position += 1;
+                }
+                let text = characters[start..position].iter().collect::<String>();
+                let value = text.parse::<f64>().map_err(|_| ExpressionError::InvalidNumber(text.clone(), start))?;
+                tokens.push((Token::Number(value), start));
+                continue;
+            },
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                while position < characters.len() && (characters[position].is_ascii_alphanumeric() || characters[position] == '_') {
+                    position += 1;
+                }
+                tokens.push((Token::Name(characters[start..position].iter().collect()), start));
+                continue;
+            },
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' if in_brackets => Token::Star,
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '[' => {
+                in_brackets = true;
+                Token::OpenBracket
+            },
+            ']' => {
+                in_brackets = false;
+                Token::CloseBracket
+            },
+            ',' => Token::Comma,
+            c => return Err(ExpressionError::UnexpectedCharacter(c, start)),
+        };
+        tokens.push((token, start));
+        position += 1;
+    }
+    Ok(tokens)
+}
+
+//Parsing, evaluating and dropping an expression all recurse once per level of the tree, so deeper expressions are rejected rather than
+// overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 64;
+
+//Recursive descent parser. Lowest to highest precedence: + and -, * and /, unary -, then numbers, channels, functions and parentheses.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    //Each operator in a chain counts too, since a + b + c nests as (a + b) + c.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ExpressionError> {
+        let token = self.tokens.get(self.position).cloned().ok_or(ExpressionError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        let (token, position) = self.next()?;
+        if token != expected {
+            return Err(ExpressionError::UnexpectedToken(token.to_string(), position))
+        }
+        Ok(())
+    }
+
+    //Errors end the parse, so the depth only needs restoring on success.
+    fn nest(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH))
+        }
+        Ok(())
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        let mut expression = self.parse_product()?;
+        while let Some((Token::Operator(operator @ (Operator::Add | Operator::Subtract)), _)) = self.peek() {
+            let operator = *operator;
+            self.position += 1;
+            self.nest()?;
+            expression = Expression::Binary(Box::new(expression), operator, Box::new(self.parse_product()?));
+        }
+        self.depth = depth;
+        Ok(expression)
+    }
+
+    fn parse_product(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        let mut expression = self.parse_unary()?;
+        while let Some((Token::Operator(operator @ (Operator::Multiply | Operator::Divide)), _)) = self.peek() {
+            let operator = *operator;
+            self.position += 1;
+            self.nest()?;
+            expression = Expression::Binary(Box::new(expression), operator, Box::new(self.parse_unary()?));
+        }
+        self.depth = depth;
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ExpressionError> {
+        if let Some((Token::Operator(Operator::Subtract), _)) = self.peek() {
+            self.position += 1;
+            self.nest()?;
+            let expression = Expression::Negate(Box::new(self.parse_unary()?));
+            self.depth -= 1;
+            return Ok(expression)
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ExpressionError> {
+        let (token, position) = self.next()?;
+        match token {
+            Token::Number(value) => Ok(Expression::Number(value)),
+            Token::OpenParen => {
+                self.nest()?;
+                let expression = self.parse_sum()?;
+                self.expect(Token::CloseParen)?;
+                self.depth -= 1;
+                Ok(expression)
+            },
+            Token::Name(name) => {
+                if let Some(function) = Function::from_name(&name) {
+                    if let Some((Token::OpenParen, _)) = self.peek() {
+                        return self.parse_function(function, name)
+                    }
+                }
+                match self.parse_channel(name, position)? {
+                    Argument::Value(expression) => Ok(expression),
+                    Argument::Wildcard(channel) => Err(ExpressionError::WildcardOutsideFunction(channel.get_name().to_owned())),
+                }
+            },
+            token => Err(ExpressionError::UnexpectedToken(token.to_string(), position)),
+        }
+    }
+
+    fn parse_function(&mut self, function: Function, name: String) -> Result<Expression, ExpressionError> {
+        self.nest()?;
+        self.expect(Token::OpenParen)?;
+        let mut arguments = Vec::new();
+        if let Some((Token::CloseParen, _)) = self.peek() {
+            self.position += 1;
+        } else {
+            loop {
+                arguments.push(self.parse_argument()?);
+                let (token, position) = self.next()?;
+                match token {
+                    Token::Comma => continue,
+                    Token::CloseParen => break,
+                    token => return Err(ExpressionError::UnexpectedToken(token.to_string(), position)),
+                }
+            }
+        }
+        match function {
+            Function::Abs => {
+                if arguments.len() != 1 || !matches!(arguments[0], Argument::Value(_)) {
+                    return Err(ExpressionError::WrongArgumentCount(name, "1".to_owned(), arguments.len()))
+                }
+            },
+            _ => {
+                if arguments.is_empty() {
+                    return Err(ExpressionError::WrongArgumentCount(name, "at least 1".to_owned(), 0))
+                }
+            },
+        }
+        self.depth -= 1;
+        Ok(Expression::Function(function, arguments))
+    }
+
+    //Wildcards are only allowed directly inside a function call.
+    fn parse_argument(&mut self) -> Result<Argument, ExpressionError> {
+        if let (Some((Token::Name(name), position)), Some((Token::OpenBracket, _)), Some((Token::Star, _))) = (self.tokens.get(self.position), self.tokens.get(self.position + 1), self.tokens.get(self.position + 2)) {
+            let (name, position) = (name.clone(), *position);
+            self.position += 1;
+            return self.parse_channel(name, position)
+        }
+        Ok(Argument::Value(self.parse_sum()?))
+    }
+
+    fn parse_channel(&mut self, name: String, position: usize) -> Result<Argument, ExpressionError> {
+        let simple_channel = match name.as_str() {
+            "BatteryVoltage" => Some(AxisDataType::BatteryVoltage),
+            "BatteryAmps" => Some(AxisDataType::BatteryAmps),
+            "SolarWatts" => Some(AxisDataType::SolarWatts),
+            "LoadWatts" => Some(AxisDataType::LoadWatts),
+            "StateOfChargePercent" => Some(AxisDataType::StateOfChargePercent),
+            _ => None,
+        };
+        let indexed_channel = match name.as_str() {
+            "CellVoltage" => Some(IndexedChannel::CellVoltage),
+            "ControllerPanelVoltage" => Some(IndexedChannel::ControllerPanelVoltage),
+            "ControllerAmps" => Some(IndexedChannel::ControllerAmps),
+            "ControllerTemperatureF" => Some(IndexedChannel::ControllerTemperatureF),
+            _ => None,
+        };
+        let has_index = matches!(self.peek(), Some((Token::OpenBracket, _)));
+        match (simple_channel, indexed_channel) {
+            (Some(_), _) if has_index => Err(ExpressionError::UnexpectedIndex(name)),
+            (Some(channel), _) => Ok(Argument::Value(Expression::Channel(channel))),
+            (None, Some(_)) if !has_index => Err(ExpressionError::MissingIndex(name)),
+            (None, Some(channel)) => {
+                self.expect(Token::OpenBracket)?;
+                let argument = match self.next()? {
+                    (Token::Star, _) => Argument::Wildcard(channel),
+                    (Token::Number(index), position) => {
+                        if index.fract() != 0f64 || !(0f64..=u16::MAX as f64).contains(&index) {
+                            return Err(ExpressionError::InvalidNumber(index.to_string(), position))
+                        }
+                        Argument::Value(Expression::Channel(channel.get_axis_data_type(index as u16)))
+                    },
+                    (token, position) => return Err(ExpressionError::UnexpectedToken(token.to_string(), position)),
+                };
+                self.expect(Token::CloseBracket)?;
+                Ok(argument)
+            },
+            (None, None) => Err(ExpressionError::UnknownName(name, position)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str, line: &DataLine) -> Option<f64> {
+        Expression::parse(source).unwrap().evaluate(line)
+    }
+
+    fn get_line() -> DataLine {
+        //Lines are kept sorted for searching, so values have to go in through add_data.
+        let mut line = DataLine::from(0);
+        [
+            DataValue::SolarWatts(600.0),
+            DataValue::LoadWatts(200.0),
+            DataValue::CellVoltage { cell: 0, voltage: 3.25 },
+            DataValue::CellVoltage { cell: 1, voltage: 3.5 },
+            DataValue::CellVoltage { cell: 2, voltage: 3.0 },
+        ].into_iter().for_each(|value| line.add_data(value));
+        line
+    }
+
+    #[test]
+    fn precedence_and_unary_minus() {
+        let line = DataLine::from(0);
+        assert_eq!(evaluate("1 + 2 * 3", &line), Some(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3", &line), Some(9.0));
+        assert_eq!(evaluate("8 - 2 - 1", &line), Some(5.0));
+        assert_eq!(evaluate("12 / 2 / 3", &line), Some(2.0));
+        assert_eq!(evaluate("-2 * 3 + 10 / 4", &line), Some(-3.5));
+        assert_eq!(evaluate("--2 - -1", &line), Some(3.0));
+        assert_eq!(evaluate("SolarWatts - LoadWatts * 2", &get_line()), Some(200.0));
+    }
+
+    #[test]
+    fn functions_over_wildcards() {
+        let line = get_line();
+        assert_eq!(evaluate("max(CellVoltage[*])", &line), Some(3.5));
+        assert_eq!(evaluate("min(CellVoltage[*]) - CellVoltage[0]", &line), Some(-0.25));
+        assert_eq!(evaluate("count(CellVoltage[*])", &line), Some(3.0));
+        assert_eq!(evaluate("avg(CellVoltage[*], 3.25)", &line), Some(3.25));
+        assert_eq!(evaluate("abs(LoadWatts - SolarWatts)", &line), Some(400.0));
+        //No cells at all, so there's nothing to take the maximum of.
+        assert_eq!(evaluate("max(CellVoltage[*])", &DataLine::from(0)), None);
+        assert_eq!(evaluate("count(ControllerAmps[*])", &line), Some(0.0));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |source: &str| Expression::parse(source).unwrap_err();
+        assert_eq!(error("  "), ExpressionError::Empty);
+        assert_eq!(error("1 $ 2"), ExpressionError::UnexpectedCharacter('$', 2));
+        assert_eq!(error("1 2"), ExpressionError::UnexpectedToken("2".to_owned(), 2));
+        assert_eq!(error("1 +"), ExpressionError::UnexpectedEnd);
+        assert_eq!(error("1..2"), ExpressionError::InvalidNumber("1..2".to_owned(), 0));
+        assert_eq!(error("SolarWats + 1"), ExpressionError::UnknownName("SolarWats".to_owned(), 0));
+        assert_eq!(error("CellVoltage + 1"), ExpressionError::MissingIndex("CellVoltage".to_owned()));
+        assert_eq!(error("SolarWatts[0]"), ExpressionError::UnexpectedIndex("SolarWatts".to_owned()));
+        assert_eq!(error("CellVoltage[*] * 2"), ExpressionError::WildcardOutsideFunction("CellVoltage".to_owned()));
+        assert_eq!(error("abs(CellVoltage[*])"), ExpressionError::WrongArgumentCount("abs".to_owned(), "1".to_owned(), 1));
+        assert_eq!(error("sum()"), ExpressionError::WrongArgumentCount("sum".to_owned(), "at least 1".to_owned(), 0));
+    }
+
+    #[test]
+    fn non_finite_results_are_none() {
+        let line = get_line();
+        assert_eq!(evaluate("1 / 0", &line), None);
+        assert_eq!(evaluate("SolarWatts / (LoadWatts - LoadWatts)", &line), None);
+        assert_eq!(evaluate("0 / 0 + 1", &line), None);
+        //A missing channel is None too.
+        assert_eq!(evaluate("BatteryVoltage + 1", &line), None);
+    }
+
+    #[test]
+    fn nesting_depth_limit() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(evaluate(&nested(MAX_NESTING_DEPTH), &DataLine::from(0)), Some(1.0));
+        assert_eq!(Expression::parse(&nested(MAX_NESTING_DEPTH + 1)), Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH)));
+        //Long enough to overflow the stack if it were parsed.
+        assert_eq!(Expression::parse(&nested(100_000)), Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH)));
+        assert_eq!(Expression::parse(&format!("{}1", "-".repeat(100_000))), Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH)));
+        assert_eq!(Expression::parse(&format!("1{}", " + 1".repeat(100_000))), Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH)));
+        assert_eq!(Expression::parse(&format!("{}1{}", "abs(".repeat(100_000), ")".repeat(100_000))), Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH)));
+        //Function arguments don't add up, and an operator only adds one level.
+        assert!(Expression::parse(&format!("max({0}, {0}, {0})", nested(MAX_NESTING_DEPTH - 1))).is_ok());
+        assert!(Expression::parse(&format!("{0} * {0}", nested(MAX_NESTING_DEPTH - 1))).is_ok());
+    }
+}
\ No newline at end of file
@@ -1,7 +1,7 @@
 use std::cmp::Ordering;
 use serde::{Serialize, Deserialize};
 use time::{Date, Time};
-use crate::{solar_data::value::DataValue, graph::graph_axis::AxisDataType};
+use crate::{solar_data::{value::DataValue, expression::Expression, cell_balance::{calculate_cell_balance, calculate_cell_deviation}}, graph::graph_axis::AxisDataType};
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -39,8 +39,9 @@ impl DataLine {
 
     pub fn calculate_axis_data(&self, axis: AxisDataType) -> Option<f64> {
         match axis {
+            //Periodic time is folded after bucketing, see graph_fold.
             AxisDataType::Time 
-            | AxisDataType::PeriodicTime => Some(self.unix_time as f64),
+            | AxisDataType::PeriodicTime(_) => Some(self.unix_time as f64),
             AxisDataType::BatteryVoltage => {
               let search_result = self.search_data(&DataValue::BatteryVoltage(0.0)).cloned();
               match search_result {
@@ -140,10 +141,30 @@ impl DataLine {
                   None => None,
                 }
               },
-            // AxisDataType::Custom(s) => todo!(),
+            AxisDataType::SolarEnergy
+            | AxisDataType::LoadEnergy
+            | AxisDataType::SolarEnergyTotal
+            | AxisDataType::LoadEnergyTotal
+            | AxisDataType::BatteryCapacity
+            | AxisDataType::StateOfHealthPercent
+            | AxisDataType::PerformanceRatioPercent
+            | AxisDataType::ChargeStage(_) => None,
+            //These need the solar site from the settings. See solar_data::clear_sky.
+            AxisDataType::ExpectedSolarWatts
+            | AxisDataType::ExpectedControllerAmps(_) => None,
+            AxisDataType::CellSpread => calculate_cell_balance(self).map(|balance| balance.spread),
+            AxisDataType::HighestCell => calculate_cell_balance(self).map(|balance| balance.highest_cell as f64),
+            AxisDataType::LowestCell => calculate_cell_balance(self).map(|balance| balance.lowest_cell as f64),
+            AxisDataType::CellDeviation(cell) => calculate_cell_deviation(self, cell),
+            //The line only knows the derived channel's name. Use calculate_derived_data with the saved expression instead.
+            AxisDataType::Custom(_) => None,
           }
     }
 
+    pub fn calculate_derived_data(&self, expression: &Expression) -> Option<f64> {
+        expression.evaluate(self)
+    }
+
     // pub fn len(&self) -> usize {
     //     self.line.len()
     // }
@@ -181,7 +202,7 @@ impl PartialEq for DataLine {
 
 impl PartialOrd for DataLine {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
@@ -230,7 +251,7 @@ impl DataLineBuilder<private_state::NoDateSet, private_state::NoTimeSet> {
     pub fn set_time(self, time: Time) -> DataLineBuilder<private_state::NoDateSet, private_state::TimeIsSet>{
         DataLineBuilder {
             date: self.date,
-            time: time,
+            time,
             line: self.line,
             is_date_set: std::marker::PhantomData,
             is_time_set: std::marker::PhantomData,
@@ -242,7 +263,7 @@ impl DataLineBuilder<private_state::DateIsSet, private_state::NoTimeSet> {
     pub fn set_time(self, time: Time) -> DataLineBuilder<private_state::DateIsSet, private_state::TimeIsSet>{
         DataLineBuilder {
             date: self.date,
-            time: time,
+            time,
             line: self.line,
             is_date_set: std::marker::PhantomData,
             is_time_set: std::marker::PhantomData,
@@ -253,7 +274,7 @@ impl DataLineBuilder<private_state::DateIsSet, private_state::NoTimeSet> {
 impl DataLineBuilder<private_state::NoDateSet, private_state::TimeIsSet> {
     pub fn set_date(self, date: Date) -> DataLineBuilder<private_state::DateIsSet, private_state::TimeIsSet>{
         DataLineBuilder {
-            date: date,
+            date,
             time: self.time,
             line: self.line,
             is_date_set: std::marker::PhantomData,
@@ -4,19 +4,11 @@ use crate::{solar_data::line::DataLine};
 
 
 //Note that PartialEq implementation might be a bit slow here, as it will test for equality for all datalines. For large data sets, this may be slow.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct DataStorage {
     pub data: Vec<DataLine>,
 }
 
-impl Default for DataStorage {
-    fn default() -> Self {
-        Self {
-            data: Default::default(),
-        }
-    }
-}
-
 impl DataStorage {
     pub fn push_data_line(&mut self, line: DataLine) {
         match self.data.binary_search(&line) {
@@ -44,4 +36,8 @@ impl DataStorage {
     pub fn len(&self) -> usize {
         self.data.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,316 @@
+use std::cmp::Ordering;
+
+use serde::{Serialize, Deserialize};
+use time::OffsetDateTime;
+
+use crate::{graph::{graph_aggregation::get_bucket_start, graph_axis::AxisDataType, graph_state_request::Resolution}, solar_data::{energy::integrate_energy, line::DataLine, storage::DataStorage, value::DataValue}};
+
+
+//One row of the daily summary report. Values that had no data on that day are None.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct DailySummary {
+    //Unix time of the start of the day.
+    pub day: i64,
+    pub solar_kwh: f64,
+    pub load_kwh: f64,
+    pub net_kwh: f64,
+    pub min_state_of_charge: Option<f64>,
+    pub max_state_of_charge: Option<f64>,
+    pub min_battery_voltage: Option<f64>,
+    pub max_battery_voltage: Option<f64>,
+    pub peak_solar_watts: Option<f64>,
+    pub max_controller_temperature_f: Option<f64>,
+    //The largest difference between the highest and lowest cell voltage seen at one time.
+    pub max_cell_spread: Option<f64>,
+    //Number of times a new non-zero alarm code showed up.
+    pub alarm_count: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum SummaryColumn {
+    Day,
+    SolarKwh,
+    LoadKwh,
+    NetKwh,
+    MinStateOfCharge,
+    MaxStateOfCharge,
+    MinBatteryVoltage,
+    MaxBatteryVoltage,
+    PeakSolarWatts,
+    MaxControllerTemperatureF,
+    MaxCellSpread,
+    AlarmCount,
+}
+
+impl SummaryColumn {
+    //All columns, in the order they are displayed and exported.
+    pub fn get_all_columns() -> Vec<SummaryColumn> {
+        vec![
+            SummaryColumn::Day,
+            SummaryColumn::SolarKwh,
+            SummaryColumn::LoadKwh,
+            SummaryColumn::NetKwh,
+            SummaryColumn::MinStateOfCharge,
+            SummaryColumn::MaxStateOfCharge,
+            SummaryColumn::MinBatteryVoltage,
+            SummaryColumn::MaxBatteryVoltage,
+            SummaryColumn::PeakSolarWatts,
+            SummaryColumn::MaxControllerTemperatureF,
+            SummaryColumn::MaxCellSpread,
+            SummaryColumn::AlarmCount,
+        ]
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SummaryColumn::Day => "Day",
+            SummaryColumn::SolarKwh => "Solar kWh",
+            SummaryColumn::LoadKwh => "Load kWh",
+            SummaryColumn::NetKwh => "Net kWh",
+            SummaryColumn::MinStateOfCharge => "Min S.O.C. %",
+            SummaryColumn::MaxStateOfCharge => "Max S.O.C. %",
+            SummaryColumn::MinBatteryVoltage => "Min Battery Voltage",
+            SummaryColumn::MaxBatteryVoltage => "Max Battery Voltage",
+            SummaryColumn::PeakSolarWatts => "Peak Solar Watts",
+            SummaryColumn::MaxControllerTemperatureF => "Max Controller °F",
+            SummaryColumn::MaxCellSpread => "Max Cell Spread",
+            SummaryColumn::AlarmCount => "Alarms",
+        }
+    }
+}
+
+impl DailySummary {
+    fn new(day: i64) -> Self {
+        DailySummary {
+            day,
+            solar_kwh: 0f64,
+            load_kwh: 0f64,
+            net_kwh: 0f64,
+            min_state_of_charge: None,
+            max_state_of_charge: None,
+            min_battery_voltage: None,
+            max_battery_voltage: None,
+            peak_solar_watts: None,
+            max_controller_temperature_f: None,
+            max_cell_spread: None,
+            alarm_count: 0,
+        }
+    }
+
+    pub fn get_value(&self, column: SummaryColumn) -> Option<f64> {
+        match column {
+            SummaryColumn::Day => Some(self.day as f64),
+            SummaryColumn::SolarKwh => Some(self.solar_kwh),
+            SummaryColumn::LoadKwh => Some(self.load_kwh),
+            SummaryColumn::NetKwh => Some(self.net_kwh),
+            SummaryColumn::MinStateOfCharge => self.min_state_of_charge,
+            SummaryColumn::MaxStateOfCharge => self.max_state_of_charge,
+            SummaryColumn::MinBatteryVoltage => self.min_battery_voltage,
+            SummaryColumn::MaxBatteryVoltage => self.max_battery_voltage,
+            SummaryColumn::PeakSolarWatts => self.peak_solar_watts,
+            SummaryColumn::MaxControllerTemperatureF => self.max_controller_temperature_f,
+            SummaryColumn::MaxCellSpread => self.max_cell_spread,
+            SummaryColumn::AlarmCount => Some(self.alarm_count as f64),
+        }
+    }
+
+    //The value as it is shown in the table and written to csv. Missing values are empty.
+    pub fn get_formatted_value(&self, column: SummaryColumn) -> String {
+        match column {
+            SummaryColumn::Day => format_day(self.day),
+            SummaryColumn::AlarmCount => self.alarm_count.to_string(),
+            SummaryColumn::MaxCellSpread => self.max_cell_spread.map(|spread| format!("{:.3}", spread)).unwrap_or_default(),
+            column => self.get_value(column).map(|value| format!("{:.2}", value)).unwrap_or_default(),
+        }
+    }
+
+    fn add_line(&mut self, line: &DataLine) {
+        update_min(&mut self.min_state_of_charge, line.calculate_axis_data(AxisDataType::StateOfChargePercent));
+        update_max(&mut self.max_state_of_charge, line.calculate_axis_data(AxisDataType::StateOfChargePercent));
+        update_min(&mut self.min_battery_voltage, line.calculate_axis_data(AxisDataType::BatteryVoltage));
+        update_max(&mut self.max_battery_voltage, line.calculate_axis_data(AxisDataType::BatteryVoltage));
+        update_max(&mut self.peak_solar_watts, line.calculate_axis_data(AxisDataType::SolarWatts));
+
+        let mut cell_range: Option<(f64, f64)> = None;
+        line.line.iter().for_each(|value| {
+            match value {
+                DataValue::ControllerTemperatureF { controller: _, temperature } => {
+                    update_max(&mut self.max_controller_temperature_f, Some(*temperature as f64));
+                },
+                DataValue::CellVoltage { cell: _, voltage } => {
+                    let voltage = *voltage as f64;
+                    cell_range = match cell_range {
+                        Some((low, high)) => Some((low.min(voltage), high.max(voltage))),
+                        None => Some((voltage, voltage)),
+                    };
+                },
+                _ => {},
+            }
+        });
+        update_max(&mut self.max_cell_spread, cell_range.map(|(low, high)| high - low));
+    }
+}
+
+//Builds one summary row per day of data, in ascending day order.
+pub fn get_daily_summaries(storage: &DataStorage) -> Vec<DailySummary> {
+    let resolution = Resolution::OneDay;
+    let mut summaries: Vec<DailySummary> = Vec::new();
+    let mut previous_alarm_code = 0;
+    storage.data.iter().for_each(|line| {
+        let day = get_bucket_start(line.unix_time, &resolution);
+        if summaries.last().map(|summary| summary.day) != Some(day) {
+            summaries.push(DailySummary::new(day));
+        }
+        let summary = summaries.last_mut().expect("A summary for this day was just pushed");
+        summary.add_line(line);
+
+        if let Some(DataValue::AlarmCode(alarm_code)) = line.search_data(&DataValue::AlarmCode(0)) {
+            if *alarm_code != 0 && *alarm_code != previous_alarm_code {
+                summary.alarm_count += 1;
+            }
+            previous_alarm_code = *alarm_code;
+        }
+    });
+
+    let power_points = |power_data_type: AxisDataType| {
+        storage.data.iter().filter_map(move |line| {
+            line.calculate_axis_data(power_data_type.clone()).map(|watts| (line.unix_time, watts))
+        })
+    };
+    integrate_energy(power_points(AxisDataType::SolarWatts), &resolution).into_iter().for_each(|(day, kwh)| {
+        if let Ok(index) = summaries.binary_search_by_key(&day, |summary| summary.day) {
+            summaries[index].solar_kwh = kwh;
+        }
+    });
+    integrate_energy(power_points(AxisDataType::LoadWatts), &resolution).into_iter().for_each(|(day, kwh)| {
+        if let Ok(index) = summaries.binary_search_by_key(&day, |summary| summary.day) {
+            summaries[index].load_kwh = kwh;
+        }
+    });
+    summaries.iter_mut().for_each(|summary| {
+        summary.net_kwh = summary.solar_kwh - summary.load_kwh;
+    });
+    summaries
+}
+
+//Sorts by the given column. Missing values always go last, whichever the direction.
+pub fn sort_daily_summaries(summaries: &mut [DailySummary], column: SummaryColumn, ascending: bool) {
+    summaries.sort_by(|a, b| {
+        match (a.get_value(column), b.get_value(column)) {
+            (Some(a), Some(b)) => {
+                if ascending {
+                    a.total_cmp(&b)
+                } else {
+                    b.total_cmp(&a)
+                }
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    });
+}
+
+pub fn daily_summaries_to_csv(summaries: &[DailySummary]) -> String {
+    let columns = SummaryColumn::get_all_columns();
+    let mut csv = columns.iter().map(|column| column.get_name()).collect::<Vec<_>>().join(",");
+    csv.push('\n');
+    summaries.iter().for_each(|summary| {
+        csv.push_str(&columns.iter().map(|column| summary.get_formatted_value(*column)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    });
+    csv
+}
+
+pub fn format_day(day: i64) -> String {
+    match OffsetDateTime::from_unix_timestamp(day) {
+        Ok(date_time) => {
+            let date = date_time.date();
+            format!("{}-{:02}-{:02}", date.year(), date.month() as u8, date.day())
+        },
+        Err(_) => day.to_string(),
+    }
+}
+
+fn update_min(current: &mut Option<f64>, value: Option<f64>) {
+    if let Some(value) = value {
+        *current = Some(current.map_or(value, |current| current.min(value)));
+    }
+}
+
+fn update_max(current: &mut Option<f64>, value: Option<f64>) {
+    if let Some(value) = value {
+        *current = Some(current.map_or(value, |current| current.max(value)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_line(unix_time: i64, alarm_code: i32, battery_voltage: f32, state_of_charge: f32) -> DataLine {
+        //Lines are kept sorted for searching, so values have to go in through add_data.
+        let mut line = DataLine::from(unix_time);
+        [
+            DataValue::AlarmCode(alarm_code),
+            DataValue::BatteryVoltage(battery_voltage),
+            DataValue::SolarWatts(1200.0),
+            DataValue::LoadWatts(0.0),
+            DataValue::StateOfChargePercent(state_of_charge),
+        ].into_iter().for_each(|value| line.add_data(value));
+        line
+    }
+
+    #[test]
+    fn grouped_by_day() {
+        //Two lines either side of midnight. The line exactly at midnight starts the new day.
+        let storage = DataStorage { data: vec![
+            get_line(86400 - 600, 0, 13.0, 80.0),
+            get_line(86400 - 300, 3, 13.5, 85.0),
+            get_line(86400, 3, 12.75, 84.0),
+            get_line(86400 + 300, 0, 12.5, 83.0),
+        ] };
+        let summaries = get_daily_summaries(&storage);
+        assert_eq!(summaries.iter().map(|summary| summary.day).collect::<Vec<_>>(), vec![0, 86400]);
+        assert_eq!((summaries[0].min_battery_voltage, summaries[0].max_battery_voltage), (Some(13.0), Some(13.5)));
+        assert_eq!((summaries[1].min_state_of_charge, summaries[1].max_state_of_charge), (Some(83.0), Some(84.0)));
+        //The alarm carried over midnight isn't counted again.
+        assert_eq!((summaries[0].alarm_count, summaries[1].alarm_count), (1, 0));
+        //The interval ending at midnight belongs to the day before it.
+        assert!((summaries[0].solar_kwh - 0.2).abs() < 1e-9 && (summaries[1].solar_kwh - 0.1).abs() < 1e-9);
+        assert_eq!(summaries[1].net_kwh, summaries[1].solar_kwh - summaries[1].load_kwh);
+        assert_eq!(summaries[0].peak_solar_watts, Some(1200.0));
+    }
+
+    #[test]
+    fn missing_values_sort_last() {
+        let mut summaries = [Some(2.0), None, Some(1.0), Some(3.0)].iter().enumerate().map(|(day, peak_solar_watts)| {
+            DailySummary { peak_solar_watts: *peak_solar_watts, ..DailySummary::new(day as i64) }
+        }).collect::<Vec<_>>();
+        let peaks = |summaries: &[DailySummary]| summaries.iter().map(|summary| summary.peak_solar_watts).collect::<Vec<_>>();
+        sort_daily_summaries(&mut summaries, SummaryColumn::PeakSolarWatts, true);
+        assert_eq!(peaks(&summaries), vec![Some(1.0), Some(2.0), Some(3.0), None]);
+        sort_daily_summaries(&mut summaries, SummaryColumn::PeakSolarWatts, false);
+        assert_eq!(peaks(&summaries), vec![Some(3.0), Some(2.0), Some(1.0), None]);
+    }
+
+    #[test]
+    fn csv_format() {
+        let summary = DailySummary {
+            solar_kwh: 1.5,
+            load_kwh: 0.5,
+            net_kwh: 1.0,
+            min_state_of_charge: Some(20.0),
+            max_state_of_charge: Some(95.0),
+            max_cell_spread: Some(0.012),
+            alarm_count: 2,
+            ..DailySummary::new(86400)
+        };
+        let csv = daily_summaries_to_csv(&[summary]);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "Day,Solar kWh,Load kWh,Net kWh,Min S.O.C. %,Max S.O.C. %,Min Battery Voltage,Max Battery Voltage,Peak Solar Watts,Max Controller °F,Max Cell Spread,Alarms");
+        assert_eq!(lines[1], "1970-01-02,1.50,0.50,1.00,20.00,95.00,,,,,0.012,2");
+        assert_eq!(lines.len(), 2);
+    }
+}
@@ -14,7 +14,7 @@ impl<T> FixedSizeQueue<T> {
         } else {
             size
         };
-        FixedSizeQueue { size: size, elements: Vec::with_capacity(size) }
+        FixedSizeQueue { size, elements: Vec::with_capacity(size) }
     }
 
     // A reverse order push function
@@ -32,6 +32,10 @@ impl<T> FixedSizeQueue<T> {
         self.elements.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
     pub fn retain<F>(&mut self, f: F)
     where
         F: FnMut(&T) -> bool
@@ -49,7 +53,7 @@ impl<T> FixedSizeQueue<T> {
         popped_value
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         self.elements.iter()
     }
 }
\ No newline at end of file
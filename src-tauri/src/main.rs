@@ -3,38 +3,100 @@
     windows_subsystem = "windows"
 )]
 
-use std::sync::{Mutex, OnceLock};
+use std::{sync::{Mutex, MutexGuard, OnceLock}, path::PathBuf, collections::HashMap};
 
-use shared::{parse::{live_data::LiveData, stored_data::StoredData, traits::TryParse, utils::ParseCompleteReturnValue}, solar_data::{storage::DataStorage, line::DataLine, controllers::AvailableControllers, cell::AvailableCells, value::DataValue}, graph::{graph_axis::{LineSeriesHolder, LineSeriesData, AxisDataType, AxisDataOption, LineSeriesAxisData}, graph_state_request::{GraphStateRequest, Resolution}}};
+use shared::{parse::{live_data::LiveData, stored_data::StoredData, traits::TryParse, utils::{ParseCompleteReturnValue, ImportProgress, ImportState, ImportRegistry}}, solar_data::{storage::DataStorage, line::DataLine, controllers::AvailableControllers, cell::AvailableCells, value::DataValue, energy::{integrate_energy, cumulative_energy, MAX_INTEGRATION_GAP}, summary::{DailySummary, get_daily_summaries}, expression::{DerivedChannel, Expression}, cell_balance::{CellBalanceRow, get_cell_balance_summary}, capacity::{estimate_capacity, get_monthly_capacity}, cycles::{CycleSummary, count_cycles, get_cycle_summary}, clear_sky::{SolarSite, get_daily_performance}, alerts::{AlertRule, AlertEvent, evaluate_alert_rules}, anomalies::{Anomaly, detect_anomalies}, charge_stage::{ChargeStageDay, classify_charge_stages, get_charge_stage_days}}, graph::{graph_axis::{LineSeriesHolder, LineSeriesData, AxisDataType, AxisDataOption, LineSeriesAxisData, AxisTimeRequest, AxisControlsRequest}, graph_state_request::{GraphStateRequest, Resolution}, graph_export::{GraphExportRequest, GraphExportReturnValue, export_graph}, graph_aggregation::{aggregate_axis_data, join_on_buckets, Aggregator, BucketFillPolicy}, graph_fold::{PeriodicDisplay, fold_periods, get_envelope}, graph_histogram::{HistogramEdges, get_histogram}, graph_type::{GraphType, HistogramOptions}, graph_transform::SeriesTransform, graph_comparison::{get_frame_name, shift_series}, graph_transport::encode_line_series}};
+use shared::settings::Settings;
+use serde::{Serialize, de::DeserializeOwned};
+use shared::command_error::CommandError;
 use tauri::{AppHandle, Manager};
 
 static DATA: OnceLock<Mutex<DataStorage>> = OnceLock::new();
 static AVAILABLE_CELLS: OnceLock<Mutex<AvailableCells>> = OnceLock::new();
 static AVAILABLE_CONTROLLERS: OnceLock<Mutex<AvailableControllers>> = OnceLock::new();
+static SETTINGS: OnceLock<Mutex<Settings>> = OnceLock::new();
+//The newest data request of each graph. Older requests from the same graph are superseded and stop at their next check.
+static LATEST_DATA_REQUESTS: OnceLock<Mutex<HashMap<String, u32>>> = OnceLock::new();
+//Imports in progress, and whether each has been asked to stop.
+static IMPORTS: OnceLock<Mutex<ImportRegistry>> = OnceLock::new();
+//How many lines are parsed between progress events, which is also how often an import checks whether it was cancelled.
+const IMPORT_PROGRESS_INTERVAL: usize = 5000;
 
 fn main() {
     DATA.get_or_init(|| {Mutex::from(DataStorage::default())}); 
     AVAILABLE_CELLS.get_or_init(|| {Mutex::from(AvailableCells::default())});
     AVAILABLE_CONTROLLERS.get_or_init(|| {Mutex::from(AvailableControllers::default())});
+    SETTINGS.get_or_init(|| {Mutex::from(Settings::default())});
+    LATEST_DATA_REQUESTS.get_or_init(|| {Mutex::from(HashMap::new())});
+    IMPORTS.get_or_init(|| {Mutex::from(ImportRegistry::default())});
 
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![parse_solar_data, retrieve_solar_data])
+        .setup(|app| {
+          *lock(&SETTINGS)? = load_settings(&app.handle());
+          Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![parse_solar_data, cancel_import, retrieve_solar_data, cancel_data_request, export_graph_image, retrieve_daily_summary, retrieve_cell_balance_summary, retrieve_cycle_summary, 
+          retrieve_settings, save_derived_channel, remove_derived_channel, save_nominal_capacity, save_solar_site, 
+          save_alert_rule, remove_alert_rule, retrieve_alerts, retrieve_anomalies, retrieve_charge_stage_summary])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+//A lock is only poisoned if something panicked while holding it. Commands don't panic, so that would be a bug elsewhere, and the value
+// is still served rather than failing every command after it.
+fn lock<T>(value: &OnceLock<Mutex<T>>) -> Result<MutexGuard<'_, T>, CommandError> {
+  let mutex = value.get().ok_or(CommandError::NotInitialized)?;
+  Ok(mutex.lock().unwrap_or_else(|poisoned| {
+    mutex.clear_poison();
+    poisoned.into_inner()
+  }))
+}
+
+fn emit<T: Serialize>(app: &AppHandle, event: &str, payload: &T) -> Result<(), CommandError> {
+  let payload = serde_json::to_string(payload).map_err(|e| CommandError::Serialization(e.to_string()))?;
+  app.emit_all(event, payload).map_err(|e| CommandError::Emit(event.to_owned(), e.to_string()))
+}
+
+fn parse_request<T: DeserializeOwned>(request: &str) -> Result<T, CommandError> {
+  serde_json::from_str::<T>(request).map_err(|e| CommandError::InvalidRequest(e.to_string()))
+}
+
+//Parses a file and merges it into the data. Progress goes out as import_progress events, ending with one that says how the import
+// finished. A cancelled import stops before anything is merged, so the data either has all of a file or none of it.
 #[tauri::command(async)]
-fn parse_solar_data(name: String, data: String, app: AppHandle) {
+fn parse_solar_data(name: String, data: String, import_id: u32, app: AppHandle) -> Result<(), CommandError> {
+    let handle = ImportHandle::register(import_id)?;
+    let mut progress = ImportProgress::new(import_id, name, data.len());
+    let result = import_solar_data(&data, &handle, &mut progress, &app);
+    progress.state = match &result {
+      Ok(Some(_)) => ImportState::Complete,
+      Ok(None) => ImportState::Cancelled,
+      Err(_) => ImportState::Failed,
+    };
+    //Sent however the import ended, so the progress bar never outlives it.
+    let progress_emitted = emit(&app, "import_progress", &progress);
+    if let Some(payload) = result? {
+      emit(&app, "solar_parse_complete", &payload)?;
+    }
+    progress_emitted
+}
+
+//Returns None if the import was cancelled.
+fn import_solar_data(data: &str, handle: &ImportHandle, progress: &mut ImportProgress, app: &AppHandle) -> Result<Option<ParseCompleteReturnValue>, CommandError> {
     let mut live_data = LiveData::default();
     let mut stored_data = StoredData::default();
     let mut controller_ids = AvailableControllers::default();
     let mut cell_ids = AvailableCells::default();
+    emit(app, "import_progress", progress)?;
     for line in data.split('\r') {
         //file string returned by javascript uses carriage return newline delimeters for some reason...
         let whole_line = line.to_owned();
         let line: Vec<&str> = whole_line.split(',').map(|x| x.trim()).collect();
         let live_data_data_line = live_data.try_parse(&line);
         let stored_data_data_line = stored_data.try_parse(&line);
+        if live_data_data_line.is_err() && stored_data_data_line.is_err() && !whole_line.trim().is_empty() {
+          progress.lines_rejected += 1;
+        }
         if let Ok(good_data_line) = live_data_data_line {
           good_data_line.line.iter().for_each(|datavalue| {
             match datavalue {
@@ -61,275 +123,506 @@ fn parse_solar_data(name: String, data: String, app: AppHandle) {
         if let Ok(good_data_line) = stored_data_data_line {
           stored_data.data.push_data_line(good_data_line);
         } 
+        //The delimiter was split off, but it was still read.
+        progress.bytes_processed = (progress.bytes_processed + whole_line.len() + 1).min(progress.total_bytes);
+        progress.lines_processed += 1;
+        //is_multiple_of needs a newer Rust than the rust-version in Cargo.toml.
+        #[allow(clippy::manual_is_multiple_of)]
+        if progress.lines_processed % IMPORT_PROGRESS_INTERVAL == 0 {
+          if handle.is_cancelled()? {
+            return Ok(None)
+          }
+          emit(app, "import_progress", progress)?;
+        }
     }
     let mut combined_data = DataStorage::default();
     combined_data.combine_data(&live_data.data);
     combined_data.combine_data(&stored_data.data);
-    let mut data_guard = DATA.get().expect("Value set at program init").lock().unwrap();
+    //Individual bad lines are skipped, but a file with no good lines at all is probably not a log file.
+    if combined_data.data.is_empty() {
+      return Err(CommandError::NoData(progress.name.clone()))
+    }
+    //The last chance to stop. Past here the import always finishes.
+    if handle.is_cancelled()? {
+      return Ok(None)
+    }
+    //Only the new data is checked here, so each import only notifies about what it brought in. retrieve_alerts checks everything.
+    let alert_rules = lock(&SETTINGS)?.alert_rules.clone();
+    let alert_events = evaluate_alert_rules(&alert_rules, &combined_data.data);
+    if !alert_events.is_empty() {
+      emit(app, "alerts_triggered", &alert_events)?;
+    }
+    //Every lock is taken before anything changes, so a failure can't leave the data merged without its cells and controllers.
+    let mut data_guard = lock(&DATA)?;
+    let mut cell_guard = lock(&AVAILABLE_CELLS)?;
+    let mut controller_guard = lock(&AVAILABLE_CONTROLLERS)?;
     data_guard.combine_data(&combined_data);
-    let mut cell_guard = AVAILABLE_CELLS.get().expect("Value set at program init").lock().unwrap();
     cell_guard.combine(&cell_ids);
-    let mut controller_guard = AVAILABLE_CONTROLLERS.get().expect("Value set at program init").lock().unwrap();
     controller_guard.combine(&controller_ids);
-    let payload = ParseCompleteReturnValue { 
-      name, 
+    Ok(Some(ParseCompleteReturnValue { 
+      name: progress.name.clone(), 
       cell_ids: cell_guard.clone(),
       controller_ids: controller_guard.clone(),
-    };
-    let payload = serde_json::to_string(&payload).unwrap();
+    }))
+}
+
+//An import in progress, registered so it can be cancelled.
+struct ImportHandle {
+  import_id: u32,
+}
+
+impl ImportHandle {
+  fn register(import_id: u32) -> Result<Self, CommandError> {
+    lock(&IMPORTS)?.register(import_id);
+    Ok(Self { import_id })
+  }
+
+  fn is_cancelled(&self) -> Result<bool, CommandError> {
+    Ok(lock(&IMPORTS)?.is_cancelled(self.import_id))
+  }
+}
+
+impl Drop for ImportHandle {
+  fn drop(&mut self) {
+    if let Ok(mut imports) = lock(&IMPORTS) {
+      imports.finish(self.import_id);
+    }
+  }
+}
+
+//The import stops the next time it checks in. One that has already finished, or started merging, is left alone.
+#[tauri::command]
+fn cancel_import(import_id: u32) -> Result<(), CommandError> {
+  //Kept if the import hasn't registered yet, so that it stops as soon as it does. See ImportRegistry.
+  lock(&IMPORTS)?.cancel(import_id);
+  Ok(())
+}
+
+//One graph's data request, which checks in between series whether it is still wanted.
+struct DataRequestHandle {
+  graph_id: String,
+  request_id: u32,
+}
+
+impl DataRequestHandle {
+  //Replaces any request the graph already has in flight.
+  fn register(graph_id: String, request_id: u32) -> Result<Self, CommandError> {
+    lock(&LATEST_DATA_REQUESTS)?.insert(graph_id.clone(), request_id);
+    Ok(Self { graph_id, request_id })
+  }
+
+  fn check(&self) -> Result<(), CommandError> {
+    match lock(&LATEST_DATA_REQUESTS)?.get(&self.graph_id) {
+      Some(latest) if *latest == self.request_id => Ok(()),
+      _ => Err(CommandError::Superseded(self.request_id)),
+    }
+  }
+}
+
+//The series are returned in the compact encoding. The request id lets the graph tell responses to requests it has since replaced apart from the current one.
+#[tauri::command(async)]
+fn retrieve_solar_data(graph_state_request: String, request_id: u32) -> Result<String, CommandError> {
+  let graph_state_request = parse_request::<GraphStateRequest>(&graph_state_request)?;
+  let handle = DataRequestHandle::register(graph_state_request.graph_id.clone(), request_id)?;
+  let data_guard = lock(&DATA)?;
+  //Requests queue up on the data lock, so one that was replaced while waiting stops here.
+  handle.check()?;
+  let time_frame = &graph_state_request.time_frame;
+  let slice = get_time_frame_slice(&data_guard.data, time_frame.start, time_frame.end);
 
-    app.emit_all("solar_parse_complete", payload).expect("Failed to emit event");
+  let series_data = match graph_state_request.get_graph_type() {
+    GraphType::Histogram(options) => get_histogram_series(slice, time_frame, &graph_state_request.y_axis.0, &options),
+    GraphType::Heatmap => get_heatmap_series(slice, time_frame, &graph_state_request.y_axis.0),
+    GraphType::XAxisLine | GraphType::XYScatter => {
+      let mut container = get_axis_line_series(slice, &graph_state_request, &handle)?;
+      //Compared periods are built the same way from their own slice of the data, then moved onto this time frame.
+      let comparison_frames = time_frame.get_comparison_frames();
+      if !comparison_frames.is_empty() {
+        let name = get_frame_name(time_frame.start, time_frame.end);
+        container.series.iter_mut().chain(container.secondary_series.iter_mut()).for_each(|series| {
+          series.name = format!("{} ({})", series.name, name);
+        });
+      }
+      for frame in comparison_frames.iter() {
+        let comparison = get_axis_line_series(get_time_frame_slice(&data_guard.data, frame.start, frame.end), &graph_state_request, &handle)?;
+        container.series.extend(comparison.series.into_iter().map(|series| shift_series(series, frame)));
+        container.secondary_series.extend(comparison.secondary_series.into_iter().map(|series| shift_series(series, frame)));
+      }
+      container
+    },
+  };
+  handle.check()?;
+  encode_line_series(&series_data).map_err(|e| CommandError::Serialization(e.to_string()))
+}
+
+//For graphs that go away with a request still in flight.
+#[tauri::command]
+fn cancel_data_request(graph_id: String) -> Result<(), CommandError> {
+  lock(&LATEST_DATA_REQUESTS)?.remove(&graph_id);
+  Ok(())
+}
+
+#[tauri::command(async)]
+fn export_graph_image(graph_export_request: String) -> Result<GraphExportReturnValue, CommandError> {
+  let graph_export_request = parse_request::<GraphExportRequest>(&graph_export_request)?;
+  //Rendering happens here rather than in the webview so that the bitmap backend has access to system fonts.
+  Ok(export_graph(&graph_export_request)?)
+}
+
+#[tauri::command(async)]
+fn retrieve_daily_summary() -> Result<Vec<DailySummary>, CommandError> {
+  Ok(get_daily_summaries(&*lock(&DATA)?))
+}
+
+#[tauri::command(async)]
+fn retrieve_cell_balance_summary() -> Result<Vec<CellBalanceRow>, CommandError> {
+  Ok(get_cell_balance_summary(&lock(&DATA)?.data))
+}
+
+#[tauri::command(async)]
+fn retrieve_cycle_summary() -> Result<CycleSummary, CommandError> {
+  Ok(get_cycle_summary(&count_cycles(&lock(&DATA)?.data)))
+}
+
+#[tauri::command(async)]
+fn retrieve_charge_stage_summary() -> Result<Vec<ChargeStageDay>, CommandError> {
+  let controllers = lock(&AVAILABLE_CONTROLLERS)?.get_controllers().clone();
+  Ok(get_charge_stage_days(&lock(&DATA)?.data, &controllers))
+}
+
+#[tauri::command(async)]
+fn retrieve_alerts() -> Result<Vec<AlertEvent>, CommandError> {
+  let alert_rules = lock(&SETTINGS)?.alert_rules.clone();
+  Ok(evaluate_alert_rules(&alert_rules, &lock(&DATA)?.data))
+}
+
+#[tauri::command(async)]
+fn retrieve_anomalies() -> Result<Vec<Anomaly>, CommandError> {
+  Ok(detect_anomalies(&*lock(&DATA)?))
 }
 
+#[tauri::command(async)]
+fn retrieve_settings() -> Result<Settings, CommandError> {
+  Ok(lock(&SETTINGS)?.clone())
+}
+
+#[tauri::command(async)]
+fn save_derived_channel(derived_channel: String, replace: bool, app: AppHandle) -> Result<(), CommandError> {
+  let derived_channel = parse_request::<DerivedChannel>(&derived_channel)?;
+  let mut settings_guard = lock(&SETTINGS)?;
+  let mut new_settings = settings_guard.clone();
+  new_settings.save_derived_channel(derived_channel, replace)?;
+  update_settings(&mut settings_guard, new_settings, &app)
+}
+
+#[tauri::command(async)]
+fn remove_derived_channel(name: String, app: AppHandle) -> Result<(), CommandError> {
+  let mut settings_guard = lock(&SETTINGS)?;
+  let mut new_settings = settings_guard.clone();
+  new_settings.remove_derived_channel(&name)?;
+  update_settings(&mut settings_guard, new_settings, &app)
+}
 
+#[tauri::command(async)]
+fn save_alert_rule(alert_rule: String, replace: bool, app: AppHandle) -> Result<(), CommandError> {
+  let alert_rule = parse_request::<AlertRule>(&alert_rule)?;
+  let mut settings_guard = lock(&SETTINGS)?;
+  let mut new_settings = settings_guard.clone();
+  new_settings.save_alert_rule(alert_rule, replace)?;
+  update_settings(&mut settings_guard, new_settings, &app)
+}
 
+#[tauri::command(async)]
+fn remove_alert_rule(name: String, app: AppHandle) -> Result<(), CommandError> {
+  let mut settings_guard = lock(&SETTINGS)?;
+  let mut new_settings = settings_guard.clone();
+  new_settings.remove_alert_rule(&name)?;
+  update_settings(&mut settings_guard, new_settings, &app)
+}
 
+#[tauri::command(async)]
+fn save_nominal_capacity(nominal_capacity_ah: Option<f64>, app: AppHandle) -> Result<(), CommandError> {
+  let mut settings_guard = lock(&SETTINGS)?;
+  let mut new_settings = settings_guard.clone();
+  new_settings.set_nominal_capacity(nominal_capacity_ah)?;
+  update_settings(&mut settings_guard, new_settings, &app)
+}
 
 #[tauri::command(async)]
-fn retrieve_solar_data(graph_state_request: String, app: AppHandle) {
-  let graph_state_request = serde_json::from_str::<GraphStateRequest>(&graph_state_request).unwrap();
-  let data_guard = DATA.get().unwrap().lock().unwrap();
-  let slice = &data_guard.data[
-    match data_guard.data.binary_search(&DataLine::from(graph_state_request.time_frame.start)) {
+fn save_solar_site(solar_site: String, app: AppHandle) -> Result<(), CommandError> {
+  let solar_site = parse_request::<Option<SolarSite>>(&solar_site)?;
+  let mut settings_guard = lock(&SETTINGS)?;
+  let mut new_settings = settings_guard.clone();
+  new_settings.set_solar_site(solar_site)?;
+  update_settings(&mut settings_guard, new_settings, &app)
+}
+
+//Writes the new settings to disk, and only keeps them if that worked, so memory and disk never disagree.
+fn update_settings(current_settings: &mut Settings, new_settings: Settings, app: &AppHandle) -> Result<(), CommandError> {
+  store_settings(&new_settings, app).map_err(|e| CommandError::SettingsStorage(e.to_string()))?;
+  *current_settings = new_settings;
+  emit(app, "settings_update", &*current_settings)
+}
+
+fn get_settings_path(app: &AppHandle) -> Option<PathBuf> {
+  app.path_resolver().app_config_dir().map(|directory| directory.join("settings.json"))
+}
+
+//Missing or unreadable settings fall back to the defaults rather than stopping the app from starting.
+fn load_settings(app: &AppHandle) -> Settings {
+  get_settings_path(app)
+    .and_then(|path| std::fs::read_to_string(path).ok())
+    .and_then(|contents| serde_json::from_str::<Settings>(&contents).ok())
+    .unwrap_or_default()
+}
+
+fn store_settings(settings: &Settings, app: &AppHandle) -> Result<(), std::io::Error> {
+  let path = get_settings_path(app).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No config directory available"))?;
+  if let Some(directory) = path.parent() {
+    std::fs::create_dir_all(directory)?;
+  }
+  let contents = serde_json::to_string_pretty(settings).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+  std::fs::write(path, contents)
+}
+
+
+fn get_time_frame_slice(data: &[DataLine], start: i64, end: i64) -> &[DataLine] {
+  &data[
+    match data.binary_search(&DataLine::from(start)) {
       Ok(index) => index,
       Err(index) => index,
     }..
-    match data_guard.data.binary_search(&DataLine::from(graph_state_request.time_frame.end)) {
+    match data.binary_search(&DataLine::from(end)) {
       Ok(index) => index,
       Err(index) => index,
     }
-  ]; 
-
-  let series_data = {
-    let mut container = LineSeriesHolder::default();
-    let resolution =  graph_state_request.time_frame.get_resolution();
-
-    graph_state_request.x_axis.requests.iter().cloned().for_each(|(x_data_type, x_data_option)| {
-      graph_state_request.y_axis.0.requests.iter().cloned().for_each(|(y_data_type, y_data_option)| {
-        //We know the x axis data type and current option, and we know the y_axis data type and current option. We have all we need to collect data.
-        let data = get_line_series_data(&slice, &resolution, &x_data_type, &x_data_option, &y_data_type, &y_data_option);
-        let name = generage_series_name(
-          &x_data_type, 
-          &x_data_option, 
-          &y_data_type, 
-          &y_data_option);
-
-        container.series.push(
-          LineSeriesData { 
-            name: name, 
-            data_points: data, 
-            x_axis: LineSeriesAxisData { data_type: x_data_type.clone(), data_option: x_data_option.clone() }, 
-            y_axis: LineSeriesAxisData { data_type: y_data_type, data_option: y_data_option },
-          });
-      });
+  ]
+}
 
-      //Secondary y axis
-      graph_state_request.y_axis.1.requests.iter().cloned().for_each(|(y_data_type, y_data_option)| {
-        //We know the x axis data type and current option, and we know the y_axis data type and current option. We have all we need to collect data.
-        let data = get_line_series_data(&slice, &resolution, &x_data_type, &x_data_option, &y_data_type, &y_data_option);
-        let name = generage_series_name(
-          &x_data_type, 
-          &x_data_option, 
-          &y_data_type, 
-          &y_data_option);
-
-
-        container.secondary_series.push(
-          LineSeriesData { 
-            name: name, 
-            data_points: data, 
-            x_axis: LineSeriesAxisData { data_type: x_data_type.clone(), data_option: x_data_option.clone() }, 
-            y_axis: LineSeriesAxisData { data_type: y_data_type, data_option: y_data_option },
-          });
-        });
-    });
-    container
-  };
-  // series_data.series.iter().for_each(|x| {
-  //   println!("Series name: {}, Data Points: {}", x.name.clone(), x.data_points.len());
-  // });
-  let line_series_holder = format!("{}\\{}", serde_json::to_string(&series_data).unwrap(), graph_state_request.graph_id);
-  app.emit_all("data_request_complete", line_series_holder).expect("Failed to emit event");
-}
-
-
-fn get_line_series_data(data: &[DataLine], resolution: &Resolution, x_axis_data_type: &AxisDataType, x_axis_data_option: &AxisDataOption, 
-                          y_axis_data_type: &AxisDataType, y_axis_data_option: &AxisDataOption) -> Vec<(f64, f64)> {
-  let data = data.iter().cloned().filter_map(|line| {
-    let time = line.unix_time;
-    let x = line.calculate_axis_data(x_axis_data_type.clone());
-    let y = line.calculate_axis_data(y_axis_data_type.clone());
-    match x {
-      Some(x) => match y {
-          Some(y) => {
-            Some((time, x, y))
-          },
-          None => None,
-        },
-      None => None,
+//Every pair of requested x and y channels, for both y axes.
+fn get_axis_line_series(slice: &[DataLine], graph_state_request: &GraphStateRequest, handle: &DataRequestHandle) -> Result<LineSeriesHolder, CommandError> {
+  let mut container = LineSeriesHolder::default();
+  let time_frame = &graph_state_request.time_frame;
+
+  for (x_data_type, x_data_option) in graph_state_request.x_axis.requests.iter() {
+    for (y_data_type, y_data_option) in graph_state_request.y_axis.0.requests.iter() {
+      //We know the x axis data type and current option, and we know the y_axis data type and current option. We have all we need to collect data.
+      handle.check()?;
+      let transform = graph_state_request.y_axis.0.get_transform(y_data_type, y_data_option);
+      container.series.extend(get_line_series(slice, time_frame, x_data_type, x_data_option, y_data_type, y_data_option, transform));
     }
-  }).fold((Vec::new(), Vec::new(), Vec::new()), |mut acc, element| {
-    acc.0.push(element.0);
-    acc.1.push(element.1);
-    acc.2.push(element.2);
-    acc
-  });
-
-  let x_vec = process_axis_data(data.0.iter().cloned().zip(data.1.into_iter()), resolution, x_axis_data_option);
-  let y_vec = process_axis_data(data.0.into_iter().zip(data.2.into_iter()), resolution, y_axis_data_option);
-
-  x_vec.into_iter().zip(y_vec.into_iter()).collect::<Vec<_>>()
-}
-
-
-
-fn process_axis_data<T>(mut data: T, resolution: &Resolution, axis_option: &AxisDataOption) -> Vec<f64> 
-where
-  T: Iterator<Item = (i64, f64)>
-{
-  let time_interval = resolution.get_timestamp_offset();
-
-  let processed_axis_data = match axis_option {
-    // AxisDataOptions::Sample => {
-    //   let mut last: i64 = 0;
-    //   let sampled_data = data.into_iter().filter_map(|current| {
-    //     if current.0 - last >= time_interval {
-    //       //Set the last value to the start of the current time "bucket"
-    //       last = current.0 - current.0 % time_interval;
-    //       Some(current.1)
-    //     } else {
-    //       None
-    //     }
-    //   }).collect::<Vec<_>>();
-    //   sampled_data
-    // },
-    AxisDataOption::Average => {
-      let series_data_average: Vec<f64> = if let Some(first) = data.nth(0) {
-        let mut storage = vec![first];
-        let mut average_data = data.filter_map(|current| {
-          if current.0 - storage.last().expect("Storage is never empty").0 >= time_interval {
-            let count = storage.len();
-            let sum = storage.drain(..).fold((0i64, 0f64), |acc, point| {
-              (acc.0 + point.0, acc.1 + point.1)
-            });
-            let average = sum.1 / count as f64;
-            storage.push((current.0 - current.0 % time_interval, current.1));
-            Some(average)
-          } else {
-            storage.push((current.0 - current.0 % time_interval, current.1));
-            None
-          }
-        }).collect::<Vec<_>>();
-        if storage.len() > 0 {
-          let count = storage.len();
-          let sum = storage.drain(..).fold((0i64, 0f64), |acc, point| {
-            (acc.0 + point.0, acc.1 + point.1)
-          });
-          let average = sum.1 / count as f64;
-          average_data.push(average);
-        }
-        average_data
-      } else {
-        Vec::new()
-      };
-      series_data_average
+
+    //Secondary y axis
+    for (y_data_type, y_data_option) in graph_state_request.y_axis.1.requests.iter() {
+      handle.check()?;
+      let transform = graph_state_request.y_axis.1.get_transform(y_data_type, y_data_option);
+      container.secondary_series.extend(get_line_series(slice, time_frame, x_data_type, x_data_option, y_data_type, y_data_option, transform));
+    }
+  }
+  Ok(container)
+}
+
+//Usually one series per pair of axes. Periodic time splits the data into one series per period, or into the min, mean and max of all periods.
+fn get_line_series(data: &[DataLine], time_frame: &AxisTimeRequest, x_axis_data_type: &AxisDataType, x_axis_data_option: &AxisDataOption, 
+                   y_axis_data_type: &AxisDataType, y_axis_data_option: &AxisDataOption, transform: Option<SeriesTransform>) -> Vec<LineSeriesData> {
+  let name = generage_series_name(x_axis_data_type, x_axis_data_option, y_axis_data_type, y_axis_data_option, transform.as_ref());
+  let x_axis = LineSeriesAxisData { data_type: x_axis_data_type.clone(), data_option: x_axis_data_option.clone() };
+  let y_axis = LineSeriesAxisData { data_type: y_axis_data_type.clone(), data_option: y_axis_data_option.clone() };
+
+  let period = match x_axis_data_type {
+    AxisDataType::PeriodicTime(period) => *period,
+    _ => {
+      let resolution = time_frame.get_resolution();
+      let (timestamps, data_points) = get_line_series_data(data, &resolution, &time_frame.fill_policy, (x_axis_data_type, x_axis_data_option), (y_axis_data_type, y_axis_data_option), transform);
+      return vec![LineSeriesData { name, data_points, timestamps, x_axis, y_axis }]
     },
-    AxisDataOption::Minimum => {
-      let series_data_minimum: Vec<f64> = if let Some(first) = data.nth(0) {
-        let mut storage = vec![first];
-        let mut minimum_data = data.filter_map(|current| {
-          if current.0 - storage.last().expect("Storage is never empty").0 >= time_interval {
-            let minimum = storage.drain(..).reduce(|acc, current| {
-              if current.1 < acc.1 {
-                current
-              } else {
-                acc
-              }
-            }).expect("Storage is never empty");
-            let minimum = minimum.1;
-            storage.push((current.0 - current.0 % time_interval, current.1));
-            Some(minimum)
-          } else {
-            storage.push((current.0 - current.0 % time_interval, current.1));
-            None
-          }
-        }).collect::<Vec<_>>();
-        if storage.len() > 0 {
-          let minimum = storage.drain(..).reduce(|acc, current| {
-            if current.1 < acc.1 {
-              current
-            } else {
-              acc
-            }
-          }).expect("Storage is proven non-empty");
-          let minimum = minimum.1;
-          minimum_data.push(minimum);
+  };
+
+  let resolution = time_frame.manual_resolution.clone().unwrap_or_else(|| period.get_default_resolution());
+  //Fold on the bucket start, so that the same bucket of every period lands on the same x value.
+  let (timestamps, data_points) = get_line_series_data(data, &resolution, &time_frame.fill_policy, (&AxisDataType::Time, &AxisDataOption::First), (y_axis_data_type, y_axis_data_option), transform);
+  let values = data_points.into_iter().map(|(_x, y)| y).collect::<Vec<_>>();
+  let periods = fold_periods(&timestamps, &values, period);
+  match time_frame.periodic_display {
+    PeriodicDisplay::Overlay => periods.into_iter().map(|folded_period| {
+      LineSeriesData {
+        name: format!("{} {}", name, period.get_period_name(folded_period.start)),
+        data_points: folded_period.data_points,
+        timestamps: folded_period.timestamps,
+        x_axis: x_axis.clone(),
+        y_axis: y_axis.clone(),
+      }
+    }).collect(),
+    PeriodicDisplay::Envelope => {
+      //Envelope points come from many periods at once, so there's no single timestamp to give them.
+      let envelope = get_envelope(&periods);
+      [("Min", envelope.minimum), ("Mean", envelope.mean), ("Max", envelope.maximum)].into_iter().map(|(statistic, data_points)| {
+        LineSeriesData {
+          name: format!("{}, {}", name, statistic),
+          data_points,
+          timestamps: Vec::new(),
+          x_axis: x_axis.clone(),
+          y_axis: y_axis.clone(),
         }
-        minimum_data
-      } else {
-        Vec::new()
-      };
-      series_data_minimum
+      }).collect()
     },
-    AxisDataOption::Maximum => {
-      let series_data_maximum: Vec<f64> = if let Some(first) = data.nth(0) {
-        let mut storage = vec![first];
-        let mut maximum_data = data.filter_map(|current| {
-          if current.0 - storage.last().expect("Storage is never empty").0 >= time_interval {
-            let maximum = storage.drain(..).reduce(|acc, current| {
-              if current.1 > acc.1 {
-                current
-              } else {
-                acc
-              }
-            }).expect("Storage is never empty");
-            let maximum = maximum.1;
-            storage.push((current.0 - current.0 % time_interval, current.1));
-            Some(maximum)
-          } else {
-            storage.push((current.0 - current.0 % time_interval, current.1));
-            None
-          }
-        }).collect::<Vec<_>>();
-        if storage.len() > 0 {
-          let maximum = storage.drain(..).reduce(|acc, current| {
-            if current.1 < acc.1 {
-              current
-            } else {
-              acc
-            }
-          }).expect("Storage is proven non-empty");
-          let maximum = maximum.1;
-          maximum_data.push(maximum);
-        }
-        maximum_data
+  }
+}
+
+//Histograms are built from the primary y axis channels only. All of the channels share the same bins, so they can be compared.
+fn get_histogram_series(data: &[DataLine], time_frame: &AxisTimeRequest, y_axis: &AxisControlsRequest, options: &HistogramOptions) -> LineSeriesHolder {
+  let resolution = time_frame.get_resolution();
+  let channels = y_axis.requests.iter().map(|(y_axis_data_type, y_axis_data_option)| {
+    let transform = y_axis.get_transform(y_axis_data_type, y_axis_data_option);
+    let (timestamps, data_points) = get_line_series_data(data, &resolution, &time_frame.fill_policy, (&AxisDataType::Time, &AxisDataOption::First), (y_axis_data_type, y_axis_data_option), transform);
+    let points = timestamps.into_iter().zip(data_points.into_iter().map(|(_x, y)| y)).collect::<Vec<_>>();
+    (y_axis_data_type, y_axis_data_option, transform, points)
+  }).collect::<Vec<_>>();
+
+  let mut container = LineSeriesHolder::default();
+  let edges = HistogramEdges::from_values(channels.iter().flat_map(|(_, _, _, points)| points.iter().map(|(_, value)| *value)), &options.bins);
+  if let Some(edges) = edges {
+    container.series = channels.into_iter().map(|(y_axis_data_type, y_axis_data_option, transform, points)| {
+      //A bucket lasts as long as the resolution. Raw samples last until the next sample, unless there's a gap in the data.
+      let max_duration = if y_axis_data_option.uses_buckets() {
+        resolution.get_timestamp_offset()
       } else {
-        Vec::new()
+        MAX_INTEGRATION_GAP
       };
-      series_data_maximum
-    },
+      let axis_data = LineSeriesAxisData { data_type: y_axis_data_type.clone(), data_option: y_axis_data_option.clone() };
+      LineSeriesData {
+        name: generage_series_name(&AxisDataType::Time, &AxisDataOption::First, y_axis_data_type, y_axis_data_option, transform.as_ref()),
+        data_points: get_histogram(&points, &edges, options.measure, max_duration),
+        timestamps: Vec::new(),
+        x_axis: axis_data.clone(),
+        y_axis: axis_data,
+      }
+    }).collect();
+  }
+  container
+}
+
+//A heatmap has one cell per hour, whatever the resolution, coloured by the first primary y axis channel.
+fn get_heatmap_series(data: &[DataLine], time_frame: &AxisTimeRequest, y_axis: &AxisControlsRequest) -> LineSeriesHolder {
+  let mut container = LineSeriesHolder::default();
+  if let Some((y_axis_data_type, y_axis_data_option)) = y_axis.requests.first() {
+    let transform = y_axis.get_transform(y_axis_data_type, y_axis_data_option);
+    let (timestamps, data_points) = get_line_series_data(data, &Resolution::OneHour, &time_frame.fill_policy, (&AxisDataType::Time, &AxisDataOption::First), (y_axis_data_type, y_axis_data_option), transform);
+    container.series.push(LineSeriesData {
+      name: generage_series_name(&AxisDataType::Time, &AxisDataOption::First, y_axis_data_type, y_axis_data_option, transform.as_ref()),
+      data_points: timestamps.iter().zip(data_points.iter()).map(|(timestamp, (_x, y))| (*timestamp as f64, *y)).collect(),
+      timestamps,
+      x_axis: LineSeriesAxisData { data_type: AxisDataType::Time, data_option: AxisDataOption::First },
+      y_axis: LineSeriesAxisData { data_type: y_axis_data_type.clone(), data_option: y_axis_data_option.clone() },
+    });
+  }
+  container
+}
+
+//Each axis is a channel and how it's aggregated.
+fn get_line_series_data(data: &[DataLine], resolution: &Resolution, fill_policy: &BucketFillPolicy, (x_axis_data_type, x_axis_data_option): (&AxisDataType, &AxisDataOption), 
+                          (y_axis_data_type, y_axis_data_option): (&AxisDataType, &AxisDataOption), transform: Option<SeriesTransform>) -> (Vec<i64>, Vec<(f64, f64)>) {
+  //Each axis is collected on its own, so a channel missing from some lines doesn't throw away the other channel's data.
+  let x_data = get_axis_points(data, resolution, x_axis_data_type);
+  let y_data = get_axis_points(data, resolution, y_axis_data_type);
+
+  let x_vec = aggregate_axis_data(x_data, resolution, x_axis_data_option);
+  let y_vec = aggregate_axis_data(y_data, resolution, y_axis_data_option);
+  //Transforms work on the bucketed values, so a window covers the same number of points whatever the resolution.
+  let y_vec = match transform {
+    Some(transform) => transform.apply(&y_vec),
+    None => y_vec,
+  };
+
+  //Pair the values up by time, rather than by position, so that every point comes from the same bucket.
+  join_on_buckets(&x_vec, x_axis_data_option.uses_buckets(), &y_vec, y_axis_data_option.uses_buckets(), resolution, fill_policy)
+    .into_iter()
+    .map(|(timestamp, x, y)| (timestamp, (x, y)))
+    .unzip()
+}
+
+
+
+//Every line that has a value for the axis. Energy has no per line value, it's integrated from power into one point per bucket.
+//Capacity is estimated over the whole range, and has one point per month. Performance ratio has one point per day.
+//Charge stages depend on how the voltage moved over the lines before, so they're classified over the whole range too.
+fn get_axis_points(data: &[DataLine], resolution: &Resolution, axis_data_type: &AxisDataType) -> Vec<(i64, f64)> {
+  let power_points = |power_data_type: AxisDataType| {
+    data.iter().filter_map(move |line| {
+      line.calculate_axis_data(power_data_type.clone()).map(|watts| (line.unix_time, watts))
+    })
   };
-  // println!("Processed axis has {} elements", processed_axis_data.len());
-  processed_axis_data
+  match axis_data_type {
+    AxisDataType::SolarEnergy => integrate_energy(power_points(AxisDataType::SolarWatts), resolution),
+    AxisDataType::LoadEnergy => integrate_energy(power_points(AxisDataType::LoadWatts), resolution),
+    AxisDataType::SolarEnergyTotal => cumulative_energy(&integrate_energy(power_points(AxisDataType::SolarWatts), resolution)),
+    AxisDataType::LoadEnergyTotal => cumulative_energy(&integrate_energy(power_points(AxisDataType::LoadWatts), resolution)),
+    AxisDataType::BatteryCapacity => {
+      get_monthly_capacity(&estimate_capacity(data), None).into_iter().map(|month| (month.month, month.capacity_ah)).collect()
+    },
+    AxisDataType::StateOfHealthPercent => {
+      let nominal_capacity_ah = lock(&SETTINGS).ok().and_then(|settings| settings.nominal_capacity_ah);
+      get_monthly_capacity(&estimate_capacity(data), nominal_capacity_ah).into_iter().filter_map(|month| {
+        month.state_of_health_percent.map(|state_of_health| (month.month, state_of_health))
+      }).collect()
+    },
+    AxisDataType::ExpectedSolarWatts
+    | AxisDataType::ExpectedControllerAmps(_)
+    | AxisDataType::PerformanceRatioPercent => {
+      //Without a site there's nothing to expect.
+      let solar_site = lock(&SETTINGS).ok().and_then(|settings| settings.solar_site.clone());
+      match (solar_site, axis_data_type) {
+        (Some(site), AxisDataType::ExpectedSolarWatts) => data.iter().filter_map(|line| {
+          site.get_expected_watts(line.unix_time).map(|watts| (line.unix_time, watts))
+        }).collect(),
+        (Some(site), AxisDataType::ExpectedControllerAmps(controller)) => data.iter().filter_map(|line| {
+          site.get_expected_controller_amps(line, *controller).map(|amps| (line.unix_time, amps))
+        }).collect(),
+        (Some(site), _) => get_daily_performance(data, &site).into_iter().map(|day| (day.day, day.performance_ratio_percent)).collect(),
+        (None, _) => Vec::new(),
+      }
+    },
+    AxisDataType::ChargeStage(controller) => {
+      classify_charge_stages(data, *controller).into_iter().map(|(unix_time, stage)| (unix_time, stage.get_value())).collect()
+    },
+    AxisDataType::Custom(name) => {
+      //Parse once up front, rather than for every line. Unknown or invalid channels just have no data.
+      let expression = lock(&SETTINGS).ok().and_then(|settings| {
+        settings.derived_channels.iter()
+          .find(|derived_channel| derived_channel.name == *name)
+          .and_then(|derived_channel| Expression::parse(&derived_channel.expression).ok())
+      });
+      match expression {
+        Some(expression) => data.iter().filter_map(|line| {
+          line.calculate_derived_data(&expression).map(|value| (line.unix_time, value))
+        }).collect(),
+        None => Vec::new(),
+      }
+    },
+    _ => data.iter().filter_map(|line| {
+      line.calculate_axis_data(axis_data_type.clone()).map(|value| (line.unix_time, value))
+    }).collect(),
+  }
 }
 
-fn generage_series_name(x_axis_data_type: &AxisDataType, x_axis_option: &AxisDataOption, y_axis_data_type: &AxisDataType, y_axis_option: &AxisDataOption) -> String {
-  let name_prefix = if *x_axis_data_type != AxisDataType::Time {
+fn generage_series_name(x_axis_data_type: &AxisDataType, x_axis_option: &AxisDataOption, y_axis_data_type: &AxisDataType, y_axis_option: &AxisDataOption, 
+                        y_axis_transform: Option<&SeriesTransform>) -> String {
+  let name_prefix = if !matches!(x_axis_data_type, AxisDataType::Time | AxisDataType::PeriodicTime(_)) {
     match x_axis_option {
-      // AxisDataOptions::Sample => format!("{}", x_axis_data_type.get_name()),
-      AxisDataOption::Average => format!("{} {}", x_axis_data_type.get_name(), "Avg"),
-      AxisDataOption::Minimum => format!("{} {}", x_axis_data_type.get_name(), "Min"),
-      AxisDataOption::Maximum => format!("{} {}", x_axis_data_type.get_name(), "Max"),
+      AxisDataOption::Sample => x_axis_data_type.get_name(),
+      _ => format!("{} {}", x_axis_data_type.get_name(), x_axis_option.get_name()),
     }
   } else {
     "".to_string()
   };
   let name_suffix = if *y_axis_data_type != AxisDataType::Time {
-    match y_axis_option {
-      // AxisDataOptions::Sample => format!("{}", y_axis_data_type.get_name()),
-      AxisDataOption::Average => format!("{} {}", y_axis_data_type.get_name(), "Avg"),
-      AxisDataOption::Minimum => format!("{} {}", y_axis_data_type.get_name(), "Min"),
-      AxisDataOption::Maximum => format!("{} {}", y_axis_data_type.get_name(), "Max"),
+    let name = match y_axis_option {
+      AxisDataOption::Sample => y_axis_data_type.get_name(),
+      _ => format!("{} {}", y_axis_data_type.get_name(), y_axis_option.get_name()),
+    };
+    match y_axis_transform {
+      Some(transform) => format!("{} ({})", name, transform.get_name()),
+      None => name,
     }
   } else {
     "".to_string()